@@ -2,7 +2,7 @@ use ic_cdk::{query, update};
 use system_configs_macro::has_permission_result;
 use types::{
   assets_management::ProposalId,
-  pagination::{PageRequest, PageResponse},
+  pagination::{collect_page, CursorPageRequest, CursorPageResponse, PageRequest, PageResponse},
 };
 
 use super::{
@@ -12,7 +12,7 @@ use super::{
 };
 
 #[update]
-#[has_permission_result("assets_management::proposal::create")]
+#[has_permission_result("assets_management::proposal::create", audit)]
 fn create_proposal(dto: AddProposalDto) -> Result<ProposalId, String> {
   if dto.title.is_empty() {
     return Err("Title cannot be empty".to_string());
@@ -33,7 +33,7 @@ fn create_proposal(dto: AddProposalDto) -> Result<ProposalId, String> {
 }
 
 #[update]
-#[has_permission_result("assets_management::proposal::update")]
+#[has_permission_result("assets_management::proposal::update", audit)]
 fn update_proposal(dto: UpdateProposalDto) -> Result<ProposalId, String> {
   let add_dto = &dto.add_dto;
 
@@ -64,32 +64,36 @@ fn list_proposal(request: PageRequest<ProposalListParams>) -> PageResponse<Propo
     page_size,
   } = request;
 
-  let proposals = PROPOSAL_MAP.with(|map| {
-    if status.is_none() {
-      return map.borrow().values().collect::<Vec<_>>();
-    } else {
-      let status = status.unwrap();
-      return map
-        .borrow()
-        .values()
-        .filter(|proposal| proposal.get_status().to_string() == status)
-        .collect::<Vec<_>>();
-    }
+  // Newest first, only the requested page is kept
+  let (total, records) = PROPOSAL_MAP.with(|map| {
+    let map = map.borrow();
+    let matched = map
+      .values()
+      .rev()
+      .filter(|proposal| status.as_ref().is_none_or(|status| proposal.get_status().to_string() == *status));
+    collect_page(page, page_size, matched, |proposal| ProposalVo::from_proposal(&proposal))
   });
 
-  let total = proposals.len() as u32;
-  let start = (page - 1) * page_size;
-
   PageResponse {
     total,
     page,
     page_size,
-    records: proposals
-      .iter()
-      .rev()
-      .skip(start as usize)
-      .take(page_size as usize)
-      .map(|proposal| ProposalVo::from_proposal(proposal))
-      .collect(),
+    records,
   }
 }
+
+/// Stream the proposals page by page, newest first unless ascending is requested
+#[query]
+fn list_proposal_by_cursor(request: CursorPageRequest<ProposalListParams>) -> Result<CursorPageResponse<ProposalVo>, String> {
+  let position = request.position(&["id", "created_at"])?;
+  let ProposalListParams { status } = request.params;
+
+  PROPOSAL_MAP.with(|map| {
+    Ok(position.scan_map(&map.borrow(), |proposal| {
+      status
+        .as_ref()
+        .is_none_or(|status| proposal.get_status().to_string() == *status)
+        .then(|| ProposalVo::from_proposal(proposal))
+    }))
+  })
+}
@@ -3,8 +3,11 @@ use system_configs_macro::has_permission_result;
 use types::{assets_management::ProposalId, sys::ExteralCanisterLabels};
 
 use crate::{
-  guard_keys::get_execute_proposal_guard_key, parallel_guard::EntryGuard, system_configs::get_exteral_canister_id,
+  guard_keys::get_execute_proposal_guard_key,
+  parallel_guard::EntryGuard,
+  system_configs::get_exteral_canister_id,
   transfer_address::stable_structures::TransferAddress,
+  utils::staking_canister::{nns_disburse_maturity, nns_stake_maturity, set_nns_followees, stake_to_nns_ladder_neuron},
 };
 
 use super::{
@@ -13,7 +16,7 @@ use super::{
 };
 
 #[update]
-#[has_permission_result("assets_management::proposal::execute")]
+#[has_permission_result("assets_management::proposal::execute", audit)]
 async fn execute_proposal(proposal_id: ProposalId) -> Result<u64, String> {
   if proposal_id == 0 {
     return Err("Invalid proposal ID".to_string());
@@ -55,6 +58,26 @@ async fn execute_proposal(proposal_id: ProposalId) -> Result<u64, String> {
         Err(error) => Err(format!("Transfer failed: {:?}", error)),
       }
     }
+    ProposalInstructionType::NNSLadderStake {
+      pool_id,
+      amount,
+      ladder_index,
+      dissolve_delay_seconds,
+      neuron_id: _,
+    } => {
+      let neuron_id = stake_to_nns_ladder_neuron(proposal_id, pool_id, amount, ladder_index, dissolve_delay_seconds).await?;
+
+      ic_cdk::println!(
+        "Successfully staked {} ICP to NNS neuron with ID: {}, ladder index: {}",
+        amount,
+        neuron_id,
+        ladder_index
+      );
+
+      proposal.executed_nns_stake(neuron_id)?;
+
+      Ok(neuron_id)
+    }
     ProposalInstructionType::JackpotInvestment {
       pool_id: _,
       jackpot_id: _,
@@ -75,12 +98,53 @@ async fn execute_proposal(proposal_id: ProposalId) -> Result<u64, String> {
 
       Ok(transfer_address.get_id())
     }
+    ProposalInstructionType::NNSDisburseMaturity {
+      pool_id,
+      percentage_to_disburse,
+      disbursed_amount: _,
+    } => {
+      let amount = nns_disburse_maturity(pool_id, percentage_to_disburse).await?;
+      ic_cdk::println!("Disbursed {} maturity of NNS neuron in pool {}", amount, pool_id);
+
+      proposal.executed_nns_maturity(amount)?;
+
+      Ok(amount)
+    }
+    ProposalInstructionType::NNSStakeMaturity {
+      pool_id,
+      percentage_to_stake,
+      staked_amount: _,
+    } => {
+      let amount = nns_stake_maturity(pool_id, percentage_to_stake).await?;
+      ic_cdk::println!("Staked {} maturity of NNS neuron in pool {}", amount, pool_id);
+
+      proposal.executed_nns_maturity(amount)?;
+
+      Ok(amount)
+    }
+    ProposalInstructionType::NNSSetFollowees { pool_id, topic, followees } => {
+      let failed_neuron_ids = set_nns_followees(pool_id, topic, followees).await?;
+      ic_cdk::println!("Set followees of topic {} for NNS neuron in pool {}", topic, pool_id);
+      // The staking canister keeps the followees, its NNS sync task re-applies them to the neurons that failed
+      if !failed_neuron_ids.is_empty() {
+        ic_cdk::println!(
+          "NNS neurons {:?} of pool {} failed to take the followees of topic {}, they are re-applied by the NNS sync",
+          failed_neuron_ids,
+          pool_id,
+          topic
+        );
+      }
+
+      proposal.executed_nns_set_followees()?;
+
+      Ok(pool_id)
+    }
     ProposalInstructionType::None => Err("No action needed for None instruction".to_string()),
   }
 }
 
 #[update]
-#[has_permission_result("assets_management::proposal::change_status")]
+#[has_permission_result("assets_management::proposal::change_status", audit)]
 fn change_status(proposal_id: ProposalId, status: String) -> Result<(), String> {
   if proposal_id == 0 {
     return Err("Invalid proposal ID".to_string());
@@ -57,7 +57,7 @@ impl Proposal {
     self.status = Some(ProposalStatus::Executed);
     let mut instruction = self.get_proposal_instruction();
 
-    if let ProposalInstructionType::NNSStake { .. } = instruction {
+    if let ProposalInstructionType::NNSStake { .. } | ProposalInstructionType::NNSLadderStake { .. } = instruction {
       instruction.set_neuron_id(neuron_id);
       self.proposal_instruction = Some(instruction);
       self.meta = Some(self.get_meta().update());
@@ -68,6 +68,35 @@ impl Proposal {
     }
   }
 
+  pub fn executed_nns_maturity(&mut self, amount: E8S) -> Result<(), String> {
+    self.status = Some(ProposalStatus::Executed);
+    let mut instruction = self.get_proposal_instruction();
+
+    match instruction {
+      ProposalInstructionType::NNSDisburseMaturity {
+        ref mut disbursed_amount, ..
+      } => *disbursed_amount = Some(amount),
+      ProposalInstructionType::NNSStakeMaturity { ref mut staked_amount, .. } => *staked_amount = Some(amount),
+      _ => return Err("Proposal instruction is not NNS maturity management".to_string()),
+    }
+
+    self.proposal_instruction = Some(instruction);
+    self.meta = Some(self.get_meta().update());
+    self.update_to_stable();
+    Ok(())
+  }
+
+  pub fn executed_nns_set_followees(&mut self) -> Result<(), String> {
+    if let ProposalInstructionType::NNSSetFollowees { .. } = self.get_proposal_instruction() {
+      self.status = Some(ProposalStatus::Executed);
+      self.meta = Some(self.get_meta().update());
+      self.update_to_stable();
+      Ok(())
+    } else {
+      Err("Proposal instruction is not NNSSetFollowees".to_string())
+    }
+  }
+
   pub fn executed_add_transfer_address(&mut self, transfer_address_id: u64) -> Result<(), String> {
     self.status = Some(ProposalStatus::Executed);
     let mut instruction = self.get_proposal_instruction();
@@ -158,6 +187,14 @@ pub enum ProposalInstructionType {
     amount: E8S,
     neuron_id: Option<u64>,
   },
+  /// Stake the specified amount in the specified staking pool into the neuron at the ladder index of the pool's neuron ladder
+  NNSLadderStake {
+    pool_id: StakingPoolId,
+    amount: E8S,
+    ladder_index: u32,
+    dissolve_delay_seconds: u64,
+    neuron_id: Option<u64>,
+  },
   /// Transfer the specified amount of funds in the staking pool to the jackpot account
   JackpotInvestment {
     pool_id: StakingPoolId,
@@ -174,28 +211,56 @@ pub enum ProposalInstructionType {
     address: String,
     address_type: TransferAddressType,
   },
+  /// Disburse a percentage of the maturity of the staking pool's NNS neuron to the staking pool
+  NNSDisburseMaturity {
+    pool_id: StakingPoolId,
+    percentage_to_disburse: u32,
+    disbursed_amount: Option<E8S>,
+  },
+  /// Stake a percentage of the maturity of the staking pool's NNS neuron
+  NNSStakeMaturity {
+    pool_id: StakingPoolId,
+    percentage_to_stake: u32,
+    staked_amount: Option<E8S>,
+  },
+  /// Set the followees of the staking pool's NNS neuron for the topic, an empty list clears the topic
+  NNSSetFollowees {
+    pool_id: StakingPoolId,
+    topic: i32,
+    followees: Vec<u64>,
+  },
 }
 
 impl ProposalInstructionType {
   pub fn get_pool_id(&self) -> StakingPoolId {
     match self {
       ProposalInstructionType::NNSStake { pool_id, .. } => *pool_id,
+      ProposalInstructionType::NNSLadderStake { pool_id, .. } => *pool_id,
       ProposalInstructionType::JackpotInvestment { pool_id, .. } => *pool_id,
+      ProposalInstructionType::NNSDisburseMaturity { pool_id, .. } => *pool_id,
+      ProposalInstructionType::NNSStakeMaturity { pool_id, .. } => *pool_id,
+      ProposalInstructionType::NNSSetFollowees { pool_id, .. } => *pool_id,
       ProposalInstructionType::None => StakingPoolId::default(),
       ProposalInstructionType::AddTransferAddress { .. } => StakingPoolId::default(),
     }
   }
 
   pub fn set_neuron_id(&mut self, neuron_id: u64) {
-    if let ProposalInstructionType::NNSStake { neuron_id: ref mut id, .. } = self {
-      *id = Some(neuron_id);
+    match self {
+      ProposalInstructionType::NNSStake { neuron_id: ref mut id, .. } => *id = Some(neuron_id),
+      ProposalInstructionType::NNSLadderStake { neuron_id: ref mut id, .. } => *id = Some(neuron_id),
+      _ => {}
     }
   }
 
   pub fn get_amount(&self) -> E8S {
     match self {
       ProposalInstructionType::NNSStake { amount, .. } => *amount,
+      ProposalInstructionType::NNSLadderStake { amount, .. } => *amount,
       ProposalInstructionType::JackpotInvestment { amount, .. } => *amount,
+      ProposalInstructionType::NNSDisburseMaturity { disbursed_amount, .. } => disbursed_amount.unwrap_or_default(),
+      ProposalInstructionType::NNSStakeMaturity { staked_amount, .. } => staked_amount.unwrap_or_default(),
+      ProposalInstructionType::NNSSetFollowees { .. } => 0,
       ProposalInstructionType::None => 0,
       ProposalInstructionType::AddTransferAddress { .. } => 0,
     }
@@ -17,6 +17,7 @@ use ic_stable_structures::{memory_manager::MemoryManager, DefaultMemoryImpl};
 pub mod dao;
 pub mod guard_keys;
 pub mod memory_ids;
+pub mod migrations;
 pub mod transfer_address;
 pub mod utils;
 
@@ -24,6 +25,17 @@ thread_local! {
   static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
 }
 
+#[ic_cdk::init]
+fn init() {
+  migrations::SCHEMA_MIGRATOR.mark_latest();
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+  system_configs::restore_system_configs();
+  migrations::SCHEMA_MIGRATOR.run_on_upgrade();
+}
+
 use candid::Principal;
 use dao::proposal::transport_structures::AddProposalDto;
 use dao::proposal::transport_structures::ProposalListParams;
@@ -31,9 +43,20 @@ use dao::proposal::transport_structures::ProposalVo;
 use dao::proposal::transport_structures::UpdateProposalDto;
 use transfer_address::transfer_structures::TransferAddressVo;
 use types::assets_management::ProposalId;
+use types::pagination::CursorPageRequest;
+use types::pagination::CursorPageResponse;
 use types::pagination::PageRequest;
 use types::pagination::PageResponse;
+use types::sys::audit::AdminAuditEntryVo;
+use types::sys::audit::AdminAuditQueryParams;
+use types::sys::config::PermissionExplanationVo;
+use types::sys::config::RolePermissionVo;
 use types::sys::config::SystemConfig;
+use types::sys::config::SystemConfigStatusVo;
 use types::sys::config::UserRolePermissionVo;
+use types::sys::guard::EntryGuardVo;
+use types::sys::identity_mapping::IdentityMappingDelta;
+use types::sys::identity_mapping::IdentityMappingEventVo;
+use types::sys::migration::SchemaMigrationStatusVo;
 
 ic_cdk::export_candid!();
@@ -0,0 +1,31 @@
+use std::cell::RefCell;
+
+use ic_stable_structures::{memory_manager::MemoryId, Cell};
+use types::sys::{
+  migration::{SchemaMigrationState, SchemaMigrationStateCell, SchemaMigrationStatusVo, SchemaMigrator},
+  SCHEMA_MIGRATION_MEMORY_ID,
+};
+
+use crate::MEMORY_MANAGER;
+
+thread_local! {
+  /// Schema version of the assets management stable memory
+  static SCHEMA_MIGRATION_STATE: RefCell<SchemaMigrationStateCell> = RefCell::new(
+    Cell::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(SCHEMA_MIGRATION_MEMORY_ID))),
+      SchemaMigrationState::default(),
+    ).unwrap()
+  );
+}
+
+/// Migrations of the assets management stable memory, new steps are appended with the next version
+pub static SCHEMA_MIGRATOR: SchemaMigrator = SchemaMigrator {
+  state: &SCHEMA_MIGRATION_STATE,
+  steps: &[],
+};
+
+/// Query the schema version and the progress of the running migration
+#[ic_cdk::query]
+fn get_schema_migration_status() -> SchemaMigrationStatusVo {
+  SCHEMA_MIGRATOR.status()
+}
@@ -14,3 +14,54 @@ pub async fn stake_to_nns_neuron(pool_id: u64, amount: u64) -> Result<u64, Strin
 
   result
 }
+
+pub async fn stake_to_nns_ladder_neuron(
+  proposal_id: u64,
+  pool_id: u64,
+  amount: u64,
+  ladder_index: u32,
+  dissolve_delay_seconds: u64,
+) -> Result<u64, String> {
+  let staking_canister_id = get_exteral_canister_id(ExteralCanisterLabels::Staking);
+
+  ic_cdk::call::Call::unbounded_wait(staking_canister_id, "stake_to_nns_ladder_neuron")
+    .with_args(&(proposal_id, pool_id, amount, ladder_index, dissolve_delay_seconds))
+    .await
+    .map_err(|e| format!("Call staking method stake_to_nns_ladder_neuron failed: {:?}", e))?
+    .candid::<Result<u64, String>>()
+    .map_err(|e| format!("Candid decoding failed: {:?}", e))?
+}
+
+pub async fn nns_disburse_maturity(pool_id: u64, percentage_to_disburse: u32) -> Result<u64, String> {
+  let staking_canister_id = get_exteral_canister_id(ExteralCanisterLabels::Staking);
+
+  ic_cdk::call::Call::unbounded_wait(staking_canister_id, "nns_disburse_maturity")
+    .with_args(&(pool_id, percentage_to_disburse))
+    .await
+    .map_err(|e| format!("Call staking method nns_disburse_maturity failed: {:?}", e))?
+    .candid::<Result<u64, String>>()
+    .map_err(|e| format!("Candid decoding failed: {:?}", e))?
+}
+
+pub async fn nns_stake_maturity(pool_id: u64, percentage_to_stake: u32) -> Result<u64, String> {
+  let staking_canister_id = get_exteral_canister_id(ExteralCanisterLabels::Staking);
+
+  ic_cdk::call::Call::unbounded_wait(staking_canister_id, "nns_stake_maturity")
+    .with_args(&(pool_id, percentage_to_stake))
+    .await
+    .map_err(|e| format!("Call staking method nns_stake_maturity failed: {:?}", e))?
+    .candid::<Result<u64, String>>()
+    .map_err(|e| format!("Candid decoding failed: {:?}", e))?
+}
+
+/// Set the followees of the pool neurons for the topic, returns the neurons that failed to take them
+pub async fn set_nns_followees(pool_id: u64, topic: i32, followees: Vec<u64>) -> Result<Vec<u64>, String> {
+  let staking_canister_id = get_exteral_canister_id(ExteralCanisterLabels::Staking);
+
+  ic_cdk::call::Call::unbounded_wait(staking_canister_id, "set_nns_followees")
+    .with_args(&(pool_id, topic, followees))
+    .await
+    .map_err(|e| format!("Call staking method set_nns_followees failed: {:?}", e))?
+    .candid::<Result<Vec<u64>, String>>()
+    .map_err(|e| format!("Candid decoding failed: {:?}", e))?
+}
@@ -10,12 +10,14 @@ use types::{
 
 use crate::{
   account::{badge_utils::remove_staker_badge, crud_utils::delete_staking_account, stable_structures::StakingAccountRecoverableError},
+  errors::StakingError,
   event_log::{
     stake_and_unstake_events::{save_dissolve_event, save_stake_event, save_unstake_event},
     staking_account_events::save_create_staking_account_event_log,
     transfer_events::{
       save_dissolve_pay_center_receive_fail_event, save_dissolve_pay_center_receive_ok_event, save_dissolve_pay_center_receive_start_event,
-      save_dissolve_pay_center_transfer_ok_event, save_dissolve_pay_center_transfer_start_event, save_stake_pay_center_transfer_fail_event,
+      save_dissolve_pay_center_transfer_ok_event, save_dissolve_pay_center_transfer_start_event, save_dissolve_wallet_transfer_fail_event,
+      save_dissolve_wallet_transfer_ok_event, save_dissolve_wallet_transfer_start_event, save_stake_pay_center_transfer_fail_event,
       save_stake_pay_center_transfer_ok_event, save_stake_pay_center_transfer_start_event, save_stake_transfer_fail_event,
       save_stake_transfer_ok_event, save_stake_transfer_start_event, save_unstake_penalty_pay_center_ok_event,
       save_unstake_penalty_pay_center_start_event, save_unstake_penalty_transfer_fail_event, save_unstake_penalty_transfer_ok_event,
@@ -24,8 +26,9 @@ use crate::{
   },
   guard_keys::{get_dissolve_guard_key, get_stake_guard_key, get_unstake_guard_key},
   on_chain::transfer::{
-    transfer_from_staking_account_to_pay_center, transfer_from_staking_account_to_staking_pool, transfer_from_staking_pool_to_pay_center,
-    transfer_from_staking_pool_to_staking_account,
+    check_wallet_allowance, get_pool_ledger, is_transfer_failed, transfer_from_staking_account_to_pay_center,
+    transfer_from_staking_account_to_staking_pool, transfer_from_staking_account_to_wallet, transfer_from_staking_pool_to_pay_center,
+    transfer_from_staking_pool_to_staking_account, transfer_from_wallet_to_staking_pool, transfer_op_key, TRANSFER_SCENE_STAKE_APPROVE,
   },
   parallel_guard::EntryGuard,
   pool::{crud_utils::query_staking_pool_by_id, stable_structures::StakingPool},
@@ -39,7 +42,7 @@ use super::{
     recover_dissolve::recover_dissolve_error,
     recover_early_unstake::{recover_unstake_penalty_onchain_error, recover_unstake_penalty_pay_center_error},
   },
-  stable_structures::{StakingAccount, StakingAccountStatus},
+  stable_structures::{StakeMode, StakingAccount, StakingAccountStatus},
   transport_structures::StakingAccountVo,
   STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP,
 };
@@ -50,17 +53,23 @@ const ONE_HUNDRED_AND_EIGHTY_DAYS_OF_NANOSECONDS: u64 = 180 * 24 * 60 * 60 * 1_0
 /// User initiates a stake request
 #[ic_cdk::update]
 async fn stake(dto: StakeDto) -> Result<StakingAccountVo, String> {
+  stake_v2(dto).await.map_err(|e| e.to_string())
+}
+
+/// User initiates a stake request, failures are returned as a structured error
+#[ic_cdk::update]
+async fn stake_v2(dto: StakeDto) -> Result<StakingAccountVo, StakingError> {
   let caller: Principal = crate::identity_mapping::wl_caller();
 
   // Anonymous users cannot initiate stake requests
   if caller == Principal::anonymous() {
-    return Err("Anonymous user cannot stake".to_string());
+    return Err(StakingError::AnonymousCaller);
   }
 
   // Reentry protection
   let _entry_guard = EntryGuard::new(get_stake_guard_key(caller.to_string())).map_err(|_| {
     ic_cdk::println!("Stake entry guard failed");
-    "You already have a stake in progress, please do not repeat the operation!".to_string()
+    StakingError::already_processing("You already have a stake in progress, please do not repeat the operation!")
   })?;
 
   let StakeDto {
@@ -69,7 +78,7 @@ async fn stake(dto: StakeDto) -> Result<StakingAccountVo, String> {
     staking_days,
   } = dto;
 
-  let mut staking_pool = query_staking_pool_by_id(pool_id)?;
+  let mut staking_pool = query_staking_pool_by_id(pool_id).map_err(|_| StakingError::PoolNotFound { pool_id })?;
 
   // check term of the staking pool
   let term_config = staking_pool.get_term_config();
@@ -120,7 +129,7 @@ async fn stake(dto: StakeDto) -> Result<StakingAccountVo, String> {
       delete_staking_account(&account.get_id())?;
       staking_pool.restore_locked_size(staking_amount)?;
 
-      return Err("A system error has occurred. Please try again. ".to_string());
+      return Err(StakingError::PayCenterUnavailable { message: error_message });
     }
   };
 
@@ -144,7 +153,7 @@ async fn stake(dto: StakeDto) -> Result<StakingAccountVo, String> {
       delete_staking_account(&account.get_id())?;
       staking_pool.restore_locked_size(staking_amount)?;
 
-      return Err("A system error has occurred. Please try again. ".to_string());
+      return Err(StakingError::PayCenterRejected { message: error_message });
     }
   };
 
@@ -176,7 +185,10 @@ async fn stake(dto: StakeDto) -> Result<StakingAccountVo, String> {
           stake_pay_center_tx_id,
         ));
 
-        return Err("A system error has occurred. Please try again. ".to_string());
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: e,
+        });
       }
     };
 
@@ -194,44 +206,161 @@ async fn stake(dto: StakeDto) -> Result<StakingAccountVo, String> {
   Ok(StakingAccountVo::from_staking_account(&account))
 }
 
+/// User initiates a stake request with an ICRC-2 approve
+/// The wallet approves the staking canister the stake amount plus three handling fees of the pool's token, and the stake is pulled directly into the stake pool
+#[ic_cdk::update]
+async fn stake_with_approve(dto: StakeDto) -> Result<StakingAccountVo, String> {
+  stake_with_approve_v2(dto).await.map_err(|e| e.to_string())
+}
+
+/// User initiates a stake request with an ICRC-2 approve, failures are returned as a structured error
+#[ic_cdk::update]
+async fn stake_with_approve_v2(dto: StakeDto) -> Result<StakingAccountVo, StakingError> {
+  let caller: Principal = crate::identity_mapping::wl_caller();
+  // The allowance is approved by the wallet itself
+  let wallet = ic_cdk::api::msg_caller();
+
+  // Anonymous users cannot initiate stake requests
+  if caller == Principal::anonymous() {
+    return Err(StakingError::AnonymousCaller);
+  }
+
+  // Reentry protection
+  let _entry_guard = EntryGuard::new(get_stake_guard_key(caller.to_string())).map_err(|_| {
+    ic_cdk::println!("Stake entry guard failed");
+    StakingError::already_processing("You already have a stake in progress, please do not repeat the operation!")
+  })?;
+
+  let StakeDto {
+    pool_id,
+    staking_amount,
+    staking_days,
+  } = dto;
+
+  let mut staking_pool = query_staking_pool_by_id(pool_id).map_err(|_| StakingError::PoolNotFound { pool_id })?;
+
+  // check term of the staking pool
+  let term_config = staking_pool.get_term_config();
+  term_config.validate_term(staking_days)?;
+
+  // check staking amount
+  let limit_config = staking_pool.get_limit_config();
+  let current_user_in_stake_accounts = query_current_user_in_stake_accounts(pool_id);
+  limit_config.validate_stake_amount(staking_amount, &current_user_in_stake_accounts)?;
+
+  // The wallet has to approve the stake amount plus the handling fees
+  check_wallet_allowance(wallet, pool_id, staking_amount).await?;
+
+  // Verify and lock the stake pool amount
+  staking_pool = staking_pool.validate_and_lock_size(staking_amount)?;
+
+  // Create a staked account
+  let mut account = StakingAccount::from_stake_dto_and_pool_with_mode(&dto, &staking_pool, StakeMode::Icrc2Approve(wallet))?;
+
+  // Save the staked account to stable memory
+  save_stake_account_to_stable_memory(&account)?;
+  // Save the event log of the new stake account
+  save_create_staking_account_event_log(&account);
+
+  // stake：Event log of transfers from the wallet to stake pools-start
+  save_stake_transfer_start_event(account.get_id(), staking_pool.get_id());
+
+  let wallet_to_pool_tx_id =
+    match transfer_from_wallet_to_staking_pool(wallet, account.get_id(), staking_pool.get_id(), account.get_staked_amount()).await {
+      Ok(tx_id) => {
+        ic_cdk::println!("Transfer from wallet to pool success: {}", tx_id);
+
+        // stake：Transfer Event Log from the wallet to stake Pool-success
+        save_stake_transfer_ok_event(account.get_id(), staking_pool.get_id(), tx_id);
+
+        tx_id
+      }
+      Err(e) => {
+        ic_cdk::println!("Transfer from wallet to pool failed: {:?}", e);
+
+        // stake：Transfer Event Log from the wallet to stake Pool-fail
+        save_stake_transfer_fail_event(account.get_id(), staking_pool.get_id(), e.clone());
+
+        if is_transfer_failed(&transfer_op_key(TRANSFER_SCENE_STAKE_APPROVE, account.get_id())) {
+          // The ledger rejected the transfer, nothing was pulled from the wallet
+          delete_staking_account(&account.get_id())?;
+          staking_pool.restore_locked_size(staking_amount)?;
+
+          return Err(StakingError::ledger_transfer_failed(e));
+        }
+
+        // The outcome is unknown, the recovery task looks the transfer up in the ledger
+        account.stable_to_recoverable_error(StakingAccountRecoverableError::StakeTransferFromWalletFailed);
+
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: e,
+        });
+      }
+    };
+
+  // Update the status of the staked account，and save to stable memory
+  account.change_to_in_stake(0, 0, wallet_to_pool_tx_id);
+  save_stake_account_to_stable_memory(&account)?;
+  // Add account index based on expiration date
+  STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP.with(|map| add_indexed_id(map, &YearMonthDay::from(account.get_stake_deadline()), account.get_id()));
+  // Update the available amount of the stake pool
+  let updated_pool = staking_pool.add_stake_account(&account, &current_user_in_stake_accounts)?;
+
+  // Save stake events，When the stake is issued，The stake pool and stake account will be updated at the same time
+  save_stake_event(&updated_pool, &account);
+
+  Ok(StakingAccountVo::from_staking_account(&account))
+}
+
 /// Manually initiate a request to unstake，There will be a handling fee here
 #[ic_cdk::update]
 async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo, String> {
+  early_unstake_v2(account_id).await.map_err(|e| e.to_string())
+}
+
+/// Manually initiate a request to unstake, failures are returned as a structured error
+#[ic_cdk::update]
+async fn early_unstake_v2(account_id: StakingAccountId) -> Result<StakingAccountVo, StakingError> {
   let caller: Principal = crate::identity_mapping::wl_caller();
 
   if caller == Principal::anonymous() {
-    return Err("Anonymous user cannot stake".to_string());
+    return Err(StakingError::AnonymousCaller);
   }
 
   // Reentry protection
   let _entry_guard = EntryGuard::new(get_unstake_guard_key(account_id)).map_err(|_| {
     ic_cdk::println!("Stake entry guard failed");
-    "The current staking account is in the process of unstaking, please do not repeat the operation!".to_string()
+    StakingError::already_processing("The current staking account is in the process of unstaking, please do not repeat the operation!")
   })?;
 
   let user_id = caller.to_string();
 
   // Query staked account
-  let account = StakingAccount::query_by_id(account_id)?;
+  let account = StakingAccount::query_by_id(account_id).map_err(|_| StakingError::AccountNotFound { account_id })?;
 
   // Verify the owner of the staked account
   if account.get_owner() != user_id {
-    return Err("The caller is not the owner of the staking account".to_string());
+    return Err(StakingError::NotAccountOwner { account_id });
   }
 
   // Verify the status of the staked account
   if account.get_status() != StakingAccountStatus::InStake {
-    return Err("The staking account is not in stake".to_string());
+    return Err(StakingError::AccountStatusInvalid {
+      account_id,
+      status: account.get_status().to_string(),
+      expected_status: StakingAccountStatus::InStake.to_string(),
+    });
   }
 
   match account.recoverable_error {
     Some(StakingAccountRecoverableError::EarlyUnstakePenaltyOnChainFailed(_, _, _)) => {
       // If the current staked account status is a restored error status，Indicates that the on-chain transfer has been completed，Payment Center failed to bookkeeping，Therefore, the error recovery process is directly followed
-      return recover_unstake_penalty_onchain_error(&account).await;
+      return recover_unstake_penalty_onchain_error(&account).await.map_err(StakingError::from);
     }
     Some(StakingAccountRecoverableError::EarlyUnstakePenaltyPayCenterFailed(_, _, _, _)) => {
       // If the current staked account status is a restored error status，Indicates that the on-chain transfer has been completed，Payment Center failed to bookkeeping，Therefore, the error recovery process is directly followed
-      return recover_unstake_penalty_pay_center_error(&account).await;
+      return recover_unstake_penalty_pay_center_error(&account).await.map_err(StakingError::from);
     }
     _ => {}
   };
@@ -241,10 +370,11 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
 
   // Check if account can be unstake at this time
   if now < account.get_can_early_unstake_time() {
-    return Err(format!(
-      "The staking account cannot be released within {} days.",
-      account.get_min_early_unstake_days()
-    ));
+    return Err(StakingError::EarlyUnstakeTooSoon {
+      account_id,
+      min_early_unstake_days: account.get_min_early_unstake_days(),
+      can_early_unstake_time: account.get_can_early_unstake_time(),
+    });
   }
 
   // Calculate the penalty amount
@@ -257,8 +387,8 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
   }
   .unwrap_or_default();
 
-  // If the penalty amount is less than or equal to 10,000(0.0001ICP), then set to 0
-  if penalty_amount <= 10_000 {
+  // If the penalty amount does not cover the handling fee of the penalty transfer, then set to 0
+  if penalty_amount <= get_pool_ledger(account.get_pool_id())?.get_fee() {
     penalty_amount = 0;
   }
 
@@ -292,7 +422,7 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
         // Unstake：Transfer Event Log from stake Pool to stake Account-fail
         save_unstake_transfer_fail_event(account.get_id(), account.get_pool_id(), e.clone());
 
-        return Err("A system error has occurred. Please try again. ".to_string());
+        return Err(StakingError::ledger_transfer_failed(e));
       }
     }
   } else {
@@ -305,7 +435,7 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
     save_unstake_penalty_transfer_start_event(account.get_id(), account.get_pool_id());
 
     // Execute unstake penalty fee on-chain transfer
-    let penalty_onchain_tx_id = match transfer_from_staking_pool_to_pay_center(account.get_pool_id(), penalty_amount).await {
+    let penalty_onchain_tx_id = match transfer_from_staking_pool_to_pay_center(account.get_pool_id(), account.get_id(), penalty_amount).await {
       Ok(tx_id) => {
         ic_cdk::println!("On-chain transfer success: {}", tx_id);
 
@@ -321,7 +451,10 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
         save_unstake_penalty_transfer_fail_event(account.get_id(), account.get_pool_id(), e.clone());
         account.stable_to_recoverable_error(StakingAccountRecoverableError::EarlyUnstakePenaltyOnChainFailed(unstake_tx_id, now, penalty_amount));
 
-        return Err(format!("On-chain transfer failed: {}", e));
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: format!("On-chain transfer failed: {}", e),
+        });
       }
     };
 
@@ -351,7 +484,10 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
           penalty_amount,
         ));
 
-        return Err(error_message);
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: error_message,
+        });
       }
     };
 
@@ -378,7 +514,10 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
           penalty_amount,
         ));
 
-        return Err(error_message);
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: error_message,
+        });
       }
     };
 
@@ -395,7 +534,7 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
     Ok(pool) => pool,
     Err(e) => {
       ic_cdk::println!("Staking pool unstake failed: {:?}", e);
-      return Err(e);
+      return Err(e.into());
     }
   };
 
@@ -405,7 +544,7 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
       Ok(account) => account,
       Err(e) => {
         ic_cdk::println!("Staking account change to unstake failed: {:?}", e);
-        return Err(e);
+        return Err(e.into());
       }
     };
 
@@ -426,36 +565,51 @@ async fn early_unstake(account_id: StakingAccountId) -> Result<StakingAccountVo,
 
 #[ic_cdk::update]
 async fn dissolve(account_id: StakingAccountId) -> Result<StakingAccountVo, String> {
+  dissolve_v2(account_id).await.map_err(|e| e.to_string())
+}
+
+/// Dissolve a released staking account, failures are returned as a structured error
+#[ic_cdk::update]
+async fn dissolve_v2(account_id: StakingAccountId) -> Result<StakingAccountVo, StakingError> {
   let caller: Principal = crate::identity_mapping::wl_caller();
 
   if caller == Principal::anonymous() {
-    return Err("Anonymous user cannot stake".to_string());
+    return Err(StakingError::AnonymousCaller);
   }
 
   // Reentry protection
   let _entry_guard = EntryGuard::new(get_dissolve_guard_key(account_id)).map_err(|_| {
     ic_cdk::println!("Stake entry guard failed");
-    "The current staking account is being dissolved, please do not repeat the operation!".to_string()
+    StakingError::already_processing("The current staking account is being dissolved, please do not repeat the operation!")
   })?;
 
   let user_id = caller.to_string();
 
   // Query staked account
-  let account = StakingAccount::query_by_id(account_id)?;
+  let account = StakingAccount::query_by_id(account_id).map_err(|_| StakingError::AccountNotFound { account_id })?;
 
   // Verify the owner of the staked account
   if account.get_owner() != user_id {
-    return Err("The caller is not the owner of the staking account".to_string());
+    return Err(StakingError::NotAccountOwner { account_id });
   }
 
   // Verify the status of the staked account，Only accounts that have been de-staked can be dissolved
   if account.get_status() != StakingAccountStatus::Released {
-    return Err("The staking account is not released".to_string());
+    return Err(StakingError::AccountStatusInvalid {
+      account_id,
+      status: account.get_status().to_string(),
+      expected_status: StakingAccountStatus::Released.to_string(),
+    });
   }
 
   // If the current staked account status is a restored error status，Indicates that the on-chain transfer has been completed，Payment Center failed to bookkeeping，Therefore, the error recovery process is directly followed
   if let Some(StakingAccountRecoverableError::DissolvePayCenterFailed(dissolve_tx_id)) = account.recoverable_error {
-    return recover_dissolve_error(&account, dissolve_tx_id).await;
+    return recover_dissolve_error(&account, dissolve_tx_id).await.map_err(StakingError::from);
+  }
+
+  // A stake with approve is returned to the wallet it was pulled from, without the payment center
+  if let StakeMode::Icrc2Approve(wallet) = account.get_stake_mode() {
+    return dissolve_to_wallet(&account, wallet).await;
   }
 
   // Initiate a dissolving payment request from the payment center
@@ -488,7 +642,7 @@ async fn dissolve(account_id: StakingAccountId) -> Result<StakingAccountVo, Stri
         // stake dissolve：Transfer from the stake account to the payment center on-chain fail
         save_stake_pay_center_transfer_fail_event(account.get_id(), pay_center_canister_id.to_string(), e.clone());
 
-        return Err("A system error has occurred. Please try again. ".to_string());
+        return Err(StakingError::ledger_transfer_failed(e));
       }
     };
 
@@ -517,7 +671,10 @@ async fn dissolve(account_id: StakingAccountId) -> Result<StakingAccountVo, Stri
         // Record the dissolution time，Recoverable status of payment center failure
         account.stable_to_recoverable_error(StakingAccountRecoverableError::DissolvePayCenterFailed(dissolve_tx_id));
 
-        return Err("A system error has occurred. Please try again. ".to_string());
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: error_message,
+        });
       }
     };
 
@@ -541,7 +698,10 @@ async fn dissolve(account_id: StakingAccountId) -> Result<StakingAccountVo, Stri
         // Record the dissolution time，Recoverable status of payment center failure
         account.stable_to_recoverable_error(StakingAccountRecoverableError::DissolvePayCenterFailed(dissolve_tx_id));
 
-        return Err("A system error has occurred. Please try again. ".to_string());
+        return Err(StakingError::RecoverableErrorPending {
+          account_id: account.get_id(),
+          message: error_message,
+        });
       }
     };
     (dissolve_tx_id, pay_center_tx_id)
@@ -552,7 +712,7 @@ async fn dissolve(account_id: StakingAccountId) -> Result<StakingAccountVo, Stri
     Ok(account) => account,
     Err(e) => {
       ic_cdk::println!("Staking account change to dissolve failed: {}", e);
-      return Err(e);
+      return Err(e.into());
     }
   };
 
@@ -562,28 +722,77 @@ async fn dissolve(account_id: StakingAccountId) -> Result<StakingAccountVo, Stri
   Ok(StakingAccountVo::from_staking_account(&account))
 }
 
+/// Dissolve a staked account funded with approve, the released funds are transferred back to the wallet
+async fn dissolve_to_wallet(account: &StakingAccount, wallet: Principal) -> Result<StakingAccountVo, StakingError> {
+  let dissolve_tx_id = if account.get_released_amount() == 0 {
+    // Unstake the zero amount，No on-chain transfer is required
+    ic_cdk::println!("The released amount is 0, no need to transfer on-chain");
+    0
+  } else {
+    // stake dissolve：Transfer from the stake account to the wallet on-chain
+    save_dissolve_wallet_transfer_start_event(account.get_id(), wallet.to_string());
+
+    match transfer_from_staking_account_to_wallet(account.get_id(), wallet, account.get_released_amount()).await {
+      Ok(tx_id) => {
+        ic_cdk::println!("On-chain transfer success: {}", tx_id);
+
+        // stake dissolve：Transfer from the stake account to the wallet on-chain success
+        save_dissolve_wallet_transfer_ok_event(account.get_id(), wallet.to_string(), tx_id);
+
+        tx_id
+      }
+      Err(e) => {
+        ic_cdk::println!("On-chain transfer failed: {:?}", e);
+
+        // stake dissolve：Transfer from the stake account to the wallet on-chain fail
+        save_dissolve_wallet_transfer_fail_event(account.get_id(), wallet.to_string(), e.clone());
+
+        return Err(StakingError::ledger_transfer_failed(e));
+      }
+    }
+  };
+
+  // Update the status of the staked account，and save to stable memory
+  let updated_account = account.change_to_dissolved(dissolve_tx_id, 0)?;
+
+  // Save the event log of the dissolved account
+  save_dissolve_event(&updated_account);
+
+  Ok(StakingAccountVo::from_staking_account(&updated_account))
+}
+
 /// Pre-resolution inspection of staked accounts
 #[ic_cdk::query]
 fn early_unstake_pre_check(account_id: StakingAccountId) -> Result<EarlyUnstakePreCheckVo, String> {
+  early_unstake_pre_check_v2(account_id).map_err(|e| e.to_string())
+}
+
+/// Pre-resolution inspection of staked accounts, failures are returned as a structured error
+#[ic_cdk::query]
+fn early_unstake_pre_check_v2(account_id: StakingAccountId) -> Result<EarlyUnstakePreCheckVo, StakingError> {
   let caller: Principal = crate::identity_mapping::wl_caller();
 
   if caller == Principal::anonymous() {
-    return Err("Anonymous user cannot stake".to_string());
+    return Err(StakingError::AnonymousCaller);
   }
 
   let user_id = caller.to_string();
 
   // Query staked account
-  let account = StakingAccount::query_by_id(account_id)?;
+  let account = StakingAccount::query_by_id(account_id).map_err(|_| StakingError::AccountNotFound { account_id })?;
 
   // Verify the owner of the staked account
   if account.get_owner() != user_id {
-    return Err("The caller is not the owner of the staking account".to_string());
+    return Err(StakingError::NotAccountOwner { account_id });
   }
 
   // Verify the status of the staked account
   if account.get_status() != StakingAccountStatus::InStake {
-    return Err("The staking account is not in stake".to_string());
+    return Err(StakingError::AccountStatusInvalid {
+      account_id,
+      status: account.get_status().to_string(),
+      expected_status: StakingAccountStatus::InStake.to_string(),
+    });
   }
 
   let now = ic_cdk::api::time();
@@ -591,10 +800,11 @@ fn early_unstake_pre_check(account_id: StakingAccountId) -> Result<EarlyUnstakeP
 
   // Check if account can be unstake at this time
   if now < account.get_can_early_unstake_time() {
-    return Err(format!(
-      "The staking account cannot be released within {} days.",
-      account.get_min_early_unstake_days()
-    ));
+    return Err(StakingError::EarlyUnstakeTooSoon {
+      account_id,
+      min_early_unstake_days: account.get_min_early_unstake_days(),
+      can_early_unstake_time: account.get_can_early_unstake_time(),
+    });
   }
 
   // Calculate the penalty amount
@@ -607,8 +817,8 @@ fn early_unstake_pre_check(account_id: StakingAccountId) -> Result<EarlyUnstakeP
   }
   .unwrap_or_default();
 
-  // If the penalty amount is less than or equal to 10,000(0.0001ICP), then set to 0
-  if penalty_amount <= 10_000 {
+  // If the penalty amount does not cover the handling fee of the penalty transfer, then set to 0
+  if penalty_amount <= get_pool_ledger(account.get_pool_id())?.get_fee() {
     penalty_amount = 0;
   }
 
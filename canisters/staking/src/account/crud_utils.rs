@@ -1,17 +1,89 @@
 use candid::Principal;
 use types::{
+  composite_entity_index,
   date::YearMonthDay,
-  entities::{add_indexed_id, get_indexed_ids, remove_indexed_id},
+  entities::{self, get_indexed_ids},
   staking::{StakingAccountId, StakingPoolId},
+  EntityId, UserId,
 };
 
-use crate::event_log::staking_account_events::save_delete_staking_account_event_log;
+use crate::{
+  event_log::staking_account_events::save_delete_staking_account_event_log,
+  migrations::{ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION, SCHEMA_MIGRATOR},
+  on_chain::address::{index_staking_account_address, remove_staking_account_address},
+};
 
 use super::{
   stable_structures::{StakingAccount, StakingAccountStatus},
-  STAKING_ACCOUNT_MAP, STAKING_POOL_ACCOUNT_INDEX_MAP, STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP, STAKING_USER_ACCOUNT_INDEX_MAP,
+  LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP, LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP, STAKING_ACCOUNT_MAP, STAKING_POOL_ACCOUNT_INDEX_MAP,
+  STAKING_STATUS_ACCOUNT_INDEX_MAP, STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP, STAKING_USER_ACCOUNT_INDEX_MAP,
 };
 
+/// Add the staking account to the pool and user indexes, the vector indexes are kept in step until the composite indexes are built
+pub fn add_staking_account_indexes(pool_id: StakingPoolId, owner: &UserId, account_id: StakingAccountId) {
+  STAKING_POOL_ACCOUNT_INDEX_MAP.with(|index_map| composite_entity_index::add_indexed_id(index_map, &pool_id, account_id));
+  STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| composite_entity_index::add_indexed_id(index_map, owner, account_id));
+
+  if !SCHEMA_MIGRATOR.is_migrated_to(ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION) {
+    LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP.with(|index_map| entities::add_indexed_id(index_map, &pool_id, account_id));
+    LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| entities::add_indexed_id(index_map, owner, account_id));
+  }
+}
+
+/// Remove the staking account from the pool and user indexes
+fn remove_staking_account_indexes(pool_id: StakingPoolId, owner: &UserId, account_id: StakingAccountId) {
+  STAKING_POOL_ACCOUNT_INDEX_MAP.with(|index_map| composite_entity_index::remove_indexed_id(index_map, &pool_id, account_id));
+  STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| composite_entity_index::remove_indexed_id(index_map, owner, account_id));
+
+  if !SCHEMA_MIGRATOR.is_migrated_to(ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION) {
+    LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP.with(|index_map| entities::remove_indexed_id(index_map, &pool_id, account_id));
+    LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| entities::remove_indexed_id(index_map, owner, account_id));
+  }
+}
+
+/// Get the staking account ids of the user in ascending order
+pub fn get_user_account_ids(user_id: &UserId) -> Vec<EntityId> {
+  if SCHEMA_MIGRATOR.is_migrated_to(ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION) {
+    STAKING_USER_ACCOUNT_INDEX_MAP.with(|m| composite_entity_index::get_indexed_ids(m, user_id))
+  } else {
+    LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP.with(|m| entities::get_indexed_ids(m, user_id))
+  }
+}
+
+/// Get the staking account ids of the staking pool in ascending order
+pub fn get_pool_account_ids(pool_id: &StakingPoolId) -> Vec<EntityId> {
+  if SCHEMA_MIGRATOR.is_migrated_to(ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION) {
+    STAKING_POOL_ACCOUNT_INDEX_MAP.with(|m| composite_entity_index::get_indexed_ids(m, pool_id))
+  } else {
+    LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP.with(|m| entities::get_indexed_ids(m, pool_id))
+  }
+}
+
+/// Move the staking account in the status index from its previous status to its current status
+pub fn index_staking_account_status(
+  account_id: StakingAccountId,
+  previous_status: Option<StakingAccountStatus>,
+  status: Option<StakingAccountStatus>,
+) {
+  if previous_status == status {
+    return;
+  }
+
+  STAKING_STATUS_ACCOUNT_INDEX_MAP.with(|map| {
+    if let Some(previous_status) = previous_status {
+      composite_entity_index::remove_indexed_id(map, &previous_status.to_string(), account_id);
+    }
+    if let Some(status) = status {
+      composite_entity_index::add_indexed_id(map, &status.to_string(), account_id);
+    }
+  });
+}
+
+/// Query the ids of the staking accounts in the status
+pub fn query_staking_account_ids_with_status(status: &StakingAccountStatus) -> Vec<StakingAccountId> {
+  STAKING_STATUS_ACCOUNT_INDEX_MAP.with(|map| composite_entity_index::get_indexed_ids(map, &status.to_string()))
+}
+
 /// Query the list of staked accounts of the current session user in the stake pool
 pub fn query_current_user_staking_accounts(pool_id: StakingPoolId) -> Vec<StakingAccount> {
   let user = crate::identity_mapping::wl_caller();
@@ -46,7 +118,7 @@ pub fn query_user_in_stake_accounts(user_id: String, pool_id: StakingPoolId) ->
 
 /// Query the list of staked accounts for the same user as the current staked account
 pub fn query_user_staking_accounts(user_id: String) -> Vec<StakingAccount> {
-  let user_account_index = STAKING_USER_ACCOUNT_INDEX_MAP.with(|map| get_indexed_ids(map, &user_id));
+  let user_account_index = get_user_account_ids(&user_id);
 
   if user_account_index.is_empty() {
     return vec![]; // No staking accounts for the user
@@ -67,26 +139,13 @@ pub fn query_user_staking_accounts(user_id: String) -> Vec<StakingAccount> {
     .filter(|account| account.get_owner() == user_id)
     .collect::<Vec<StakingAccount>>();
 
-  STAKING_USER_ACCOUNT_INDEX_MAP.with(|map| {
-    let mut map = map.borrow_mut();
-
-    let mut index = map.get(&user_id).unwrap_or_default();
-    let mut pending_remove_ids = vec![];
-
-    // Clear the index of staked accounts that are not part of the current user
-    for account_id in index.get_entity_ids() {
-      if !filter_accounts.iter().any(|account| account.get_id() == account_id) {
-        pending_remove_ids.push(account_id);
-      }
-    }
-
-    for account_id in pending_remove_ids {
-      index.remove_entity_id(&account_id);
+  // Clear the index of staked accounts that are not part of the current user
+  for account_id in user_account_index {
+    if !filter_accounts.iter().any(|account| account.get_id() == account_id) {
+      STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| composite_entity_index::remove_indexed_id(index_map, &user_id, account_id));
+      LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| entities::remove_indexed_id(index_map, &user_id, account_id));
     }
-
-    // Update index
-    map.insert(user_id.clone(), index);
-  });
+  }
 
   filter_accounts
 }
@@ -97,10 +156,15 @@ pub fn save_stake_account_to_stable_memory(staking_account: &StakingAccount) ->
     .with(|map| {
       let mut map = map.borrow_mut();
       // Save the staked account to stable memory
-      map.insert(staking_account.get_id(), staking_account.clone());
+      let previous = map.insert(staking_account.get_id(), staking_account.clone());
+      index_staking_account_address(staking_account.get_id());
+      index_staking_account_status(
+        staking_account.get_id(),
+        previous.map(|previous| previous.get_status()),
+        Some(staking_account.get_status()),
+      );
 
-      STAKING_POOL_ACCOUNT_INDEX_MAP.with(|pool_map| add_indexed_id(pool_map, &staking_account.get_pool_id(), staking_account.get_id()));
-      STAKING_USER_ACCOUNT_INDEX_MAP.with(|user_map| add_indexed_id(user_map, &staking_account.get_owner(), staking_account.get_id()));
+      add_staking_account_indexes(staking_account.get_pool_id(), &staking_account.get_owner(), staking_account.get_id());
 
       Ok(staking_account)
     })
@@ -158,8 +222,9 @@ pub fn delete_staking_account(account_id: &StakingAccountId) -> Result<(), Strin
     map.remove(account_id);
 
     // Delete the index
-    STAKING_POOL_ACCOUNT_INDEX_MAP.with(|pool_map| remove_indexed_id(pool_map, &staking_account.get_pool_id(), *account_id));
-    STAKING_USER_ACCOUNT_INDEX_MAP.with(|user_map| remove_indexed_id(user_map, &staking_account.get_owner(), *account_id));
+    remove_staking_account_indexes(staking_account.get_pool_id(), &staking_account.get_owner(), *account_id);
+    index_staking_account_status(*account_id, Some(staking_account.get_status()), None);
+    remove_staking_account_address(*account_id);
 
     save_delete_staking_account_event_log(account_id);
     Ok(())
@@ -167,14 +232,7 @@ pub fn delete_staking_account(account_id: &StakingAccountId) -> Result<(), Strin
 }
 
 pub fn query_staking_account_with_pool_id(pool_id: StakingPoolId) -> Vec<StakingAccount> {
-  let account_ids = STAKING_POOL_ACCOUNT_INDEX_MAP.with(|map| {
-    let account_ids = get_indexed_ids(map, &pool_id);
-    if account_ids.is_empty() {
-      return vec![];
-    }
-
-    account_ids
-  });
+  let account_ids = get_pool_account_ids(&pool_id);
 
   STAKING_ACCOUNT_MAP.with(|account_map| {
     let account_map = account_map.borrow();
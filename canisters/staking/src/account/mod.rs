@@ -1,12 +1,17 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, str::FromStr};
 
 use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
-use stable_structures::StakingAccount;
+use stable_structures::{StakingAccount, StakingAccountStatus};
 use system_configs_macro::has_permission;
-use transport_structures::{StakingAccountPageRequest, StakingAccountPageResponse, StakingAccountQueryParams, StakingAccountVo};
+use transport_structures::{
+  StakingAccountCursorPageRequest, StakingAccountCursorPageResponse, StakingAccountIndexedPageRequest, StakingAccountIndexedQueryParams,
+  StakingAccountPageRequest, StakingAccountPageResponse, StakingAccountQueryParams, StakingAccountSortField, StakingAccountVo,
+};
 use types::{
+  composite_entity_index::CompositeEntityIndex,
   date::YearMonthDay,
-  entities::{get_indexed_ids, EntityIndex},
+  entities::EntityIndex,
+  pagination::{collect_page, PageRequest, PageResponse, SortDirection, MAX_CURSOR_PAGE_SCAN},
   stable_structures::Memory,
   staking::{StakingAccountId, StakingPoolId},
   EntityId, UserId,
@@ -14,8 +19,13 @@ use types::{
 
 use crate::{
   memory_ids::{
-    STAKING_ACCOUNT, STAKING_ACCOUNT_SEQ, STAKING_POOL_ACCOUNT_INDEX, STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX, STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX,
-    STAKING_USER_ACCOUNT_INDEX,
+    STAKING_ACCOUNT, STAKING_ACCOUNT_SEQ, STAKING_POOL_ACCOUNT_COMPOSITE_INDEX, STAKING_POOL_ACCOUNT_INDEX, STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX,
+    STAKING_STATUS_ACCOUNT_INDEX, STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX, STAKING_USER_ACCOUNT_COMPOSITE_INDEX, STAKING_USER_ACCOUNT_INDEX,
+  },
+  migrations::{ACCOUNT_STATUS_INDEX_SCHEMA_VERSION, SCHEMA_MIGRATOR, STAKING_ADDRESS_INDEX_SCHEMA_VERSION},
+  on_chain::{
+    address::generate_staking_account_account_identifier, parse_staking_address, resolve_staking_address_owner,
+    stable_structures::StakingAddressOwner,
   },
   MEMORY_MANAGER,
 };
@@ -42,14 +52,28 @@ thread_local! {
   );
 
   /// Index of user staked accounts
-  pub static STAKING_USER_ACCOUNT_INDEX_MAP: RefCell<StableBTreeMap<UserId, EntityIndex<UserId>, Memory>> = RefCell::new(
+  pub static STAKING_USER_ACCOUNT_INDEX_MAP: RefCell<CompositeEntityIndex<UserId>> = RefCell::new(
     StableBTreeMap::init(
-      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_USER_ACCOUNT_INDEX))),
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_USER_ACCOUNT_COMPOSITE_INDEX))),
     )
   );
 
   /// stake pool stake account index
-  pub static STAKING_POOL_ACCOUNT_INDEX_MAP: RefCell<StableBTreeMap<StakingPoolId, EntityIndex<StakingPoolId>, Memory>> = RefCell::new(
+  pub static STAKING_POOL_ACCOUNT_INDEX_MAP: RefCell<CompositeEntityIndex<StakingPoolId>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_ACCOUNT_COMPOSITE_INDEX))),
+    )
+  );
+
+  /// Vector index of user staked accounts, read until the composite index is built
+  pub static LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP: RefCell<StableBTreeMap<UserId, EntityIndex<UserId>, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_USER_ACCOUNT_INDEX))),
+    )
+  );
+
+  /// Vector index of the stake pool accounts, read until the composite index is built
+  pub static LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP: RefCell<StableBTreeMap<StakingPoolId, EntityIndex<StakingPoolId>, Memory>> = RefCell::new(
     StableBTreeMap::init(
       MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_ACCOUNT_INDEX))),
     )
@@ -62,6 +86,13 @@ thread_local! {
     )
   );
 
+  /// Staking accounts indexed by status, keyed by the status code of StakingAccountStatus
+  pub static STAKING_STATUS_ACCOUNT_INDEX_MAP: RefCell<CompositeEntityIndex<String>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_STATUS_ACCOUNT_INDEX))),
+    )
+  );
+
   /// Accounts that are staked and indexed by expiration date
   pub static STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP: RefCell<StableBTreeMap<YearMonthDay, EntityIndex<YearMonthDay>, Memory>> = RefCell::new(
     StableBTreeMap::init(
@@ -85,80 +116,29 @@ fn query_staking_accounts(request: StakingAccountPageRequest) -> StakingAccountP
     },
   } = request;
 
-  // List of accounts filtered by conditions
-  let filtered_accounts: Vec<StakingAccount> = if pool_id > 0 {
-    let account_ids = STAKING_POOL_ACCOUNT_INDEX_MAP.with(|map| get_indexed_ids(map, &pool_id));
-
-    STAKING_ACCOUNT_MAP.with(|map| {
-      let map = map.borrow();
-      account_ids
-        .iter()
-        .filter_map(|account_id| map.get(account_id))
-        .filter(|account| {
-          if status.len() > 0 {
-            account.get_status().to_string() == status
-          } else {
-            true
-          }
-        })
-        .filter(|account| {
-          if user_id.len() > 0 {
-            account.get_owner().contains(&user_id)
-          } else {
-            true
-          }
-        })
-        .filter(|account| {
-          if onchain_address.len() > 0 {
-            account.get_onchain_address().contains(&onchain_address)
-          } else {
-            true
-          }
-        })
-        .collect()
-    })
-  } else {
-    STAKING_ACCOUNT_MAP.with(|map| {
-      let map = map.borrow();
-
-      map
-        .iter()
-        .map(|(_, account)| account.clone())
-        .filter(|account| {
-          if status.len() > 0 {
-            account.get_status().to_string() == status
-          } else {
-            true
-          }
-        })
-        .filter(|account| {
-          if user_id.len() > 0 {
-            account.get_owner().contains(&user_id)
-          } else {
-            true
-          }
-        })
-        .filter(|account| {
-          if onchain_address.len() > 0 {
-            account.get_onchain_address().contains(&onchain_address)
-          } else {
-            true
-          }
-        })
-        .collect()
-    })
+  let is_match = |account: &StakingAccount| {
+    (status.is_empty() || account.get_status().to_string() == status)
+      && (user_id.is_empty() || account.get_owner().contains(&user_id))
+      && (onchain_address.is_empty() || account.get_onchain_address().contains(&onchain_address))
   };
 
-  let total = filtered_accounts.len() as u32;
-  let start = (page - 1) * page_size;
+  // The accounts filtered by conditions are streamed newest first, only the requested page is kept
+  let (total, records) = STAKING_ACCOUNT_MAP.with(|map| {
+    let map = map.borrow();
+    let to_record = |account: StakingAccount| StakingAccountVo::from_staking_account(&account);
 
-  let records = filtered_accounts
-    .iter()
-    .rev()
-    .skip(start as usize)
-    .take(page_size as usize)
-    .map(|account| StakingAccountVo::from_staking_account(account))
-    .collect::<Vec<StakingAccountVo>>();
+    if pool_id > 0 {
+      let account_ids = crud_utils::get_pool_account_ids(&pool_id);
+      let matched = account_ids
+        .into_iter()
+        .rev()
+        .filter_map(|account_id| map.get(&account_id))
+        .filter(is_match);
+      collect_page(page, page_size, matched, to_record)
+    } else {
+      collect_page(page, page_size, map.values().rev().filter(is_match), to_record)
+    }
+  });
 
   StakingAccountPageResponse {
     total,
@@ -167,3 +147,136 @@ fn query_staking_accounts(request: StakingAccountPageRequest) -> StakingAccountP
     records,
   }
 }
+
+/// Query the staking accounts through the narrowest index the conditions allow, with range filters and sorting.
+/// Without an index only the newest MAX_CURSOR_PAGE_SCAN accounts are scanned, older ones are reached through query_staking_accounts_by_cursor.
+#[ic_cdk::query]
+#[has_permission("staking::account::query")]
+fn query_staking_accounts_indexed(request: StakingAccountIndexedPageRequest) -> Result<PageResponse<StakingAccountVo>, String> {
+  let PageRequest { page, page_size, params } = request;
+  let status = match &params.status {
+    Some(status) => Some(StakingAccountStatus::from_str(status).map_err(|_| format!("Invalid staking account status: {}", status))?),
+    None => None,
+  };
+  let sort_field = match &params.sort_field {
+    Some(sort_field) => StakingAccountSortField::from_str(sort_field).map_err(|_| format!("Unsupported sort field: {}", sort_field))?,
+    None => StakingAccountSortField::Id,
+  };
+
+  // Until the address index is built the address is matched against the account identifier of each scanned account
+  let unindexed_address = match &params.onchain_address {
+    Some(address) if !SCHEMA_MIGRATOR.is_migrated_to(STAKING_ADDRESS_INDEX_SCHEMA_VERSION) => Some(
+      parse_staking_address(address)
+        .map(|account_identifier| account_identifier.to_hex())
+        .unwrap_or_default(),
+    ),
+    _ => None,
+  };
+
+  // Candidate account ids from the most selective index the conditions hit, None scans all accounts
+  let candidate_ids: Option<Vec<StakingAccountId>> = if let Some(address) = params.onchain_address.as_ref().filter(|_| unindexed_address.is_none()) {
+    match resolve_staking_address_owner(address) {
+      Ok((_, StakingAddressOwner::StakingAccount(account_id))) => Some(vec![account_id]),
+      _ => Some(vec![]),
+    }
+  } else if params.has_recoverable_error == Some(true) {
+    Some(STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP.with(|map| map.borrow().values().flat_map(|index| index.get_entity_ids()).collect()))
+  } else if let Some(user_id) = &params.user_id {
+    Some(crud_utils::get_user_account_ids(user_id))
+  } else if let Some(pool_id) = params.pool_id {
+    Some(crud_utils::get_pool_account_ids(&pool_id))
+  } else {
+    match &status {
+      Some(status) if SCHEMA_MIGRATOR.is_migrated_to(ACCOUNT_STATUS_INDEX_SCHEMA_VERSION) => {
+        Some(crud_utils::query_staking_account_ids_with_status(status))
+      }
+      _ => None,
+    }
+  };
+
+  let StakingAccountIndexedQueryParams {
+    pool_id,
+    user_id,
+    has_recoverable_error,
+    stake_time_start,
+    stake_time_end,
+    stake_deadline_start,
+    stake_deadline_end,
+    min_staked_amount,
+    max_staked_amount,
+    direction,
+    ..
+  } = params;
+  let matches = |account: &StakingAccount| {
+    pool_id.is_none_or(|pool_id| account.get_pool_id() == pool_id)
+      && user_id.as_ref().is_none_or(|user_id| account.get_owner() == *user_id)
+      && status.as_ref().is_none_or(|status| account.get_status() == *status)
+      && has_recoverable_error.is_none_or(|has_error| account.recoverable_error.is_some() == has_error)
+      && stake_time_start.is_none_or(|start| account.get_stake_time() >= start)
+      && stake_time_end.is_none_or(|end| account.get_stake_time() <= end)
+      && stake_deadline_start.is_none_or(|start| account.get_stake_deadline() >= start)
+      && stake_deadline_end.is_none_or(|end| account.get_stake_deadline() <= end)
+      && min_staked_amount.is_none_or(|min| account.get_staked_amount() >= min)
+      && max_staked_amount.is_none_or(|max| account.get_staked_amount() <= max)
+      && unindexed_address
+        .as_ref()
+        .is_none_or(|address| generate_staking_account_account_identifier(account.get_id()).to_hex() == *address)
+  };
+
+  let mut accounts: Vec<StakingAccount> = STAKING_ACCOUNT_MAP.with(|map| {
+    let map = map.borrow();
+    match candidate_ids {
+      Some(account_ids) => account_ids
+        .into_iter()
+        .filter_map(|account_id| map.get(&account_id))
+        .filter(matches)
+        .collect(),
+      None => map.values().rev().take(MAX_CURSOR_PAGE_SCAN).filter(matches).collect(),
+    }
+  });
+
+  accounts.sort_by_key(|account| match sort_field {
+    StakingAccountSortField::Id => (account.get_id(), 0),
+    StakingAccountSortField::StakedAmount => (account.get_staked_amount(), account.get_id()),
+    StakingAccountSortField::StakeTime => (account.get_stake_time(), account.get_id()),
+    StakingAccountSortField::StakeDeadline => (account.get_stake_deadline(), account.get_id()),
+  });
+  if direction.unwrap_or_default() == SortDirection::Desc {
+    accounts.reverse();
+  }
+
+  let (total, records) = collect_page(page, page_size, accounts.iter(), StakingAccountVo::from_staking_account);
+
+  Ok(PageResponse::new(page, page_size, total, records))
+}
+
+/// Stream the stake account list page by page, newest first unless ascending is requested
+#[ic_cdk::query]
+#[has_permission("staking::account::query")]
+fn query_staking_accounts_by_cursor(request: StakingAccountCursorPageRequest) -> Result<StakingAccountCursorPageResponse, String> {
+  let position = request.position(&["id", "created_at"])?;
+  let StakingAccountQueryParams {
+    pool_id,
+    user_id,
+    onchain_address,
+    status,
+  } = request.params;
+
+  let to_record = |account: &StakingAccount| {
+    ((status.is_empty() || account.get_status().to_string() == status)
+      && (user_id.is_empty() || account.get_owner().contains(&user_id))
+      && (onchain_address.is_empty() || account.get_onchain_address().contains(&onchain_address)))
+    .then(|| StakingAccountVo::from_staking_account(account))
+  };
+
+  STAKING_ACCOUNT_MAP.with(|map| {
+    let map = map.borrow();
+    if pool_id > 0 {
+      let mut account_ids = crud_utils::get_pool_account_ids(&pool_id);
+      account_ids.sort_unstable();
+      Ok(position.scan_ids(account_ids.into_iter(), |account_id| map.get(&account_id), to_record))
+    } else {
+      Ok(position.scan_map(&map, to_record))
+    }
+  })
+}
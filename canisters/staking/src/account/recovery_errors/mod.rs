@@ -32,6 +32,10 @@ pub async fn recover_staking_account_error(account_id: StakingAccountId) -> Resu
         }
       }
     }
+    StakingAccountRecoverableError::StakeTransferFromWalletFailed => match recover_stake::recover_stake_with_approve_error(&account).await {
+      Ok(_) => Ok(()),
+      Err(e) => Err(format!("Failed to recover stake with approve: account_id = {}, error = {}", account_id, e)),
+    },
     StakingAccountRecoverableError::DissolvePayCenterFailed(dissolve_tx_id) => {
       match recover_dissolve::recover_dissolve_error(&account, dissolve_tx_id).await {
         Ok(_) => Ok(()),
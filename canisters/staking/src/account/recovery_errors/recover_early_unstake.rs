@@ -44,7 +44,7 @@ pub async fn recover_unstake_penalty_onchain_error(account: &StakingAccount) ->
     save_unstake_penalty_transfer_start_event(account.get_id(), account.get_pool_id());
 
     // Execute on-chain transfer of unstaking liquidated damages
-    let penalty_onchain_tx_id = match transfer_from_staking_pool_to_pay_center(account.get_pool_id(), penalty_amount).await {
+    let penalty_onchain_tx_id = match transfer_from_staking_pool_to_pay_center(account.get_pool_id(), account.get_id(), penalty_amount).await {
       Ok(tx_id) => {
         ic_cdk::println!("On-chain transfer success: {}", tx_id);
 
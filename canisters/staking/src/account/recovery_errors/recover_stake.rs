@@ -6,8 +6,8 @@ use types::{
 
 use crate::{
   account::{
-    crud_utils::{query_user_in_stake_accounts, save_stake_account_to_stable_memory},
-    stable_structures::{StakingAccount, StakingAccountStatus},
+    crud_utils::{delete_staking_account, query_user_in_stake_accounts, save_stake_account_to_stable_memory},
+    stable_structures::{StakeMode, StakingAccount, StakingAccountStatus},
     transport_structures::StakingAccountVo,
     STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP, STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP,
   },
@@ -16,7 +16,10 @@ use crate::{
     transfer_events::{save_stake_transfer_fail_event, save_stake_transfer_ok_event, save_stake_transfer_start_event},
   },
   guard_keys::get_recovery_stake_guard_key,
-  on_chain::transfer::transfer_from_staking_account_to_staking_pool,
+  on_chain::transfer::{
+    is_transfer_failed, transfer_from_staking_account_to_staking_pool, transfer_from_wallet_to_staking_pool, transfer_op_key,
+    TRANSFER_SCENE_STAKE_APPROVE,
+  },
   parallel_guard::EntryGuard,
   pool::crud_utils::query_staking_pool_by_id,
 };
@@ -83,3 +86,70 @@ pub async fn recover_stake_error(
 
   Ok(StakingAccountVo::from_staking_account(&account))
 }
+
+// During the stake with approve process, the outcome of the transfer from the wallet to the stake pool is unknown.
+// The transfer is looked up in the ledger (or sent again with the same created_at_time, which the ledger deduplicates).
+// If the ledger rejected it, nothing was pulled from the wallet and the stake account is removed.
+pub async fn recover_stake_with_approve_error(account: &StakingAccount) -> Result<StakingAccountVo, String> {
+  // Entrance guard
+  let _entry_guard = EntryGuard::new(get_recovery_stake_guard_key(account.get_id()))
+    .map_err(|_| format!("Account is already in stake recovery, account_id = {}", account.get_id()))?;
+
+  if account.get_status() != StakingAccountStatus::Created {
+    return Err("Account is not in recoverable error state".to_string());
+  }
+
+  let wallet = match account.get_stake_mode() {
+    StakeMode::Icrc2Approve(wallet) => wallet,
+    StakeMode::PayCenter => return Err("Account is not staked with approve".to_string()),
+  };
+
+  let mut staking_pool = query_staking_pool_by_id(account.get_pool_id())?;
+  let current_user_in_stake_accounts = query_user_in_stake_accounts(account.get_owner(), account.get_pool_id());
+
+  // stake: Event log of transfer from the wallet to stake pool -start
+  save_stake_transfer_start_event(account.get_id(), staking_pool.get_id());
+
+  let wallet_to_pool_tx_id =
+    match transfer_from_wallet_to_staking_pool(wallet, account.get_id(), staking_pool.get_id(), account.get_staked_amount()).await {
+      Ok(tx_id) => {
+        ic_cdk::println!("Transfer from wallet to pool success: {}", tx_id);
+
+        // stake：Transfer event log from the wallet to stake pool -success
+        save_stake_transfer_ok_event(account.get_id(), staking_pool.get_id(), tx_id);
+
+        tx_id
+      }
+      Err(e) => {
+        ic_cdk::println!("Transfer from wallet to pool failed: {:?}", e);
+
+        // stake：Transfer event log from the wallet to stake pool-fail
+        save_stake_transfer_fail_event(account.get_id(), staking_pool.get_id(), e.clone());
+
+        if is_transfer_failed(&transfer_op_key(TRANSFER_SCENE_STAKE_APPROVE, account.get_id())) {
+          delete_staking_account(&account.get_id())?;
+          staking_pool.restore_locked_size(account.get_staked_amount())?;
+          STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP.with(|map| remove_indexed_id(map, &account.get_pool_id(), account.get_id()));
+        }
+
+        return Err(format!("Transfer from wallet to pool failed: {}", e));
+      }
+    };
+
+  let mut account = account.clone();
+
+  // Update the status to in stake of the stake account
+  account.change_to_in_stake(0, 0, wallet_to_pool_tx_id);
+
+  save_stake_account_to_stable_memory(&account)?;
+
+  STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP.with(|map| add_indexed_id(map, &YearMonthDay::from(account.get_stake_deadline()), account.get_id()));
+
+  let updated_pool = staking_pool.add_stake_account(&account, &current_user_in_stake_accounts)?;
+
+  save_stake_event(&updated_pool, &account);
+
+  STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP.with(|map| remove_indexed_id(map, &account.get_pool_id(), account.get_id()));
+
+  Ok(StakingAccountVo::from_staking_account(&account))
+}
@@ -1,6 +1,6 @@
 use std::{borrow::Cow, time::Duration};
 
-use candid::{CandidType, Decode, Encode};
+use candid::{CandidType, Decode, Encode, Principal};
 use ic_ledger_types::BlockIndex;
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::{Deserialize, Serialize};
@@ -21,8 +21,8 @@ use crate::{
 };
 
 use super::{
-  client_transport_structures::StakeDto, STAKING_ACCOUNT_ID, STAKING_ACCOUNT_MAP, STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP,
-  STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP,
+  client_transport_structures::StakeDto, crud_utils::index_staking_account_status, STAKING_ACCOUNT_ID, STAKING_ACCOUNT_MAP,
+  STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP, STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP,
 };
 
 /// Status of the staked account
@@ -42,6 +42,15 @@ pub enum StakingAccountStatus {
   Dissolved,
 }
 
+/// How the stake of the staked account is funded
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum StakeMode {
+  /// The payment center transfers the stake into the staked account, which transfers it to the stake pool
+  PayCenter,
+  /// The stake is pulled from the wallet with the allowance it approved to the staking canister, released funds are returned to the wallet
+  Icrc2Approve(Principal),
+}
+
 /// Recoverable exceptions for staked accounts
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
 pub enum StakingAccountRecoverableError {
@@ -53,6 +62,8 @@ pub enum StakingAccountRecoverableError {
   EarlyUnstakePenaltyOnChainFailed(BlockIndex, TimestampNanos, E8S),
   /// Early unstaking，Payment center bookkeeping failed
   EarlyUnstakePenaltyPayCenterFailed(BlockIndex, BlockIndex, TimestampNanos, E8S),
+  /// When staking with approve，The outcome of the transfer from the wallet to the stake pool is unknown
+  StakeTransferFromWalletFailed,
 }
 
 /// stake account
@@ -114,11 +125,18 @@ pub struct StakingAccount {
   pub meta: Option<MetaData>,
   /// Abnormal state that can be restored
   pub recoverable_error: Option<StakingAccountRecoverableError>,
+  /// How the stake is funded
+  pub stake_mode: Option<StakeMode>,
 }
 
 impl StakingAccount {
   /// Create a new staked account
   pub fn from_stake_dto_and_pool(stake_dto: &StakeDto, pool: &StakingPool) -> Result<Self, String> {
+    Self::from_stake_dto_and_pool_with_mode(stake_dto, pool, StakeMode::PayCenter)
+  }
+
+  /// Create a new staked account funded with the stake mode
+  pub fn from_stake_dto_and_pool_with_mode(stake_dto: &StakeDto, pool: &StakingPool, stake_mode: StakeMode) -> Result<Self, String> {
     let StakeDto {
       pool_id,
       staking_amount,
@@ -171,6 +189,7 @@ impl StakingAccount {
       last_reward_time: None,
       meta: Some(MetaData::default()),
       recoverable_error: None,
+      stake_mode: Some(stake_mode),
     })
   }
 
@@ -251,6 +270,7 @@ impl StakingAccount {
       let mut map = map.borrow_mut();
 
       let mut account = map.get(&self.get_id()).ok_or("Staking account not found")?;
+      let previous_status = account.get_status();
       account.status = Some(StakingAccountStatus::Released);
       account.release_onchain_tx_id = Some(unstake_tx_id);
       account.released_amount = Some(release_amount);
@@ -266,6 +286,7 @@ impl StakingAccount {
       account.update_meta();
 
       map.insert(account.get_id(), account.clone());
+      index_staking_account_status(account.get_id(), Some(previous_status), Some(account.get_status()));
 
       // When unstake，Remove the staked account from the unstaked date index，Further improve the performance of timing tasks
       STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX_MAP
@@ -284,6 +305,7 @@ impl StakingAccount {
       let mut map = map.borrow_mut();
 
       let mut account = map.get(&self.get_id()).ok_or("Staking account not found")?;
+      let previous_status = account.get_status();
       account.status = Some(StakingAccountStatus::Dissolved);
       account.dissolve_onchain_tx_id = Some(dissolve_tx_id);
       account.dissolve_pay_center_tx_id = Some(pay_center_tx_id);
@@ -292,6 +314,7 @@ impl StakingAccount {
       account.recoverable_error = None;
 
       map.insert(account.get_id(), account.clone());
+      index_staking_account_status(account.get_id(), Some(previous_status), Some(account.get_status()));
 
       Ok(account)
     })
@@ -373,6 +396,10 @@ impl StakingAccount {
     self.reward_config.clone().unwrap_or_default()
   }
 
+  pub fn get_stake_mode(&self) -> StakeMode {
+    self.stake_mode.clone().unwrap_or(StakeMode::PayCenter)
+  }
+
   pub fn get_penalty_amount(&self) -> E8S {
     self.penalty_amount.unwrap_or_default()
   }
@@ -396,6 +423,9 @@ impl StakingAccount {
   pub fn get_dissolve_pay_center_tx_id(&self) -> u64 {
     self.dissolve_pay_center_tx_id.unwrap_or_default()
   }
+  pub fn get_penalty_onchain_tx_id(&self) -> u64 {
+    self.penalty_onchain_tx_id.unwrap_or_default()
+  }
   pub fn get_total_staking_days(&self) -> u16 {
     self.total_staking_days.unwrap_or_default()
   }
@@ -1,14 +1,15 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
 use types::{
-  pagination::{PageRequest, PageResponse},
+  pagination::{CursorPageRequest, CursorPageResponse, PageRequest, PageResponse, SortDirection},
   staking::{StakingAccountId, StakingPoolId},
-  UserId, E8S,
+  TimestampNanos, UserId, E8S,
 };
 
-use crate::pool::transport_structures::RewardConfigVo;
+use crate::{on_chain::address::generate_staking_account_icrc1_address, pool::transport_structures::RewardConfigVo};
 
-use super::stable_structures::StakingAccount;
+use super::stable_structures::{StakeMode, StakingAccount};
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct StakingAccountQueryParams {
@@ -21,6 +22,43 @@ pub struct StakingAccountQueryParams {
   pub status: String,
 }
 
+/// Conditions of the indexed staking account query, the conditions that are set must all match
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct StakingAccountIndexedQueryParams {
+  pub pool_id: Option<StakingPoolId>,
+  /// Exact owner of the staking account
+  pub user_id: Option<UserId>,
+  /// Exact on-chain address, account identifier hex or ICRC-1 account text
+  pub onchain_address: Option<String>,
+  /// Refer to StakingAccountStatus enumerate
+  pub status: Option<String>,
+  /// Only the accounts waiting for the error recovery task, or only the accounts without a recoverable error
+  pub has_recoverable_error: Option<bool>,
+  pub stake_time_start: Option<TimestampNanos>,
+  pub stake_time_end: Option<TimestampNanos>,
+  pub stake_deadline_start: Option<TimestampNanos>,
+  pub stake_deadline_end: Option<TimestampNanos>,
+  pub min_staked_amount: Option<E8S>,
+  pub max_staked_amount: Option<E8S>,
+  /// Refer to StakingAccountSortField enumerate, sorted by id when not set
+  pub sort_field: Option<String>,
+  /// Descending when not set
+  pub direction: Option<SortDirection>,
+}
+
+/// Sort fields of the indexed staking account query
+#[derive(EnumString, Display, Debug, Clone, Copy, PartialEq)]
+pub enum StakingAccountSortField {
+  #[strum(serialize = "id")]
+  Id,
+  #[strum(serialize = "staked_amount")]
+  StakedAmount,
+  #[strum(serialize = "stake_time")]
+  StakeTime,
+  #[strum(serialize = "stake_deadline")]
+  StakeDeadline,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct StakingAccountVo {
   /// stake accountID
@@ -31,6 +69,8 @@ pub struct StakingAccountVo {
   pub owner: UserId,
   /// Address on the chain of stake account
   pub onchain_address: String,
+  /// Stake account address in the ICRC-1 account text encoding
+  pub icrc1_address: String,
   /// The amount released in the staked account
   pub released_amount: E8S,
   /// The amount of the stake account
@@ -69,10 +109,15 @@ pub struct StakingAccountVo {
   pub last_reward_time: u64,
   /// Stake account creation time
   pub create_time: u64,
+  /// How the stake is funded
+  pub stake_mode: StakeMode,
 }
 
 pub type StakingAccountPageRequest = PageRequest<StakingAccountQueryParams>;
 pub type StakingAccountPageResponse = PageResponse<StakingAccountVo>;
+pub type StakingAccountCursorPageRequest = CursorPageRequest<StakingAccountQueryParams>;
+pub type StakingAccountCursorPageResponse = CursorPageResponse<StakingAccountVo>;
+pub type StakingAccountIndexedPageRequest = PageRequest<StakingAccountIndexedQueryParams>;
 
 impl StakingAccountVo {
   /// Convert staked account to staked account information visible to the client
@@ -82,6 +127,7 @@ impl StakingAccountVo {
       pool_id: account.get_pool_id(),
       owner: account.get_owner(),
       onchain_address: account.get_onchain_address(),
+      icrc1_address: generate_staking_account_icrc1_address(account.get_id()),
       released_amount: account.get_released_amount(),
       staked_amount: account.get_staked_amount(),
       penalty_amount: account.get_penalty_amount(),
@@ -102,6 +148,7 @@ impl StakingAccountVo {
       dissolve_time: account.get_dissolve_time(),
       last_reward_time: account.get_last_reward_time(),
       create_time: account.get_create_time(),
+      stake_mode: account.get_stake_mode(),
     }
   }
 }
@@ -0,0 +1,161 @@
+use std::{cell::RefCell, str::FromStr};
+
+use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
+use stable_structures::{DepositTarget, ExternalDeposit, ExternalDepositStatus};
+use system_configs_macro::{has_permission, has_permission_result};
+use transport_structures::{ExternalDepositQueryParams, ExternalDepositVo};
+use types::{
+  pagination::{PageRequest, PageResponse},
+  stable_structures::Memory,
+  staking::ExternalDepositId,
+  EntityId,
+};
+
+use crate::{
+  guard_keys::get_external_deposit_guard_key,
+  memory_ids::{EXTERNAL_DEPOSIT, EXTERNAL_DEPOSIT_SCAN_CURSOR, EXTERNAL_DEPOSIT_SEQ},
+  on_chain::transfer::{get_pool_ledger, transfer_external_deposit_refund, transfer_external_deposit_to_staking_pool},
+  parallel_guard::EntryGuard,
+  pool_transaction_record::utils::record_external_deposit_transaction,
+  MEMORY_MANAGER,
+};
+
+pub mod scanner;
+pub mod stable_structures;
+pub mod transport_structures;
+
+thread_local! {
+  /// External deposits are added IDGenerator
+  pub static EXTERNAL_DEPOSIT_ID: RefCell<Cell<EntityId, Memory>> = RefCell::new(Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(EXTERNAL_DEPOSIT_SEQ))), 0_u64).unwrap());
+
+  /// Transfers into pool and staking account subaccounts that the staking canister did not make itself
+  pub static EXTERNAL_DEPOSIT_MAP: RefCell<StableBTreeMap<ExternalDepositId, ExternalDeposit, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(EXTERNAL_DEPOSIT))),
+    )
+  );
+
+  /// Next ICP ledger block the deposit scanner reads, 0 until the first scan
+  pub static EXTERNAL_DEPOSIT_SCAN_CURSOR_CELL: RefCell<Cell<u64, Memory>> = RefCell::new(Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(EXTERNAL_DEPOSIT_SCAN_CURSOR))), 0_u64).unwrap());
+}
+
+/// Paging query of the external deposits, newest first
+#[ic_cdk::query]
+#[has_permission("staking::deposit::query")]
+fn get_external_deposit_page(request: PageRequest<ExternalDepositQueryParams>) -> PageResponse<ExternalDepositVo> {
+  let PageRequest {
+    page,
+    page_size,
+    params: ExternalDepositQueryParams { pool_id, status },
+  } = request;
+
+  let status = status.and_then(|status| ExternalDepositStatus::from_str(&status).ok());
+
+  let deposits: Vec<ExternalDeposit> = EXTERNAL_DEPOSIT_MAP.with(|map| {
+    map
+      .borrow()
+      .values()
+      .rev()
+      .filter(|deposit| pool_id.is_none_or(|pool_id| deposit.get_pool_id() == pool_id))
+      .filter(|deposit| status.as_ref().is_none_or(|status| deposit.get_status() == *status))
+      .collect()
+  });
+
+  let total = deposits.len() as u32;
+  let start = page.saturating_sub(1) * page_size;
+
+  let records = deposits
+    .into_iter()
+    .skip(start as usize)
+    .take(page_size as usize)
+    .map(ExternalDepositVo::from)
+    .collect();
+
+  PageResponse::new(page, page_size, total, records)
+}
+
+/// Next ICP ledger block the deposit scanner reads
+#[ic_cdk::query]
+#[has_permission("staking::deposit::query")]
+fn get_external_deposit_scan_cursor() -> u64 {
+  EXTERNAL_DEPOSIT_SCAN_CURSOR_CELL.with(|cell| *cell.borrow().get())
+}
+
+/// Move the deposit scanner to a block, used to scan earlier blocks again or to skip ahead
+#[ic_cdk::update]
+#[has_permission_result("staking::deposit::manage", audit)]
+fn set_external_deposit_scan_cursor(next_block: u64) -> Result<(), String> {
+  scanner::set_scan_cursor(next_block);
+  Ok(())
+}
+
+/// Scan the next batch of ledger blocks for external deposits right away
+#[ic_cdk::update]
+#[has_permission_result("staking::deposit::manage", audit)]
+async fn scan_external_deposits() -> Result<Vec<ExternalDepositVo>, String> {
+  Ok(
+    scanner::scan_external_deposits()
+      .await?
+      .into_iter()
+      .map(ExternalDepositVo::from)
+      .collect(),
+  )
+}
+
+fn query_pending_deposit(deposit_id: ExternalDepositId) -> Result<ExternalDeposit, String> {
+  let deposit = ExternalDeposit::query_by_id(deposit_id)?;
+
+  if deposit.get_status() != ExternalDepositStatus::Pending {
+    return Err(format!("External deposit {} is already {:?}", deposit_id, deposit.get_status()));
+  }
+
+  Ok(deposit)
+}
+
+/// Refund an external deposit to the account it came from, less the handling fee
+#[ic_cdk::update]
+#[has_permission_result("staking::deposit::manage", audit)]
+async fn refund_external_deposit(deposit_id: ExternalDepositId) -> Result<ExternalDepositVo, String> {
+  let _entry_guard = EntryGuard::new(get_external_deposit_guard_key(deposit_id))
+    .map_err(|_| format!("External deposit {} is being processed, please do not repeat the operation", deposit_id))?;
+
+  let mut deposit = query_pending_deposit(deposit_id)?;
+
+  let refund_block_index = transfer_external_deposit_refund(
+    deposit_id,
+    deposit.get_pool_id(),
+    deposit.get_target().get_subaccount(),
+    deposit.get_from_account_identifier()?,
+    deposit.get_amount(),
+  )
+  .await?;
+
+  deposit.update_to_refunded(refund_block_index);
+
+  Ok(ExternalDepositVo::from(deposit))
+}
+
+/// Adopt an external deposit into the staking pool's funds.
+/// A deposit into a staking account is first moved into the staking pool, less the handling fee.
+#[ic_cdk::update]
+#[has_permission_result("staking::deposit::manage", audit)]
+async fn adopt_external_deposit(deposit_id: ExternalDepositId) -> Result<ExternalDepositVo, String> {
+  let _entry_guard = EntryGuard::new(get_external_deposit_guard_key(deposit_id))
+    .map_err(|_| format!("External deposit {} is being processed, please do not repeat the operation", deposit_id))?;
+
+  let mut deposit = query_pending_deposit(deposit_id)?;
+  let pool_id = deposit.get_pool_id();
+
+  let (adopted_amount, adopted_block_index) = match deposit.get_target() {
+    DepositTarget::StakingPool(_) => (deposit.get_amount(), deposit.get_block_index()),
+    DepositTarget::StakingAccount(account_id) => {
+      let block_index = transfer_external_deposit_to_staking_pool(deposit_id, account_id, pool_id, deposit.get_amount()).await?;
+      (deposit.get_amount().saturating_sub(get_pool_ledger(pool_id)?.get_fee()), block_index)
+    }
+  };
+
+  record_external_deposit_transaction(pool_id, deposit_id, adopted_amount, adopted_block_index, ic_cdk::api::time())?;
+  deposit.update_to_adopted(adopted_block_index);
+
+  Ok(ExternalDepositVo::from(deposit))
+}
@@ -0,0 +1,179 @@
+use std::collections::HashSet;
+
+use ic_ledger_types::{AccountIdentifier, Operation, DEFAULT_SUBACCOUNT};
+use types::Crypto;
+
+use crate::{
+  account::stable_structures::StakingAccount,
+  guard_keys::{get_deposit_scan_guard_key, DEPOSIT_SCAN_GUARD_LEASE},
+  migrations::{SCHEMA_MIGRATOR, STAKING_ADDRESS_INDEX_SCHEMA_VERSION},
+  nns::{settle_nns_maturity_mint, stable_structures::NnsPoolNeuron},
+  on_chain::{
+    address::generate_neuron_account_with_nonce,
+    query::{query_block_batch, query_chain_length},
+    stable_structures::{LedgerConfig, LedgerProtocol, StakingAddressOwner},
+    transfer::query_pay_center_account_identifier,
+    STAKING_ADDRESS_INDEX_MAP,
+  },
+  parallel_guard::EntryGuard,
+  pool::crud_utils::get_all_staking_pools,
+};
+
+use super::{
+  stable_structures::{DepositTarget, ExternalDeposit},
+  EXTERNAL_DEPOSIT_SCAN_CURSOR_CELL,
+};
+
+/// Number of blocks scanned per run of the deposit scanner
+const DEPOSIT_SCAN_BATCH_SIZE: u64 = 1_000;
+
+/// Accounts watched for deposits, and the accounts whose transfers into them are our own
+struct WatchedAccounts {
+  /// ICP staking pools, deposits into them and into their staking accounts are watched
+  pool_ids: HashSet<u64>,
+  /// Accounts of the pool neurons, disbursed neurons transfer their stake back to the staking pool
+  neuron_accounts: HashSet<AccountIdentifier>,
+  /// The payment center funds the staking accounts
+  pay_center_account: AccountIdentifier,
+}
+
+impl WatchedAccounts {
+  /// Resolve the receiving account through the address index
+  fn get_target(&self, to: &AccountIdentifier) -> Option<(u64, DepositTarget)> {
+    let owner = STAKING_ADDRESS_INDEX_MAP.with(|map| map.borrow().get(&to.to_hex()))?;
+    let (pool_id, target) = match owner {
+      StakingAddressOwner::StakingPool(pool_id) => (pool_id, DepositTarget::StakingPool(pool_id)),
+      StakingAddressOwner::StakingAccount(account_id) => (
+        StakingAccount::query_by_id(account_id).ok()?.get_pool_id(),
+        DepositTarget::StakingAccount(account_id),
+      ),
+    };
+    self.pool_ids.contains(&pool_id).then_some((pool_id, target))
+  }
+
+  /// Transfers from our own pools, staking accounts, neurons and the payment center are not deposits
+  fn is_own_sender(&self, from: &AccountIdentifier) -> bool {
+    *from == self.pay_center_account
+      || self.neuron_accounts.contains(from)
+      || STAKING_ADDRESS_INDEX_MAP.with(|map| map.borrow().contains_key(&from.to_hex()))
+  }
+}
+
+async fn load_watched_accounts() -> Result<WatchedAccounts, String> {
+  let pool_ids: HashSet<u64> = get_all_staking_pools()
+    .into_iter()
+    .filter(|pool| pool.get_crypto() == Crypto::ICP)
+    .map(|pool| pool.get_id())
+    .collect();
+
+  let neuron_accounts = pool_ids
+    .iter()
+    .flat_map(|pool_id| NnsPoolNeuron::query_by_pool(*pool_id))
+    .map(|pool_neuron| generate_neuron_account_with_nonce(pool_neuron.get_nonce()))
+    .collect();
+
+  Ok(WatchedAccounts {
+    pool_ids,
+    neuron_accounts,
+    pay_center_account: query_pay_center_account_identifier().await?,
+  })
+}
+
+/// Scan the next batch of ICP ledger blocks for transfers into pool or staking account subaccounts that we did not make ourselves.
+/// The first run starts at the current chain tip, later runs continue after the last scanned block.
+/// Only the ICP ledger is scanned, the blocks of ICRC-1 ledgers are not read.
+pub async fn scan_external_deposits() -> Result<Vec<ExternalDeposit>, String> {
+  let _entry_guard =
+    EntryGuard::with_lease(get_deposit_scan_guard_key(), DEPOSIT_SCAN_GUARD_LEASE).map_err(|_| "The deposit scan is already running".to_string())?;
+
+  // Deposits are matched through the address index, the scan waits until the migration built it
+  if !SCHEMA_MIGRATOR.is_migrated_to(STAKING_ADDRESS_INDEX_SCHEMA_VERSION) {
+    return Err("The staking address index is not built yet".to_string());
+  }
+
+  let ledger = LedgerConfig::get_with_crypto(&Crypto::ICP)?;
+  if ledger.get_protocol() != LedgerProtocol::Legacy {
+    return Err("The ICP ledger is not registered with the legacy protocol".to_string());
+  }
+
+  let next_block = EXTERNAL_DEPOSIT_SCAN_CURSOR_CELL.with(|cell| *cell.borrow().get());
+
+  if next_block == 0 {
+    let chain_length = query_chain_length(ledger.get_canister_id()).await?;
+    set_scan_cursor(chain_length);
+    ic_cdk::println!("Deposit scan starts at block {}", chain_length);
+    return Ok(vec![]);
+  }
+
+  let (blocks, _) = query_block_batch(ledger.get_canister_id(), next_block, DEPOSIT_SCAN_BATCH_SIZE).await?;
+
+  let Some(last_block_index) = blocks.last().map(|(block_index, _)| *block_index) else {
+    return Ok(vec![]);
+  };
+
+  let watched = load_watched_accounts().await?;
+  let canister_account = AccountIdentifier::new(&ic_cdk::api::canister_self(), &DEFAULT_SUBACCOUNT);
+  let mut deposits = Vec::new();
+
+  for (block_index, block) in blocks {
+    let (from, to, amount) = match &block.transaction.operation {
+      Some(Operation::Transfer { from, to, amount, .. }) => (from, to, amount),
+      // A transfer_from spent by the staking canister is a stake pulled from a wallet
+      Some(Operation::TransferFrom {
+        from, to, spender, amount, ..
+      }) if *spender != canister_account => (from, to, amount),
+      // Mints into the staking pool are disbursed NNS maturity, booked as the pool's yield
+      Some(Operation::Mint { to, amount }) => {
+        if let Some((pool_id, DepositTarget::StakingPool(_))) = watched.get_target(to) {
+          if let Err(e) = settle_nns_maturity_mint(pool_id, block_index, to.to_hex(), amount.e8s(), block.timestamp.timestamp_nanos) {
+            ic_cdk::println!("Failed to book the maturity mint in block {}: {}", block_index, e);
+          }
+        }
+        continue;
+      }
+      _ => continue,
+    };
+
+    let Some((pool_id, target)) = watched.get_target(to) else {
+      continue;
+    };
+
+    if watched.is_own_sender(from) {
+      continue;
+    }
+
+    let deposit = ExternalDeposit::new(
+      pool_id,
+      target.clone(),
+      block_index,
+      from,
+      amount.e8s(),
+      block.transaction.memo.0,
+      block.timestamp.timestamp_nanos,
+    );
+    deposit.update_to_stable();
+
+    ic_cdk::println!(
+      "External deposit {} of {} e8s from {} into {:?} in block {}",
+      deposit.get_id(),
+      deposit.get_amount(),
+      deposit.get_from(),
+      target,
+      block_index
+    );
+
+    deposits.push(deposit);
+  }
+
+  set_scan_cursor(last_block_index + 1);
+
+  Ok(deposits)
+}
+
+pub fn set_scan_cursor(next_block: u64) {
+  EXTERNAL_DEPOSIT_SCAN_CURSOR_CELL.with(|cell| {
+    if let Err(e) = cell.borrow_mut().set(next_block) {
+      ic_cdk::println!("Failed to set the deposit scan cursor: {:?}", e);
+    }
+  });
+}
@@ -0,0 +1,186 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode};
+use ic_ledger_types::{AccountIdentifier, BlockIndex, Subaccount};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use types::{
+  stable_structures::{new_entity_id, MetaData},
+  staking::{ExternalDepositId, StakingAccountId, StakingPoolId},
+  TimestampNanos, E8S,
+};
+
+use crate::on_chain::address::{generate_staking_account_subaccount, generate_staking_pool_subaccount};
+
+use super::{EXTERNAL_DEPOSIT_ID, EXTERNAL_DEPOSIT_MAP};
+
+/// Subaccount of the staking canister an external deposit was sent to
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum DepositTarget {
+  /// Subaccount of the staking pool
+  StakingPool(StakingPoolId),
+  /// Subaccount of a staking account
+  StakingAccount(StakingAccountId),
+}
+
+impl DepositTarget {
+  pub fn get_subaccount(&self) -> Subaccount {
+    match self {
+      DepositTarget::StakingPool(pool_id) => generate_staking_pool_subaccount(*pool_id),
+      DepositTarget::StakingAccount(account_id) => generate_staking_account_subaccount(*account_id),
+    }
+  }
+}
+
+/// Handling status of an external deposit
+#[derive(EnumString, Display, Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum ExternalDepositStatus {
+  /// Waiting for an administrator to refund or adopt it
+  #[strum(serialize = "0")]
+  Pending,
+  /// Transferred back to the account it came from
+  #[strum(serialize = "1")]
+  Refunded,
+  /// Taken into the staking pool's funds
+  #[strum(serialize = "2")]
+  Adopted,
+}
+
+/// Transfer into a pool or staking account subaccount that the staking canister did not make itself
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ExternalDeposit {
+  pub id: Option<ExternalDepositId>,
+  /// Staking pool the target subaccount belongs to
+  pub pool_id: Option<StakingPoolId>,
+  /// Subaccount the deposit was sent to
+  pub target: Option<DepositTarget>,
+  /// Block index of the deposit
+  pub block_index: Option<BlockIndex>,
+  /// Account identifier the deposit came from (hex)
+  pub from: Option<String>,
+  /// Deposit amount (unit: e8s)
+  pub amount: Option<E8S>,
+  pub memo: Option<u64>,
+  /// Block time of the deposit
+  pub deposited_at: Option<TimestampNanos>,
+  pub status: Option<ExternalDepositStatus>,
+  /// Block index of the refund or of the adoption
+  pub settled_block_index: Option<BlockIndex>,
+  /// Metadata
+  pub meta: Option<MetaData>,
+}
+
+impl ExternalDeposit {
+  pub fn new(
+    pool_id: StakingPoolId,
+    target: DepositTarget,
+    block_index: BlockIndex,
+    from: &AccountIdentifier,
+    amount: E8S,
+    memo: u64,
+    deposited_at: TimestampNanos,
+  ) -> Self {
+    Self {
+      id: Some(EXTERNAL_DEPOSIT_ID.with(new_entity_id)),
+      pool_id: Some(pool_id),
+      target: Some(target),
+      block_index: Some(block_index),
+      from: Some(from.to_hex()),
+      amount: Some(amount),
+      memo: Some(memo),
+      deposited_at: Some(deposited_at),
+      status: Some(ExternalDepositStatus::Pending),
+      settled_block_index: None,
+      meta: Some(MetaData::init_create_scene()),
+    }
+  }
+
+  pub fn query_by_id(id: ExternalDepositId) -> Result<Self, String> {
+    EXTERNAL_DEPOSIT_MAP
+      .with(|map| map.borrow().get(&id))
+      .ok_or_else(|| format!("External deposit {} not found", id))
+  }
+
+  pub fn get_id(&self) -> ExternalDepositId {
+    self.id.unwrap_or_default()
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_target(&self) -> DepositTarget {
+    self.target.clone().unwrap_or(DepositTarget::StakingPool(self.get_pool_id()))
+  }
+
+  pub fn get_block_index(&self) -> BlockIndex {
+    self.block_index.unwrap_or_default()
+  }
+
+  pub fn get_from(&self) -> String {
+    self.from.clone().unwrap_or_default()
+  }
+
+  pub fn get_from_account_identifier(&self) -> Result<AccountIdentifier, String> {
+    AccountIdentifier::from_hex(&self.get_from()).map_err(|e| format!("Invalid deposit sender address: {}", e))
+  }
+
+  pub fn get_amount(&self) -> E8S {
+    self.amount.unwrap_or_default()
+  }
+
+  pub fn get_memo(&self) -> u64 {
+    self.memo.unwrap_or_default()
+  }
+
+  pub fn get_deposited_at(&self) -> TimestampNanos {
+    self.deposited_at.unwrap_or_default()
+  }
+
+  pub fn get_status(&self) -> ExternalDepositStatus {
+    self.status.clone().unwrap_or(ExternalDepositStatus::Pending)
+  }
+
+  pub fn get_settled_block_index(&self) -> Option<BlockIndex> {
+    self.settled_block_index
+  }
+
+  pub fn update_to_refunded(&mut self, block_index: BlockIndex) {
+    self.status = Some(ExternalDepositStatus::Refunded);
+    self.settled_block_index = Some(block_index);
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn update_to_adopted(&mut self, block_index: BlockIndex) {
+    self.status = Some(ExternalDepositStatus::Adopted);
+    self.settled_block_index = Some(block_index);
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  fn update_meta(&mut self) {
+    if let Some(meta) = &mut self.meta {
+      self.meta = Some(meta.update());
+    }
+  }
+
+  pub fn update_to_stable(&self) {
+    EXTERNAL_DEPOSIT_MAP.with(|map| {
+      map.borrow_mut().insert(self.get_id(), self.clone());
+    });
+  }
+}
+
+impl Storable for ExternalDeposit {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
@@ -0,0 +1,52 @@
+use candid::CandidType;
+use ic_ledger_types::BlockIndex;
+use serde::{Deserialize, Serialize};
+use types::{
+  staking::{ExternalDepositId, StakingAccountId, StakingPoolId},
+  TimestampNanos, E8S,
+};
+
+use super::stable_structures::{DepositTarget, ExternalDeposit};
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ExternalDepositQueryParams {
+  pub pool_id: Option<StakingPoolId>,
+  /// Handling status，refer to ExternalDepositStatus enumerate
+  pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ExternalDepositVo {
+  pub id: ExternalDepositId,
+  pub pool_id: StakingPoolId,
+  /// Staking account the deposit was sent to, None when it was sent to the staking pool
+  pub account_id: Option<StakingAccountId>,
+  pub block_index: BlockIndex,
+  pub from: String,
+  pub amount: E8S,
+  pub memo: u64,
+  pub deposited_at: TimestampNanos,
+  /// Handling status，refer to ExternalDepositStatus enumerate
+  pub status: String,
+  pub settled_block_index: Option<BlockIndex>,
+}
+
+impl From<ExternalDeposit> for ExternalDepositVo {
+  fn from(deposit: ExternalDeposit) -> Self {
+    Self {
+      id: deposit.get_id(),
+      pool_id: deposit.get_pool_id(),
+      account_id: match deposit.get_target() {
+        DepositTarget::StakingPool(_) => None,
+        DepositTarget::StakingAccount(account_id) => Some(account_id),
+      },
+      block_index: deposit.get_block_index(),
+      from: deposit.get_from(),
+      amount: deposit.get_amount(),
+      memo: deposit.get_memo(),
+      deposited_at: deposit.get_deposited_at(),
+      status: deposit.get_status().to_string(),
+      settled_block_index: deposit.get_settled_block_index(),
+    }
+  }
+}
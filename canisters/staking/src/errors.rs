@@ -0,0 +1,196 @@
+use std::{fmt, str::FromStr};
+
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+use types::{
+  product::e8s_to_value,
+  staking::{StakingAccountId, StakingPoolId},
+  sys::config::PermissionDenied,
+  TimestampNanos, E8S,
+};
+
+use crate::account::stable_structures::StakingAccountStatus;
+
+/// Message returned to the client for failures it cannot act on
+const SYSTEM_ERROR_MESSAGE: &str = "A system error has occurred. Please try again. ";
+
+/// Error of the staking API.
+/// The variants and their codes are stable, new failures are added as new variants.
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum StakingError {
+  /// 1001 Anonymous callers cannot call the method
+  AnonymousCaller,
+  /// 1002 The caller does not have the permission
+  PermissionDenied { permission_code: String },
+  /// 1003 The same operation is in progress
+  AlreadyProcessing { message: String },
+  /// 1004 An argument is invalid
+  InvalidArgument { message: String },
+  /// 1999 Unexpected failure
+  Internal { message: String },
+  /// 2001 The staking pool does not exist
+  PoolNotFound { pool_id: StakingPoolId },
+  /// 2002 The staking pool is not open for stake，status refer to StakingPoolStatus enumerate
+  PoolNotOpen {
+    pool_id: StakingPoolId,
+    status: String,
+    client_visible: bool,
+  },
+  /// 2003 The remaining size of the staking pool is less than the stake amount
+  PoolFull { pool_id: StakingPoolId, max_stakeable_amount: E8S },
+  /// 2004 The staking days are out of the term of the staking pool
+  TermInvalid { staking_days: u16, min_term: u16, max_term: u16 },
+  /// 2005 The stake amount breaks the limit of the staking pool
+  StakeAmountInvalid {
+    amount: E8S,
+    min_stake_amount: E8S,
+    max_stake_amount: E8S,
+    step_amount: E8S,
+    /// The most the caller can still stake in the staking pool
+    max_stakeable_amount: E8S,
+    message: String,
+  },
+  /// 2006 The staking pool does not have enough available funds
+  InsufficientPoolFunds { pool_id: StakingPoolId, available_funds: E8S },
+  /// 3001 The staking account does not exist
+  AccountNotFound { account_id: StakingAccountId },
+  /// 3002 The caller is not the owner of the staking account
+  NotAccountOwner { account_id: StakingAccountId },
+  /// 3003 The staking account is not in the status the operation requires，refer to StakingAccountStatus enumerate
+  AccountStatusInvalid {
+    account_id: StakingAccountId,
+    status: String,
+    expected_status: String,
+  },
+  /// 3004 The staking account cannot be unstaked early yet
+  EarlyUnstakeTooSoon {
+    account_id: StakingAccountId,
+    min_early_unstake_days: u16,
+    can_early_unstake_time: TimestampNanos,
+  },
+  /// 3005 The operation failed halfway, the recovery task completes it
+  RecoverableErrorPending { account_id: StakingAccountId, message: String },
+  /// 4001 The payment center could not be called
+  PayCenterUnavailable { message: String },
+  /// 4002 The payment center rejected the request
+  PayCenterRejected { message: String },
+  /// 4003 The ledger transfer failed
+  LedgerTransferFailed { message: String },
+  /// 4004 The wallet allowance does not cover the stake
+  InsufficientAllowance { message: String },
+  /// 4005 The NNS governance call failed
+  NnsGovernanceFailed { message: String },
+}
+
+impl StakingError {
+  /// Stable numeric code of the error
+  pub fn code(&self) -> u32 {
+    match self {
+      StakingError::AnonymousCaller => 1001,
+      StakingError::PermissionDenied { .. } => 1002,
+      StakingError::AlreadyProcessing { .. } => 1003,
+      StakingError::InvalidArgument { .. } => 1004,
+      StakingError::Internal { .. } => 1999,
+      StakingError::PoolNotFound { .. } => 2001,
+      StakingError::PoolNotOpen { .. } => 2002,
+      StakingError::PoolFull { .. } => 2003,
+      StakingError::TermInvalid { .. } => 2004,
+      StakingError::StakeAmountInvalid { .. } => 2005,
+      StakingError::InsufficientPoolFunds { .. } => 2006,
+      StakingError::AccountNotFound { .. } => 3001,
+      StakingError::NotAccountOwner { .. } => 3002,
+      StakingError::AccountStatusInvalid { .. } => 3003,
+      StakingError::EarlyUnstakeTooSoon { .. } => 3004,
+      StakingError::RecoverableErrorPending { .. } => 3005,
+      StakingError::PayCenterUnavailable { .. } => 4001,
+      StakingError::PayCenterRejected { .. } => 4002,
+      StakingError::LedgerTransferFailed { .. } => 4003,
+      StakingError::InsufficientAllowance { .. } => 4004,
+      StakingError::NnsGovernanceFailed { .. } => 4005,
+    }
+  }
+
+  pub fn internal(message: impl Into<String>) -> Self {
+    StakingError::Internal { message: message.into() }
+  }
+
+  pub fn invalid_argument(message: impl Into<String>) -> Self {
+    StakingError::InvalidArgument { message: message.into() }
+  }
+
+  pub fn already_processing(message: impl Into<String>) -> Self {
+    StakingError::AlreadyProcessing { message: message.into() }
+  }
+
+  pub fn ledger_transfer_failed(message: impl Into<String>) -> Self {
+    StakingError::LedgerTransferFailed { message: message.into() }
+  }
+
+  pub fn nns_governance_failed(message: impl Into<String>) -> Self {
+    StakingError::NnsGovernanceFailed { message: message.into() }
+  }
+}
+
+/// The message of the error, as returned by the methods that return a string error
+impl fmt::Display for StakingError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      StakingError::AnonymousCaller => write!(f, "Anonymous user cannot stake"),
+      StakingError::PermissionDenied { permission_code } => write!(f, "{}", String::permission_denied(permission_code)),
+      StakingError::AlreadyProcessing { message }
+      | StakingError::InvalidArgument { message }
+      | StakingError::Internal { message }
+      | StakingError::InsufficientAllowance { message }
+      | StakingError::NnsGovernanceFailed { message } => write!(f, "{}", message),
+      StakingError::PoolNotFound { pool_id } => write!(f, "Staking pool with ID {} not found", pool_id),
+      StakingError::PoolNotOpen { status, client_visible, .. } => {
+        write!(f, "Staking pool is not open, current status: {}, and client visible is {}", status, client_visible)
+      }
+      StakingError::PoolFull { max_stakeable_amount, .. } => {
+        write!(f, "Staking pool size is not enough, current remain size: {}", e8s_to_value(*max_stakeable_amount))
+      }
+      StakingError::TermInvalid {
+        staking_days,
+        min_term,
+        max_term,
+      } => {
+        if staking_days < min_term {
+          write!(f, "Minimum staking term is {} days", min_term)
+        } else {
+          write!(f, "Maximum staking term is {} days", max_term)
+        }
+      }
+      StakingError::StakeAmountInvalid { message, .. } => write!(f, "{}", message),
+      StakingError::InsufficientPoolFunds { .. } => write!(f, "The staking pool does not have enough available funds."),
+      StakingError::AccountNotFound { account_id } => write!(f, "Staking account not found: {}", account_id),
+      StakingError::NotAccountOwner { .. } => write!(f, "The caller is not the owner of the staking account"),
+      StakingError::AccountStatusInvalid { expected_status, .. } => match StakingAccountStatus::from_str(expected_status) {
+        Ok(StakingAccountStatus::InStake) => write!(f, "The staking account is not in stake"),
+        Ok(StakingAccountStatus::Released) => write!(f, "The staking account is not released"),
+        _ => write!(f, "The staking account is not in status {}", expected_status),
+      },
+      StakingError::EarlyUnstakeTooSoon { min_early_unstake_days, .. } => {
+        write!(f, "The staking account cannot be released within {} days.", min_early_unstake_days)
+      }
+      StakingError::RecoverableErrorPending { .. }
+      | StakingError::PayCenterUnavailable { .. }
+      | StakingError::PayCenterRejected { .. }
+      | StakingError::LedgerTransferFailed { .. } => write!(f, "{}", SYSTEM_ERROR_MESSAGE),
+    }
+  }
+}
+
+/// Errors of the helpers that still return a string error
+impl From<String> for StakingError {
+  fn from(message: String) -> Self {
+    StakingError::Internal { message }
+  }
+}
+
+impl PermissionDenied for StakingError {
+  fn permission_denied(permission_code: &str) -> Self {
+    StakingError::PermissionDenied {
+      permission_code: permission_code.to_string(),
+    }
+  }
+}
@@ -0,0 +1,255 @@
+use std::cell::RefCell;
+
+use candid::{types::reference::Func, Encode};
+use ic_cdk::management_canister::{
+  create_canister_with_extra_cycles, install_code, CanisterInstallMode, CanisterSettings, CreateCanisterArgs, InstallCodeArgs,
+};
+use ic_stable_structures::{memory_manager::MemoryId, Cell};
+use serde_bytes::ByteBuf;
+use system_configs_macro::has_permission_result;
+use types::{
+  stable_structures::Memory,
+  staking::event_archive::{
+    ArchivedEventLog, ArchivedEventLogRange, EventArchiveInitArgs, QueryArchivedEventLogsFn, MAX_ARCHIVED_EVENT_LOGS_PER_QUERY,
+  },
+  TimestampNanos,
+};
+
+use crate::{
+  guard_keys::{get_event_log_archive_guard_key, EVENT_LOG_ARCHIVE_GUARD_LEASE},
+  memory_ids::{STAKING_EVENT_ARCHIVE_STATE, STAKING_EVENT_ARCHIVE_WASM},
+  parallel_guard::EntryGuard,
+  MEMORY_MANAGER,
+};
+
+use super::{
+  stable_structures::{EventArchive, EventArchiveState, EventLog},
+  transport_structures::{EventArchiveSettingsDto, EventArchiveStatusVo, EventArchiveVo},
+  STAKING_EVENT_LOG_MAP,
+};
+
+const NANOS_PER_DAY: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Largest batch the archive canister takes in one call, bounded by the message size limit
+const MAX_EVENT_ARCHIVE_BATCH_SIZE: u64 = 2000;
+/// Instructions an archiving run may spend over all of its batches
+const EVENT_ARCHIVE_INSTRUCTION_BUDGET: u64 = 20_000_000_000;
+/// Most archived ranges returned by an event log query, the newest ones are kept
+const MAX_ARCHIVED_EVENT_LOG_RANGES: usize = 100;
+
+thread_local! {
+  /// Event archive canisters and the archiving settings
+  pub static EVENT_ARCHIVE_STATE_CELL: RefCell<Cell<EventArchiveState, Memory>> = RefCell::new(Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_EVENT_ARCHIVE_STATE))), EventArchiveState::default()).unwrap());
+
+  /// Wasm module installed into the spawned event archive canisters
+  pub static EVENT_ARCHIVE_WASM_CELL: RefCell<Cell<Vec<u8>, Memory>> = RefCell::new(Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_EVENT_ARCHIVE_WASM))), Vec::new()).unwrap());
+}
+
+pub fn get_event_archive_state() -> EventArchiveState {
+  EVENT_ARCHIVE_STATE_CELL.with(|cell| cell.borrow().get().clone())
+}
+
+fn save_event_archive_state(state: EventArchiveState) -> Result<(), String> {
+  EVENT_ARCHIVE_STATE_CELL.with(|cell| {
+    cell
+      .borrow_mut()
+      .set(state)
+      .map(|_| ())
+      .map_err(|e| format!("Failed to save the event archive state: {:?}", e))
+  })
+}
+
+/// Ranges of the archived event logs that overlap the time range, a time of 0 leaves that end open.
+/// At most MAX_ARCHIVED_EVENT_LOG_RANGES of the newest ranges are returned.
+pub fn get_archived_event_log_ranges(start_time: TimestampNanos, end_time: TimestampNanos) -> Vec<ArchivedEventLogRange> {
+  let mut ranges = Vec::new();
+  for archive in get_event_archive_state().get_archives() {
+    if archive.get_end() <= archive.get_start()
+      || (start_time > 0 && archive.get_last_event_time() < start_time)
+      || (end_time > 0 && archive.get_first_event_time() > end_time)
+    {
+      continue;
+    }
+
+    // Split into ranges that a single callback returns in full
+    let mut start = archive.get_start();
+    while start < archive.get_end() {
+      let length = (archive.get_end() - start).min(MAX_ARCHIVED_EVENT_LOGS_PER_QUERY);
+      ranges.push(ArchivedEventLogRange {
+        start,
+        length,
+        callback: QueryArchivedEventLogsFn(Func {
+          principal: archive.get_canister_id(),
+          method: "get_event_logs".to_string(),
+        }),
+      });
+      start += length;
+    }
+  }
+
+  if ranges.len() > MAX_ARCHIVED_EVENT_LOG_RANGES {
+    ranges.drain(..ranges.len() - MAX_ARCHIVED_EVENT_LOG_RANGES);
+  }
+  ranges
+}
+
+/// Move the event logs past the archive age to the current archive canister batch by batch,
+/// until none is left or the instruction budget of the run is spent.
+/// Nothing is archived until an archive canister is spawned.
+pub async fn archive_event_logs() -> Result<u64, String> {
+  let _entry_guard = EntryGuard::with_lease(get_event_log_archive_guard_key(), EVENT_LOG_ARCHIVE_GUARD_LEASE)
+    .map_err(|_| "The event log archiving is already running".to_string())?;
+
+  let mut archived = 0;
+  loop {
+    let batch_size = get_event_archive_state().get_batch_size();
+    let batch_archived = archive_event_log_batch().await?;
+    archived += batch_archived;
+
+    // A short batch means the rest of the event logs are not old enough yet
+    if batch_archived == 0 || batch_archived < batch_size || ic_cdk::api::call_context_instruction_counter() >= EVENT_ARCHIVE_INSTRUCTION_BUDGET {
+      return Ok(archived);
+    }
+  }
+}
+
+/// Move the oldest batch of event logs past the archive age to the current archive canister.
+/// A batch is deleted locally only after the archive canister accepted it,
+/// the archive canister skips the ids it already holds when a batch is sent again.
+async fn archive_event_log_batch() -> Result<u64, String> {
+  let state = get_event_archive_state();
+  let Some(archive) = state.get_archives().last().cloned() else {
+    return Ok(0);
+  };
+
+  let archive_before = ic_cdk::api::time().saturating_sub(state.get_archive_after_days() * NANOS_PER_DAY);
+  let batch: Vec<EventLog> = STAKING_EVENT_LOG_MAP.with(|map| {
+    map
+      .borrow()
+      .values()
+      .take(state.get_batch_size() as usize)
+      .take_while(|event_log| event_log.get_event_time() < archive_before)
+      .collect()
+  });
+  if batch.is_empty() {
+    return Ok(0);
+  }
+
+  let archived_event_logs: Vec<ArchivedEventLog> = batch.iter().map(EventLog::to_archived).collect();
+  let result = ic_cdk::call::Call::unbounded_wait(archive.get_canister_id(), "append_event_logs")
+    .with_arg(archived_event_logs)
+    .await
+    .map_err(|e| format!("Call event archive failed: {:?}", e))?;
+  result
+    .candid::<Result<u64, String>>()
+    .map_err(|e| format!("Failed to decode the event archive response: {:?}", e))??;
+
+  for event_log in &batch {
+    event_log.remove_from_stable_memory();
+  }
+
+  let mut state = get_event_archive_state();
+  let mut archives = state.get_archives();
+  if let Some(current) = archives.iter_mut().find(|current| current.get_canister_id() == archive.get_canister_id()) {
+    current.extend(&batch);
+  }
+  state.archives = Some(archives);
+  save_event_archive_state(state)?;
+
+  Ok(batch.len() as u64)
+}
+
+/// Query the event archive canisters and the archiving settings
+#[ic_cdk::query]
+fn get_event_archive_status() -> EventArchiveStatusVo {
+  let state = get_event_archive_state();
+  EventArchiveStatusVo {
+    archives: state.get_archives().iter().map(EventArchiveVo::from).collect(),
+    archive_after_days: state.get_archive_after_days(),
+    batch_size: state.get_batch_size(),
+    has_archive_wasm: EVENT_ARCHIVE_WASM_CELL.with(|cell| !cell.borrow().get().is_empty()),
+    local_event_logs: STAKING_EVENT_LOG_MAP.with(|map| map.borrow().len()),
+  }
+}
+
+/// Upload the wasm module of the event archive canister, gzipped or not, used by the archives spawned afterwards
+#[ic_cdk::update]
+#[has_permission_result("staking::event_log::archive", audit)]
+fn set_event_archive_wasm(wasm_module: ByteBuf) -> Result<(), String> {
+  if wasm_module.is_empty() {
+    return Err("The wasm module is empty".to_string());
+  }
+
+  EVENT_ARCHIVE_WASM_CELL.with(|cell| {
+    cell
+      .borrow_mut()
+      .set(wasm_module.into_vec())
+      .map(|_| ())
+      .map_err(|e| format!("Failed to save the event archive wasm: {:?}", e))
+  })
+}
+
+#[ic_cdk::update]
+#[has_permission_result("staking::event_log::archive", audit)]
+fn set_event_archive_settings(dto: EventArchiveSettingsDto) -> Result<EventArchiveStatusVo, String> {
+  if dto.batch_size == 0 || dto.batch_size > MAX_EVENT_ARCHIVE_BATCH_SIZE {
+    return Err(format!("The batch size must be between 1 and {}", MAX_EVENT_ARCHIVE_BATCH_SIZE));
+  }
+
+  let mut state = get_event_archive_state();
+  state.archive_after_days = Some(dto.archive_after_days);
+  state.batch_size = Some(dto.batch_size);
+  save_event_archive_state(state)?;
+
+  Ok(get_event_archive_status())
+}
+
+/// Spawn a new event archive canister with the uploaded wasm, it takes all later batches.
+/// The staking canister and the caller control the archive canister.
+#[ic_cdk::update]
+#[has_permission_result("staking::event_log::archive", audit)]
+async fn spawn_event_log_archive(cycles: u128) -> Result<EventArchiveVo, String> {
+  let _entry_guard =
+    EntryGuard::new(get_event_log_archive_guard_key()).map_err(|_| "The event log archiving is running, please try again later".to_string())?;
+
+  let wasm_module = EVENT_ARCHIVE_WASM_CELL.with(|cell| cell.borrow().get().clone());
+  if wasm_module.is_empty() {
+    return Err("Upload the event archive wasm module first".to_string());
+  }
+
+  let staking_canister_id = ic_cdk::api::canister_self();
+  let settings = CanisterSettings {
+    controllers: Some(vec![staking_canister_id, ic_cdk::api::msg_caller()]),
+    ..Default::default()
+  };
+  let canister_id = create_canister_with_extra_cycles(&CreateCanisterArgs { settings: Some(settings) }, cycles)
+    .await
+    .map_err(|e| format!("Failed to create the event archive canister: {:?}", e))?
+    .canister_id;
+
+  let init_args =
+    Encode!(&EventArchiveInitArgs { staking_canister_id }).map_err(|e| format!("Failed to encode the event archive init args: {:?}", e))?;
+  install_code(&InstallCodeArgs {
+    mode: CanisterInstallMode::Install,
+    canister_id,
+    wasm_module,
+    arg: init_args,
+  })
+  .await
+  .map_err(|e| format!("Failed to install the event archive canister {}: {:?}", canister_id, e))?;
+
+  let archive = EventArchive::new(canister_id);
+  let mut state = get_event_archive_state();
+  let mut archives = state.get_archives();
+  archives.push(archive.clone());
+  state.archives = Some(archives);
+  save_event_archive_state(state)?;
+
+  Ok(EventArchiveVo::from(&archive))
+}
+
+/// Archive the old event logs right away
+#[ic_cdk::update]
+#[has_permission_result("staking::event_log::archive", audit)]
+async fn archive_event_logs_now() -> Result<u64, String> {
+  archive_event_logs().await
+}
@@ -1,15 +1,29 @@
 use std::{cell::RefCell, str::FromStr};
 
+use candid::Principal;
 use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
 use stable_structures::EventLog;
-use transport_structures::{EventLogQueryParams, EventTypeCode, StakingEventLogPageRequest, StakingEventLogPageResponse};
-use types::{stable_structures::Memory, EntityId};
+use transport_structures::{
+  EventLogQueryParams, EventTypeCode, StakingEventLogCursorPageRequest, StakingEventLogCursorPageResponse, StakingEventLogPageRequest,
+  StakingEventLogPageResponse,
+};
+use types::{
+  composite_entity_index::{get_indexed_ids_page, CompositeEntityIndex},
+  pagination::{collect_page, SortDirection, MAX_CURSOR_PAGE_SCAN},
+  stable_structures::Memory,
+  staking::{StakingAccountId, StakingPoolId},
+  EntityId,
+};
 
 use crate::{
-  memory_ids::{STAKING_EVENT_LOG, STAKING_EVENT_LOG_SEQ},
-  MEMORY_MANAGER,
+  memory_ids::{
+    STAKING_EVENT_LOG, STAKING_EVENT_LOG_ACCOUNT_INDEX, STAKING_EVENT_LOG_POOL_INDEX, STAKING_EVENT_LOG_PRINCIPAL_INDEX, STAKING_EVENT_LOG_SEQ,
+  },
+  migrations::{EVENT_LOG_INDEX_SCHEMA_VERSION, SCHEMA_MIGRATOR},
+  system_configs, MEMORY_MANAGER,
 };
 
+pub mod archive;
 pub mod stable_structures;
 pub mod stake_and_unstake_events;
 pub mod stake_reward_events;
@@ -29,52 +43,129 @@ thread_local! {
       MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_EVENT_LOG))),
     )
   );
+
+  /// Event logs indexed by the staking account they belong to
+  pub static STAKING_EVENT_LOG_ACCOUNT_INDEX_MAP: RefCell<CompositeEntityIndex<StakingAccountId>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_EVENT_LOG_ACCOUNT_INDEX))),
+    )
+  );
+
+  /// Event logs indexed by the staking pool they belong to
+  pub static STAKING_EVENT_LOG_POOL_INDEX_MAP: RefCell<CompositeEntityIndex<StakingPoolId>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_EVENT_LOG_POOL_INDEX))),
+    )
+  );
+
+  /// Event logs indexed by the principal that triggered them
+  pub static STAKING_EVENT_LOG_PRINCIPAL_INDEX_MAP: RefCell<CompositeEntityIndex<Principal>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_EVENT_LOG_PRINCIPAL_INDEX))),
+    )
+  );
 }
 
-/// Query the stake event log，Only check the last two thousand
+/// A page of the event log ids in the index of the account, pool or principal condition, continuing after the `after` id,
+/// None when there is no such condition or the indexes are still being built
+fn get_indexed_event_log_ids(params: &EventLogQueryParams, after: Option<EntityId>, limit: usize, descending: bool) -> Option<Vec<EntityId>> {
+  if !SCHEMA_MIGRATOR.is_migrated_to(EVENT_LOG_INDEX_SCHEMA_VERSION) {
+    return None;
+  }
+
+  if let Some(account_id) = params.account_id {
+    Some(STAKING_EVENT_LOG_ACCOUNT_INDEX_MAP.with(|map| get_indexed_ids_page(map, &account_id, after, limit, descending)))
+  } else if let Some(pool_id) = params.pool_id {
+    Some(STAKING_EVENT_LOG_POOL_INDEX_MAP.with(|map| get_indexed_ids_page(map, &pool_id, after, limit, descending)))
+  } else {
+    params
+      .principal
+      .map(|principal| STAKING_EVENT_LOG_PRINCIPAL_INDEX_MAP.with(|map| get_indexed_ids_page(map, &principal, after, limit, descending)))
+  }
+}
+
+/// Events triggered by a principal are only listed to the callers allowed to query the event log of every user
+const EVENT_LOG_PRINCIPAL_QUERY_PERMISSION: &str = "staking::event_log::query";
+
+fn check_principal_filter(params: &EventLogQueryParams) -> Result<(), String> {
+  if params.principal.is_some() && !system_configs::has_permission(EVENT_LOG_PRINCIPAL_QUERY_PERMISSION) {
+    return Err(format!("Caller does not have permission to call {}", EVENT_LOG_PRINCIPAL_QUERY_PERMISSION));
+  }
+  Ok(())
+}
+
+/// Query the stake event log，Only check the last two thousand unless an account, pool or principal is given and the indexes are built.
+/// The indexed events are counted up to the newest MAX_CURSOR_PAGE_SCAN, older ones are reached through query_event_logs_by_cursor.
 #[ic_cdk::query]
 fn query_event_logs(request: StakingEventLogPageRequest) -> StakingEventLogPageResponse {
-  let StakingEventLogPageRequest {
-    page,
-    page_size,
-    params: EventLogQueryParams {
-      event_type,
-      start_time,
-      end_time,
-    },
-  } = request;
-  let event_type_code = match EventTypeCode::from_str(&event_type) {
+  let StakingEventLogPageRequest { page, page_size, params } = request;
+  if let Err(e) = check_principal_filter(&params) {
+    ic_cdk::trap(&e);
+  }
+  let EventLogQueryParams {
+    event_type,
+    start_time,
+    end_time,
+    ..
+  } = &params;
+  let (start_time, end_time) = (*start_time, *end_time);
+  let event_type_code = match EventTypeCode::from_str(event_type) {
     Ok(code) => code,
     Err(_) => EventTypeCode::Undefined,
   };
 
-  let start = (page - 1) * page_size;
-
   STAKING_EVENT_LOG_MAP.with(|map| {
     let map = map.borrow();
-    let filter_records: Vec<EventLog> = map
-      .values()
-      .into_iter()
-      .rev()
-      .take(2000)
-      .filter(|event_log| event_type_code.is_match(&event_log.get_event_type()))
-      .filter(|event_log| {
-        if start_time > 0 {
-          event_log.get_event_time() >= start_time
-        } else {
-          true
-        }
-      })
-      .filter(|event_log| if end_time > 0 { event_log.get_event_time() <= end_time } else { true })
-      .collect();
-
-    let total = filter_records.len() as u32;
+    let is_match = |event_log: &EventLog| {
+      event_type_code.is_match(&event_log.get_event_type())
+        && (start_time == 0 || event_log.get_event_time() >= start_time)
+        && (end_time == 0 || event_log.get_event_time() <= end_time)
+        && params.is_entity_match(event_log)
+    };
+    // Newest first, only the requested page is kept
+    let (total, records) = match get_indexed_event_log_ids(&params, None, MAX_CURSOR_PAGE_SCAN, true) {
+      Some(event_ids) => collect_page(page, page_size, event_ids.into_iter().filter_map(|id| map.get(&id)).filter(is_match), |event_log| {
+        event_log
+      }),
+      None => collect_page(page, page_size, map.values().rev().take(2000).filter(is_match), |event_log| event_log),
+    };
 
     StakingEventLogPageResponse {
       page,
       page_size,
       total,
-      records: filter_records.iter().skip(start as usize).take(page_size as usize).cloned().collect(),
+      records,
+      // The archived ranges do not depend on the page, they are returned with the first page only
+      archived_event_logs: if page <= 1 {
+        archive::get_archived_event_log_ranges(start_time, end_time)
+      } else {
+        vec![]
+      },
     }
   })
 }
+
+/// Stream the stake event log page by page over the whole log, newest first unless ascending is requested
+#[ic_cdk::query]
+fn query_event_logs_by_cursor(request: StakingEventLogCursorPageRequest) -> Result<StakingEventLogCursorPageResponse, String> {
+  let position = request.position(&["id", "event_time"])?;
+  let params = request.params;
+  check_principal_filter(&params)?;
+  let event_type_code = EventTypeCode::from_str(&params.event_type).unwrap_or(EventTypeCode::Undefined);
+  let filter_map = |event_log: &EventLog| {
+    (event_type_code.is_match(&event_log.get_event_type())
+      && (params.start_time == 0 || event_log.get_event_time() >= params.start_time)
+      && (params.end_time == 0 || event_log.get_event_time() <= params.end_time)
+      && params.is_entity_match(event_log))
+    .then(|| event_log.clone())
+  };
+
+  STAKING_EVENT_LOG_MAP.with(|map| {
+    let map = map.borrow();
+    let descending = position.direction == SortDirection::Desc;
+    Ok(match get_indexed_event_log_ids(&params, position.after, MAX_CURSOR_PAGE_SCAN, descending) {
+      Some(event_ids) => position.scan_page_ids(event_ids.into_iter(), |id| map.get(&id), filter_map),
+      None => position.scan_map(&map, filter_map),
+    })
+  })
+}
@@ -5,9 +5,10 @@ use ic_ledger_types::BlockIndex;
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::{Deserialize, Serialize};
 use types::{
+  composite_entity_index::{add_indexed_id, remove_indexed_id},
   stable_structures::new_entity_id,
-  staking::{StakingAccountId, StakingPoolId},
-  EntityId, TimestampNanos,
+  staking::{event_archive::ArchivedEventLog, StakingAccountId, StakingEventLogId, StakingPoolId},
+  CanisterId, EntityId, TimestampNanos,
 };
 
 use crate::{
@@ -16,7 +17,10 @@ use crate::{
   reward::stable_structures::StakingReward,
 };
 
-use super::{STAKING_EVENT_LOG_ID, STAKING_EVENT_LOG_MAP};
+use super::{
+  STAKING_EVENT_LOG_ACCOUNT_INDEX_MAP, STAKING_EVENT_LOG_ID, STAKING_EVENT_LOG_MAP, STAKING_EVENT_LOG_POOL_INDEX_MAP,
+  STAKING_EVENT_LOG_PRINCIPAL_INDEX_MAP,
+};
 
 /// Event log structure，Ability to store event logs，Used for querying、Analysis and replay
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -71,12 +75,57 @@ impl EventLog {
     STAKING_EVENT_LOG_MAP.with(|map| {
       map.borrow_mut().insert(self.get_id(), self.clone());
     });
+    self.add_indexes();
+  }
+
+  /// Delete the event log after it was moved to an archive canister
+  pub fn remove_from_stable_memory(&self) {
+    STAKING_EVENT_LOG_MAP.with(|map| {
+      map.borrow_mut().remove(&self.get_id());
+    });
+    self.remove_indexes();
+  }
+
+  /// Index the event log by the staking account, the staking pool and the trigger principal
+  pub fn add_indexes(&self) {
+    let id = self.get_id();
+    let event_type = self.get_event_type();
+    if let Some(account_id) = event_type.get_account_id() {
+      STAKING_EVENT_LOG_ACCOUNT_INDEX_MAP.with(|map| add_indexed_id(map, &account_id, id));
+    }
+    if let Some(pool_id) = event_type.get_pool_id() {
+      STAKING_EVENT_LOG_POOL_INDEX_MAP.with(|map| add_indexed_id(map, &pool_id, id));
+    }
+    STAKING_EVENT_LOG_PRINCIPAL_INDEX_MAP.with(|map| add_indexed_id(map, &self.get_trigger(), id));
+  }
+
+  pub fn remove_indexes(&self) {
+    let id = self.get_id();
+    let event_type = self.get_event_type();
+    if let Some(account_id) = event_type.get_account_id() {
+      STAKING_EVENT_LOG_ACCOUNT_INDEX_MAP.with(|map| remove_indexed_id(map, &account_id, id));
+    }
+    if let Some(pool_id) = event_type.get_pool_id() {
+      STAKING_EVENT_LOG_POOL_INDEX_MAP.with(|map| remove_indexed_id(map, &pool_id, id));
+    }
+    STAKING_EVENT_LOG_PRINCIPAL_INDEX_MAP.with(|map| remove_indexed_id(map, &self.get_trigger(), id));
+  }
+
+  /// Convert to the archived form, the event log is kept in its candid encoding
+  pub fn to_archived(&self) -> ArchivedEventLog {
+    ArchivedEventLog {
+      id: self.get_id(),
+      event_time: self.get_event_time(),
+      principal: self.get_trigger(),
+      event_log: Encode!(self).unwrap(),
+    }
   }
 }
 
 /// On-chain address
 pub type OnChainAddress = String;
 pub type PayCenterCanisterId = String;
+pub type WalletPrincipal = String;
 pub type ErrorMessage = String;
 
 /// Event type enumeration
@@ -126,6 +175,98 @@ pub enum EventType {
   DissolvePayCenterReceiveStart(StakingAccountId, PayCenterCanisterId, BlockIndex),
   DissolvePayCenterReceiveOk(StakingAccountId, PayCenterCanisterId, BlockIndex, u64),
   DissolvePayCenterReceiveErr(StakingAccountId, PayCenterCanisterId, ErrorMessage),
+
+  DissolveWalletTransferStart(StakingAccountId, WalletPrincipal),
+  DissolveWalletTransferOk(StakingAccountId, WalletPrincipal, BlockIndex),
+  DissolveWalletTransferErr(StakingAccountId, WalletPrincipal, ErrorMessage),
+}
+
+impl EventType {
+  /// The staking account the event belongs to
+  pub fn get_account_id(&self) -> Option<StakingAccountId> {
+    match self {
+      EventType::CreateStakingPool(_)
+      | EventType::UpdateStakingPool(_)
+      | EventType::ChangeStakingPoolStatus(_, _)
+      | EventType::ChangeStakingPoolClientVisible(_, _) => None,
+
+      EventType::CreateStakingAccount(account)
+      | EventType::UpdateStakingAccount(account)
+      | EventType::Stake(_, account)
+      | EventType::Unstake(_, account)
+      | EventType::Dissolve(account)
+      | EventType::DistributeReward(_, account) => Some(account.get_id()),
+      EventType::RewardReceived(reward) => Some(reward.get_account_id()),
+
+      EventType::DeleteStakingAccount(account_id)
+      | EventType::StakePayCenterTransferStart(account_id, _)
+      | EventType::StakePayCenterTransferOk(account_id, _, _)
+      | EventType::StakePayCenterTransferErr(account_id, _, _)
+      | EventType::StakeTransferStart(account_id, _)
+      | EventType::StakeTransferOk(account_id, _, _)
+      | EventType::StakeTransferErr(account_id, _, _)
+      | EventType::UnstakeTransferStart(account_id, _)
+      | EventType::UnstakeTransferOk(account_id, _, _)
+      | EventType::UnstakeTransferErr(account_id, _, _)
+      | EventType::UnstakePenaltyTransferStart(account_id, _)
+      | EventType::UnstakePenaltyTransferOk(account_id, _, _)
+      | EventType::UnstakePenaltyTransferErr(account_id, _, _)
+      | EventType::UnstakePenaltyPayCenterStart(account_id, _)
+      | EventType::UnstakePenaltyPayCenterOk(account_id, _, _)
+      | EventType::UnstakePenaltyPayCenterErr(account_id, _, _)
+      | EventType::DissolvePayCenterTransferStart(account_id, _)
+      | EventType::DissolvePayCenterTransferOk(account_id, _, _)
+      | EventType::DissolvePayCenterTransferErr(account_id, _, _)
+      | EventType::DissolvePayCenterReceiveStart(account_id, _, _)
+      | EventType::DissolvePayCenterReceiveOk(account_id, _, _, _)
+      | EventType::DissolvePayCenterReceiveErr(account_id, _, _)
+      | EventType::DissolveWalletTransferStart(account_id, _)
+      | EventType::DissolveWalletTransferOk(account_id, _, _)
+      | EventType::DissolveWalletTransferErr(account_id, _, _) => Some(*account_id),
+    }
+  }
+
+  /// The staking pool the event belongs to, the pay center events only carry the staking account
+  pub fn get_pool_id(&self) -> Option<StakingPoolId> {
+    match self {
+      EventType::CreateStakingPool(pool) | EventType::UpdateStakingPool(pool) | EventType::Stake(pool, _) | EventType::Unstake(pool, _) => {
+        Some(pool.get_id())
+      }
+      EventType::ChangeStakingPoolStatus(pool_id, _) | EventType::ChangeStakingPoolClientVisible(pool_id, _) => Some(*pool_id),
+
+      EventType::CreateStakingAccount(account) | EventType::UpdateStakingAccount(account) | EventType::Dissolve(account) => {
+        Some(account.get_pool_id())
+      }
+      EventType::DistributeReward(reward, _) | EventType::RewardReceived(reward) => Some(reward.get_pool_id()),
+
+      EventType::StakeTransferStart(_, pool_id)
+      | EventType::StakeTransferOk(_, pool_id, _)
+      | EventType::StakeTransferErr(_, pool_id, _)
+      | EventType::UnstakeTransferStart(_, pool_id)
+      | EventType::UnstakeTransferOk(_, pool_id, _)
+      | EventType::UnstakeTransferErr(_, pool_id, _)
+      | EventType::UnstakePenaltyTransferStart(_, pool_id)
+      | EventType::UnstakePenaltyTransferOk(_, pool_id, _)
+      | EventType::UnstakePenaltyTransferErr(_, pool_id, _) => Some(*pool_id),
+
+      EventType::DeleteStakingAccount(_)
+      | EventType::StakePayCenterTransferStart(_, _)
+      | EventType::StakePayCenterTransferOk(_, _, _)
+      | EventType::StakePayCenterTransferErr(_, _, _)
+      | EventType::UnstakePenaltyPayCenterStart(_, _)
+      | EventType::UnstakePenaltyPayCenterOk(_, _, _)
+      | EventType::UnstakePenaltyPayCenterErr(_, _, _)
+      | EventType::DissolvePayCenterTransferStart(_, _)
+      | EventType::DissolvePayCenterTransferOk(_, _, _)
+      | EventType::DissolvePayCenterTransferErr(_, _, _)
+      | EventType::DissolvePayCenterReceiveStart(_, _, _)
+      | EventType::DissolvePayCenterReceiveOk(_, _, _, _)
+      | EventType::DissolvePayCenterReceiveErr(_, _, _)
+      | EventType::DissolveWalletTransferStart(_, _)
+      | EventType::DissolveWalletTransferOk(_, _, _)
+      | EventType::DissolveWalletTransferErr(_, _, _) => None,
+    }
+  }
 }
 
 impl Storable for EventLog {
@@ -139,3 +280,99 @@ impl Storable for EventLog {
 
   const BOUND: Bound = Bound::Unbounded;
 }
+
+/// Event logs older than this are moved to the archive canister unless configured otherwise
+pub const DEFAULT_EVENT_ARCHIVE_AFTER_DAYS: u64 = 90;
+/// Event logs moved to the archive canister per call unless configured otherwise
+pub const DEFAULT_EVENT_ARCHIVE_BATCH_SIZE: u64 = 500;
+
+/// An event archive canister and the range of event logs it holds
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct EventArchive {
+  pub canister_id: Option<CanisterId>,
+  /// Id of the first event log moved to the archive, None until the first batch
+  pub start: Option<StakingEventLogId>,
+  /// Id after the last event log moved to the archive
+  pub end: Option<StakingEventLogId>,
+  pub first_event_time: Option<TimestampNanos>,
+  pub last_event_time: Option<TimestampNanos>,
+  pub create_time: Option<TimestampNanos>,
+}
+
+impl EventArchive {
+  pub fn new(canister_id: CanisterId) -> Self {
+    Self {
+      canister_id: Some(canister_id),
+      create_time: Some(ic_cdk::api::time()),
+      ..Default::default()
+    }
+  }
+
+  pub fn get_canister_id(&self) -> CanisterId {
+    self.canister_id.unwrap_or(Principal::anonymous())
+  }
+
+  pub fn get_start(&self) -> StakingEventLogId {
+    self.start.unwrap_or_default()
+  }
+
+  pub fn get_end(&self) -> StakingEventLogId {
+    self.end.unwrap_or_default()
+  }
+
+  pub fn get_first_event_time(&self) -> TimestampNanos {
+    self.first_event_time.unwrap_or_default()
+  }
+
+  pub fn get_last_event_time(&self) -> TimestampNanos {
+    self.last_event_time.unwrap_or_default()
+  }
+
+  /// Record a batch of event logs, in ascending id order, that the archive canister accepted
+  pub fn extend(&mut self, archived: &[EventLog]) {
+    let (Some(first), Some(last)) = (archived.first(), archived.last()) else {
+      return;
+    };
+    if self.start.is_none() {
+      self.start = Some(first.get_id());
+      self.first_event_time = Some(first.get_event_time());
+    }
+    self.end = Some(last.get_id() + 1);
+    self.last_event_time = Some(last.get_event_time());
+  }
+}
+
+/// Event archive canisters and the archiving settings of the event logs
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct EventArchiveState {
+  /// Archive canisters in the order they were spawned, the last one takes the new batches
+  pub archives: Option<Vec<EventArchive>>,
+  pub archive_after_days: Option<u64>,
+  pub batch_size: Option<u64>,
+}
+
+impl Storable for EventArchiveState {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+impl EventArchiveState {
+  pub fn get_archives(&self) -> Vec<EventArchive> {
+    self.archives.clone().unwrap_or_default()
+  }
+
+  pub fn get_archive_after_days(&self) -> u64 {
+    self.archive_after_days.unwrap_or(DEFAULT_EVENT_ARCHIVE_AFTER_DAYS)
+  }
+
+  pub fn get_batch_size(&self) -> u64 {
+    self.batch_size.unwrap_or(DEFAULT_EVENT_ARCHIVE_BATCH_SIZE)
+  }
+}
@@ -1,7 +1,7 @@
 use ic_ledger_types::BlockIndex;
 use types::staking::{StakingAccountId, StakingPoolId};
 
-use super::stable_structures::{ErrorMessage, EventLog, EventType, PayCenterCanisterId};
+use super::stable_structures::{ErrorMessage, EventLog, EventType, PayCenterCanisterId, WalletPrincipal};
 
 /// Stake: Event log of Staking transfer initiated from the payment center to the Staking account-start
 pub fn save_stake_pay_center_transfer_start_event(account_id: StakingAccountId, pay_center_canister_id: PayCenterCanisterId) {
@@ -128,3 +128,18 @@ pub fn save_dissolve_pay_center_receive_fail_event(
 ) {
   EventLog::new(EventType::DissolvePayCenterReceiveErr(account_id, pay_center_canister_id, error_massage)).save_to_stable_memory()
 }
+
+/// Dissolve：On-chain transfer event log from Staking account back to the wallet-start
+pub fn save_dissolve_wallet_transfer_start_event(account_id: StakingAccountId, wallet: WalletPrincipal) {
+  EventLog::new(EventType::DissolveWalletTransferStart(account_id, wallet)).save_to_stable_memory()
+}
+
+/// Dissolve：On-chain transfer event log from Staking account back to the wallet-success
+pub fn save_dissolve_wallet_transfer_ok_event(account_id: StakingAccountId, wallet: WalletPrincipal, tx_id: BlockIndex) {
+  EventLog::new(EventType::DissolveWalletTransferOk(account_id, wallet, tx_id)).save_to_stable_memory()
+}
+
+/// Dissolve：On-chain transfer event log from Staking account back to the wallet-fail
+pub fn save_dissolve_wallet_transfer_fail_event(account_id: StakingAccountId, wallet: WalletPrincipal, error_massage: ErrorMessage) {
+  EventLog::new(EventType::DissolveWalletTransferErr(account_id, wallet, error_massage)).save_to_stable_memory()
+}
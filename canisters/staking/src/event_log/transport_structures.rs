@@ -1,12 +1,13 @@
-use candid::CandidType;
+use candid::{CandidType, Principal};
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 use types::{
-  pagination::{PageRequest, PageResponse},
-  TimestampNanos,
+  pagination::{CursorPageRequest, CursorPageResponse, PageRequest},
+  staking::{event_archive::ArchivedEventLogRange, StakingAccountId, StakingPoolId},
+  EntityId, TimestampNanos,
 };
 
-use super::stable_structures::{EventLog, EventType};
+use super::stable_structures::{EventArchive, EventLog, EventType};
 
 #[derive(EnumString, Display, Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
 pub enum EventTypeCode {
@@ -49,6 +50,30 @@ pub enum EventTypeCode {
   /// Rewards are credited
   #[strum(serialize = "11")]
   RewardReceived,
+  /// Stake transfer from the payment center to the staking account, start, success and failure
+  #[strum(serialize = "12")]
+  StakePayCenterTransfer,
+  /// Stake transfer from the staking account to the staking pool, start, success and failure
+  #[strum(serialize = "13")]
+  StakeTransfer,
+  /// Unstake transfer from the staking pool to the staking account, start, success and failure
+  #[strum(serialize = "14")]
+  UnstakeTransfer,
+  /// Unstake penalty transfer from the staking pool to the payment center, start, success and failure
+  #[strum(serialize = "15")]
+  UnstakePenaltyTransfer,
+  /// Unstake penalty accounting notified to the payment center, start, success and failure
+  #[strum(serialize = "16")]
+  UnstakePenaltyPayCenter,
+  /// Dissolve transfer from the staking account to the payment center, start, success and failure
+  #[strum(serialize = "17")]
+  DissolvePayCenterTransfer,
+  /// Dissolve accounting received by the payment center, start, success and failure
+  #[strum(serialize = "18")]
+  DissolvePayCenterReceive,
+  /// Dissolve transfer from the staking account back to the wallet, start, success and failure
+  #[strum(serialize = "19")]
+  DissolveWalletTransfer,
 }
 
 impl EventTypeCode {
@@ -102,6 +127,38 @@ impl EventTypeCode {
         EventType::RewardReceived(_) => true,
         _ => false,
       },
+      EventTypeCode::StakePayCenterTransfer => matches!(
+        event_type,
+        EventType::StakePayCenterTransferStart(..) | EventType::StakePayCenterTransferOk(..) | EventType::StakePayCenterTransferErr(..)
+      ),
+      EventTypeCode::StakeTransfer => matches!(
+        event_type,
+        EventType::StakeTransferStart(..) | EventType::StakeTransferOk(..) | EventType::StakeTransferErr(..)
+      ),
+      EventTypeCode::UnstakeTransfer => matches!(
+        event_type,
+        EventType::UnstakeTransferStart(..) | EventType::UnstakeTransferOk(..) | EventType::UnstakeTransferErr(..)
+      ),
+      EventTypeCode::UnstakePenaltyTransfer => matches!(
+        event_type,
+        EventType::UnstakePenaltyTransferStart(..) | EventType::UnstakePenaltyTransferOk(..) | EventType::UnstakePenaltyTransferErr(..)
+      ),
+      EventTypeCode::UnstakePenaltyPayCenter => matches!(
+        event_type,
+        EventType::UnstakePenaltyPayCenterStart(..) | EventType::UnstakePenaltyPayCenterOk(..) | EventType::UnstakePenaltyPayCenterErr(..)
+      ),
+      EventTypeCode::DissolvePayCenterTransfer => matches!(
+        event_type,
+        EventType::DissolvePayCenterTransferStart(..) | EventType::DissolvePayCenterTransferOk(..) | EventType::DissolvePayCenterTransferErr(..)
+      ),
+      EventTypeCode::DissolvePayCenterReceive => matches!(
+        event_type,
+        EventType::DissolvePayCenterReceiveStart(..) | EventType::DissolvePayCenterReceiveOk(..) | EventType::DissolvePayCenterReceiveErr(..)
+      ),
+      EventTypeCode::DissolveWalletTransfer => matches!(
+        event_type,
+        EventType::DissolveWalletTransferStart(..) | EventType::DissolveWalletTransferOk(..) | EventType::DissolveWalletTransferErr(..)
+      ),
       EventTypeCode::Undefined => true,
     }
   }
@@ -125,7 +182,76 @@ pub struct EventLogQueryParams {
   pub start_time: TimestampNanos,
   /** End time */
   pub end_time: TimestampNanos,
+  /** Events of the staking account */
+  pub account_id: Option<StakingAccountId>,
+  /** Events of the staking pool */
+  pub pool_id: Option<StakingPoolId>,
+  /** Events triggered by the principal, only for callers with staking::event_log::query */
+  pub principal: Option<Principal>,
+}
+
+impl EventLogQueryParams {
+  /// The event log belongs to the account, pool and principal of the conditions
+  pub fn is_entity_match(&self, event_log: &EventLog) -> bool {
+    let event_type = event_log.get_event_type();
+    self.account_id.is_none_or(|account_id| event_type.get_account_id() == Some(account_id))
+      && self.pool_id.is_none_or(|pool_id| event_type.get_pool_id() == Some(pool_id))
+      && self.principal.is_none_or(|principal| event_log.get_trigger() == principal)
+  }
 }
 
 pub type StakingEventLogPageRequest = PageRequest<EventLogQueryParams>;
-pub type StakingEventLogPageResponse = PageResponse<EventLog>;
+
+/// A page of the event logs held by the staking canister, and the ranges of the event logs moved to archive canisters
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct StakingEventLogPageResponse {
+  pub page: u32,
+  pub page_size: u32,
+  pub total: u32,
+  pub records: Vec<EventLog>,
+  /// Archived event logs in the time range of the query, callers read them from the archives
+  /// and apply the type, account, pool and principal conditions themselves.
+  /// Returned with the first page only, capped to the newest ranges.
+  pub archived_event_logs: Vec<ArchivedEventLogRange>,
+}
+
+pub type StakingEventLogCursorPageRequest = CursorPageRequest<EventLogQueryParams>;
+pub type StakingEventLogCursorPageResponse = CursorPageResponse<EventLog>;
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EventArchiveVo {
+  pub canister_id: Principal,
+  pub start: EntityId,
+  pub end: EntityId,
+  pub first_event_time: TimestampNanos,
+  pub last_event_time: TimestampNanos,
+}
+
+impl From<&EventArchive> for EventArchiveVo {
+  fn from(archive: &EventArchive) -> Self {
+    Self {
+      canister_id: archive.get_canister_id(),
+      start: archive.get_start(),
+      end: archive.get_end(),
+      first_event_time: archive.get_first_event_time(),
+      last_event_time: archive.get_last_event_time(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EventArchiveStatusVo {
+  pub archives: Vec<EventArchiveVo>,
+  pub archive_after_days: u64,
+  pub batch_size: u64,
+  /// The archive canister wasm module was uploaded
+  pub has_archive_wasm: bool,
+  /// Event logs still held by the staking canister
+  pub local_event_logs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EventArchiveSettingsDto {
+  pub archive_after_days: u64,
+  pub batch_size: u64,
+}
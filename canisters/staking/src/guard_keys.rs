@@ -1,4 +1,10 @@
-use types::{assets_management::ProposalId, staking::StakingAccountId, UserId};
+use std::time::Duration;
+
+use types::{
+  assets_management::ProposalId,
+  staking::{ExternalDepositId, StakingAccountId, StakingPoolId},
+  UserId,
+};
 
 /// Obtain the key for stake entrance
 pub fn get_stake_guard_key(user_id: UserId) -> String {
@@ -38,3 +44,50 @@ pub fn get_recovery_unstake_penalty_guard_key(account_id: StakingAccountId) -> S
 pub fn get_stake_to_nns_guard_key(proposal_id: ProposalId) -> String {
   format!("stake_to_nns_guard_{}", proposal_id)
 }
+
+/// Obtain the guard key for managing the maturity of the staking pool's NNS neuron
+pub fn get_nns_maturity_guard_key(pool_id: StakingPoolId) -> String {
+  format!("nns_maturity_guard_{}", pool_id)
+}
+
+/// Obtain the guard key for disbursing the staking pool's NNS neuron
+pub fn get_nns_disburse_guard_key(pool_id: StakingPoolId) -> String {
+  format!("nns_disburse_guard_{}", pool_id)
+}
+
+/// Obtain the guard key for the scheduled recovery of staking account errors
+pub fn get_staking_error_recovery_guard_key() -> String {
+  "recover_staking_account_errors".to_string()
+}
+
+/// Obtain the guard key for the scheduled NNS neuron sync
+pub fn get_nns_sync_guard_key() -> String {
+  "nns_sync_guard".to_string()
+}
+
+/// Obtain the guard key for scanning the ledger for external deposits
+pub fn get_deposit_scan_guard_key() -> String {
+  "deposit_scan_guard".to_string()
+}
+
+/// Obtain the guard key for moving event logs to the event archive canister
+pub fn get_event_log_archive_guard_key() -> String {
+  "event_log_archive_guard".to_string()
+}
+
+/// Obtain the guard key for refunding or adopting an external deposit
+pub fn get_external_deposit_guard_key(deposit_id: ExternalDepositId) -> String {
+  format!("external_deposit_guard_{}", deposit_id)
+}
+
+/// Hold time of the staking account error recovery, a run that trapped after an await is taken over by the next round
+pub const STAKING_ERROR_RECOVERY_GUARD_LEASE: Duration = Duration::from_secs(30 * 60);
+
+/// Hold time of the NNS neuron sync
+pub const NNS_SYNC_GUARD_LEASE: Duration = Duration::from_secs(30 * 60);
+
+/// Hold time of the external deposit scan
+pub const DEPOSIT_SCAN_GUARD_LEASE: Duration = Duration::from_secs(10 * 60);
+
+/// Hold time of an event log archiving run
+pub const EVENT_LOG_ARCHIVE_GUARD_LEASE: Duration = Duration::from_secs(30 * 60);
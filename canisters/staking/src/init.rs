@@ -1,12 +1,18 @@
 use std::time::Duration;
 
 use crate::scheduled_tasks::{
-  nns_neuron_tasks::sync_nns_neuron_info_task, reward_distribution_task::distribute_staking_rewards,
-  stake_error_recovery_task::recover_staking_account_errors, unstake_account_task::unstake_accounts,
+  deposit_scan_task::scan_external_deposits_task, event_log_archive_task::archive_event_logs_task, nns_neuron_tasks::sync_nns_neuron_info_task,
+  reward_distribution_task::distribute_staking_rewards, stake_error_recovery_task::recover_staking_account_errors,
+  unstake_account_task::unstake_accounts,
 };
 
 #[ic_cdk::init]
 fn init() {
+  crate::migrations::SCHEMA_MIGRATOR.mark_latest();
+  start_scheduled_tasks();
+}
+
+fn start_scheduled_tasks() {
   // Check stake rewards and stake maturity every minute
   let interval = Duration::from_secs(60 * 60); // 1 hour
   ic_cdk::println!("Starting a periodic task with interval {interval:?}");
@@ -17,6 +23,8 @@ fn init() {
     ic_cdk::futures::spawn(async { unstake_accounts().await });
     // Perform staked account error recovery tasks every minute
     ic_cdk::futures::spawn(async { recover_staking_account_errors().await });
+    // Move the old event logs to the event archive canister
+    ic_cdk::futures::spawn(archive_event_logs_task());
   });
 
   // Sync NNS neuron info every hour
@@ -30,6 +38,12 @@ fn init() {
     });
   });
 
+  // Scan the ledger for external deposits every 10 minutes
+  let deposit_scan_interval = Duration::from_secs(10 * 60);
+  ic_cdk_timers::set_timer_interval(deposit_scan_interval, || {
+    ic_cdk::futures::spawn(scan_external_deposits_task());
+  });
+
   ic_cdk_timers::set_timer(Duration::from_secs(1), || {
     ic_cdk::futures::spawn(async {
       // Initial sync of NNS neuron info
@@ -42,5 +56,7 @@ fn init() {
 
 #[ic_cdk::post_upgrade]
 fn post_upgrade() {
-  init();
+  crate::system_configs::restore_system_configs();
+  crate::migrations::SCHEMA_MIGRATOR.run_on_upgrade();
+  start_scheduled_tasks();
 }
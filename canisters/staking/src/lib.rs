@@ -9,10 +9,13 @@ use ic_stable_structures::{memory_manager::MemoryManager, DefaultMemoryImpl};
 use types::EntityId;
 
 pub mod account;
+pub mod deposit;
+pub mod errors;
 pub mod event_log;
 pub mod guard_keys;
 mod init;
 pub mod memory_ids;
+pub mod migrations;
 pub mod nns;
 pub mod on_chain;
 pub mod pool;
@@ -30,10 +33,22 @@ use types::staking::StakingPoolId;
 
 use pool::client_transport_structures::ClientStakingPoolVo;
 use pool::transport_structures::StakingPoolUpdateDto;
+use types::sys::audit::AdminAuditEntryVo;
+use types::sys::audit::AdminAuditQueryParams;
+use types::sys::config::PermissionExplanationVo;
+use types::sys::config::RolePermissionVo;
 use types::sys::config::SystemConfig;
+use types::sys::config::SystemConfigStatusVo;
 use types::sys::config::UserRolePermissionVo;
+use types::sys::guard::EntryGuardVo;
+use types::sys::identity_mapping::IdentityMappingDelta;
+use types::sys::identity_mapping::IdentityMappingEventVo;
+use types::sys::migration::SchemaMigrationStatusVo;
 
 use account::client_transport_structures::StakeDto;
+use account::transport_structures::StakingAccountCursorPageRequest;
+use account::transport_structures::StakingAccountCursorPageResponse;
+use account::transport_structures::StakingAccountIndexedPageRequest;
 use account::transport_structures::StakingAccountPageRequest;
 use account::transport_structures::StakingAccountPageResponse;
 use account::transport_structures::StakingAccountVo;
@@ -42,21 +57,48 @@ use pool::transport_structures::StakingPoolAddDto;
 use pool::transport_structures::StakingPoolVo;
 
 use account::client_transport_structures::EarlyUnstakePreCheckVo;
+use deposit::transport_structures::ExternalDepositQueryParams;
+use deposit::transport_structures::ExternalDepositVo;
+use errors::StakingError;
+use event_log::transport_structures::EventArchiveSettingsDto;
+use event_log::transport_structures::EventArchiveStatusVo;
+use event_log::transport_structures::EventArchiveVo;
+use event_log::transport_structures::StakingEventLogCursorPageRequest;
+use event_log::transport_structures::StakingEventLogCursorPageResponse;
 use event_log::transport_structures::StakingEventLogPageRequest;
 use event_log::transport_structures::StakingEventLogPageResponse;
+use nns::stable_structures::NnsMaturityDisbursement;
+use nns::stable_structures::NnsPendingDisburseSettlement;
+use nns::stable_structures::NnsUnmatchedMaturityMint;
+use nns::transport_structures::NnsNeuronCacheVo;
+use nns::transport_structures::NnsNeuronSnapshotVo;
+use nns::transport_structures::NnsPoolNeuronVo;
 use nns::transport_structures::NnsStakeExecuteRecordVo;
 use nns_governance_api::nns_governance_api::Neuron;
+use on_chain::transport_structures::LedgerConfigDto;
+use on_chain::transport_structures::LedgerConfigVo;
+use on_chain::transport_structures::StakingAddressOwnerVo;
 use pool::transport_structures::StakingPoolAccountIds;
 use pool_transaction_record::stable_structures::PoolTransactionRecord;
+use pool_transaction_record::transport_structures::LedgerFeeRecordVo;
+use pool_transaction_record::transport_structures::PoolLedgerFeeQueryParams;
+use pool_transaction_record::transport_structures::PoolLedgerFeeTotalsVo;
 use pool_transaction_record::transport_structures::PoolTransactionQueryParams;
+use reward::transport_structures::StakingRewardCursorPageRequest;
+use reward::transport_structures::StakingRewardCursorPageResponse;
 use reward::transport_structures::StakingRewardPageRequest;
 use reward::transport_structures::StakingRewardPageResponse;
+use serde_bytes::ByteBuf;
 use subscription::transport_structures::StakingSubscribeAddDto;
+use subscription::transport_structures::SubscriptionCursorRequest;
+use subscription::transport_structures::SubscriptionCursorResponse;
 use subscription::transport_structures::SubscriptionRequest;
 use subscription::transport_structures::SubscriptionResponse;
 use types::assets_management::ProposalId;
 use types::pagination::PageRequest;
 use types::pagination::PageResponse;
+use types::staking::ExternalDepositId;
+use types::TimestampNanos;
 use types::E8S;
 
 ic_cdk::export_candid!();
@@ -1,6 +1,11 @@
 /// Memory related to event log ID definition
 pub const STAKING_EVENT_LOG: u8 = 0;
 pub const STAKING_EVENT_LOG_SEQ: u8 = 1;
+pub const STAKING_EVENT_LOG_ACCOUNT_INDEX: u8 = 2;
+pub const STAKING_EVENT_LOG_POOL_INDEX: u8 = 3;
+pub const STAKING_EVENT_LOG_PRINCIPAL_INDEX: u8 = 4;
+pub const STAKING_EVENT_ARCHIVE_STATE: u8 = 5;
+pub const STAKING_EVENT_ARCHIVE_WASM: u8 = 6;
 
 /// Memory of the stake pool ID definition
 pub const STAKING_POOL: u8 = 10;
@@ -14,6 +19,10 @@ pub const STAKING_POOL_ACCOUNT_INDEX: u8 = 23;
 pub const STAKING_UNSTAKE_ON_DAY_ACCOUNT_INDEX: u8 = 24;
 // A staked account index was generated that could recover errors
 pub const STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX: u8 = 25;
+pub const STAKING_STATUS_ACCOUNT_INDEX: u8 = 26;
+// Composite key account indexes, replaced the vector indexes 22 ~ 23 in schema version 10
+pub const STAKING_USER_ACCOUNT_COMPOSITE_INDEX: u8 = 27;
+pub const STAKING_POOL_ACCOUNT_COMPOSITE_INDEX: u8 = 28;
 
 /// Memory of stake reward ID definition
 pub const STAKING_REWARD: u8 = 30;
@@ -22,6 +31,10 @@ pub const STAKING_ACCOUNT_REWARD_INDEX: u8 = 32;
 pub const STAKING_USER_REWARD_INDEX: u8 = 33;
 pub const STAKING_POOL_REWARD_INDEX: u8 = 34;
 pub const STAKING_USER_ACCOUNT_REWARD_DATE_INDEX: u8 = 35;
+// Composite key reward indexes, replaced the vector indexes 32 ~ 34 in schema version 2
+pub const STAKING_ACCOUNT_REWARD_COMPOSITE_INDEX: u8 = 36;
+pub const STAKING_USER_REWARD_COMPOSITE_INDEX: u8 = 37;
+pub const STAKING_POOL_REWARD_COMPOSITE_INDEX: u8 = 38;
 
 /// Memory of subscription notifications ID definition
 pub const STAKING_SUBSCRIPTION: u8 = 40;
@@ -30,7 +43,34 @@ pub const STAKING_SUBSCRIPTION_SEQ: u8 = 41;
 /// Memory of staking pool transaction record ID definition
 pub const STAKING_POOL_TRANSACTION_RECORD: u8 = 50;
 pub const STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX: u8 = 51;
+pub const STAKING_POOL_LEDGER_FEE_RECORD: u8 = 52;
+// One entry per transaction record and a head per pool, replaced the per pool records 50 in schema version 4
+pub const STAKING_POOL_TRANSACTION_RECORD_ENTRY: u8 = 53;
+pub const STAKING_POOL_TRANSACTION_HEAD: u8 = 54;
+// Composite key record type index, replaced the set index 51 in schema version 13
+pub const STAKING_POOL_TRANSACTION_RECORD_TYPE_COMPOSITE_INDEX: u8 = 55;
 
 /// Memory of NNS staking record ID definition
 pub const NNS_STAKING_EXECUTE_RECORD: u8 = 60;
 pub const NNS_STAKING_POOL_NEURON_ID: u8 = 61;
+pub const NNS_NEURON_CACHE: u8 = 62;
+pub const NNS_NEURON_SNAPSHOT: u8 = 63;
+pub const NNS_POOL_FOLLOWEES: u8 = 64;
+pub const NNS_POOL_NEURON: u8 = 65;
+pub const NNS_POOL_NEURON_INDEX: u8 = 66;
+pub const NNS_UNMATCHED_MATURITY_MINT: u8 = 67;
+pub const NNS_MATURITY_DISBURSEMENT: u8 = 68;
+pub const NNS_PENDING_DISBURSE_SETTLEMENT: u8 = 69;
+
+/// Memory of on-chain transfer record ID definition
+pub const LEDGER_TRANSFER_RECORD: u8 = 70;
+pub const LEDGER_REGISTRY: u8 = 71;
+pub const STAKING_ADDRESS_INDEX: u8 = 72;
+
+/// Memory of external deposit ID definition
+pub const EXTERNAL_DEPOSIT: u8 = 80;
+pub const EXTERNAL_DEPOSIT_SEQ: u8 = 81;
+pub const EXTERNAL_DEPOSIT_SCAN_CURSOR: u8 = 82;
+
+/// Memory of NNS data added after the NNS range was full
+pub const NNS_STAKING_EXECUTE_RECORD_POOL_INDEX: u8 = 90;
@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+
+use ic_stable_structures::{memory_manager::MemoryId, Cell};
+use system_configs_macro::has_permission_result;
+use types::{
+  composite_entity_index::add_indexed_id,
+  sys::{
+    migration::{
+      migrate_map_chunk, MigrationProgress, MigrationStep, SchemaMigrationState, SchemaMigrationStateCell, SchemaMigrationStatusVo, SchemaMigrator,
+    },
+    SCHEMA_MIGRATION_MEMORY_ID,
+  },
+};
+
+use crate::{
+  account::{
+    crud_utils::index_staking_account_status, LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP, LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP, STAKING_ACCOUNT_MAP,
+    STAKING_POOL_ACCOUNT_INDEX_MAP, STAKING_USER_ACCOUNT_INDEX_MAP,
+  },
+  event_log::STAKING_EVENT_LOG_MAP,
+  nns::{utils::nns_query::register_legacy_pool_neurons, NNS_STAKING_EXECUTE_RECORD_MAP, NNS_STAKING_POOL_NEURON_ID_MAP},
+  on_chain::address::{index_staking_account_address, index_staking_pool_address},
+  pool::STAKING_POOL_MAP,
+  pool_transaction_record::{
+    stable_structures::{RecordTypeIndexKey, RecordTypeKey},
+    utils::split_legacy_pool_transaction_records,
+    LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP, LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP, STAKING_POOL_TRANSACTION_HEAD_MAP,
+    STAKING_POOL_TRANSACTION_RECORD_MAP, STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP,
+  },
+  reward::{
+    LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP, LEGACY_STAKING_POOL_REWARD_INDEX_MAP, LEGACY_STAKING_USER_REWARD_INDEX_MAP,
+    STAKING_ACCOUNT_REWARD_INDEX_MAP, STAKING_POOL_REWARD_INDEX_MAP, STAKING_REWARD_MAP, STAKING_USER_REWARD_INDEX_MAP,
+  },
+  MEMORY_MANAGER,
+};
+
+/// The reward queries read the composite reward indexes from this version
+pub const COMPOSITE_REWARD_INDEX_SCHEMA_VERSION: u32 = 2;
+/// The pool transaction records are stored one entry per record from this version
+pub const POOL_TRANSACTION_RECORD_ENTRY_SCHEMA_VERSION: u32 = 4;
+/// The staking account queries read the status index from this version
+pub const ACCOUNT_STATUS_INDEX_SCHEMA_VERSION: u32 = 6;
+/// The event log queries read the account, pool and principal indexes from this version
+pub const EVENT_LOG_INDEX_SCHEMA_VERSION: u32 = 7;
+/// The on-chain addresses of all staking pools and staking accounts are indexed from this version
+pub const STAKING_ADDRESS_INDEX_SCHEMA_VERSION: u32 = 9;
+/// The staking account queries read the composite pool and user indexes from this version
+pub const ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION: u32 = 10;
+/// The NNS staking execute records of a neuron are read through the pool index from this version
+pub const NNS_STAKING_EXECUTE_RECORD_INDEX_SCHEMA_VERSION: u32 = 12;
+/// The pool transaction queries read the composite record type index from this version
+pub const POOL_TRANSACTION_TYPE_INDEX_SCHEMA_VERSION: u32 = 13;
+
+thread_local! {
+  /// Schema version of the staking stable memory
+  static SCHEMA_MIGRATION_STATE: RefCell<SchemaMigrationStateCell> = RefCell::new(
+    Cell::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(SCHEMA_MIGRATION_MEMORY_ID))),
+      SchemaMigrationState::default(),
+    ).unwrap()
+  );
+}
+
+/// Migrations of the staking stable memory, new steps are appended with the next version
+pub static SCHEMA_MIGRATOR: SchemaMigrator = SchemaMigrator {
+  state: &SCHEMA_MIGRATION_STATE,
+  steps: &[
+    MigrationStep {
+      version: COMPOSITE_REWARD_INDEX_SCHEMA_VERSION,
+      description: "Build the composite reward indexes from the staking rewards",
+      run: build_composite_reward_indexes,
+    },
+    MigrationStep {
+      version: 3,
+      description: "Clear the vector reward indexes",
+      run: clear_legacy_reward_indexes,
+    },
+    MigrationStep {
+      version: POOL_TRANSACTION_RECORD_ENTRY_SCHEMA_VERSION,
+      description: "Split the pool transaction records into one entry per record",
+      run: split_pool_transaction_records,
+    },
+    MigrationStep {
+      version: 5,
+      description: "Clear the per pool transaction records",
+      run: clear_legacy_pool_transaction_records,
+    },
+    MigrationStep {
+      version: ACCOUNT_STATUS_INDEX_SCHEMA_VERSION,
+      description: "Index the staking accounts by status",
+      run: index_staking_accounts,
+    },
+    MigrationStep {
+      version: EVENT_LOG_INDEX_SCHEMA_VERSION,
+      description: "Index the event logs by staking account, staking pool and trigger principal",
+      run: index_event_logs,
+    },
+    MigrationStep {
+      version: 8,
+      description: "Register the NNS neurons created before the neuron ladder as the first neuron of their pool",
+      run: register_pool_neurons,
+    },
+    MigrationStep {
+      version: STAKING_ADDRESS_INDEX_SCHEMA_VERSION,
+      description: "Index the on-chain addresses of the staking pools and staking accounts",
+      run: index_staking_addresses,
+    },
+    MigrationStep {
+      version: ACCOUNT_COMPOSITE_INDEX_SCHEMA_VERSION,
+      description: "Build the composite pool and user indexes from the staking accounts",
+      run: build_composite_account_indexes,
+    },
+    MigrationStep {
+      version: 11,
+      description: "Clear the vector pool and user account indexes",
+      run: clear_legacy_account_indexes,
+    },
+    MigrationStep {
+      version: NNS_STAKING_EXECUTE_RECORD_INDEX_SCHEMA_VERSION,
+      description: "Index the NNS staking execute records by staking pool",
+      run: index_nns_staking_execute_records,
+    },
+    MigrationStep {
+      version: POOL_TRANSACTION_TYPE_INDEX_SCHEMA_VERSION,
+      description: "Build the composite record type index from the pool transaction records",
+      run: build_composite_record_type_index,
+    },
+    MigrationStep {
+      version: 14,
+      description: "Clear the set record type index",
+      run: clear_legacy_record_type_index,
+    },
+  ],
+};
+
+fn build_composite_reward_indexes(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  STAKING_REWARD_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |id, reward| {
+      STAKING_POOL_REWARD_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &reward.get_pool_id(), *id));
+      STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &reward.get_account_id(), *id));
+      STAKING_USER_REWARD_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &reward.get_owner(), *id));
+      Ok(None)
+    })
+  })
+}
+
+fn clear_legacy_reward_indexes(_cursor: Option<Vec<u8>>, _budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  let processed = LEGACY_STAKING_POOL_REWARD_INDEX_MAP.with(|map| map.borrow().len())
+    + LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|map| map.borrow().len())
+    + LEGACY_STAKING_USER_REWARD_INDEX_MAP.with(|map| map.borrow().len());
+
+  LEGACY_STAKING_POOL_REWARD_INDEX_MAP.with(|map| map.borrow_mut().clear_new());
+  LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|map| map.borrow_mut().clear_new());
+  LEGACY_STAKING_USER_REWARD_INDEX_MAP.with(|map| map.borrow_mut().clear_new());
+
+  Ok(MigrationProgress {
+    next_cursor: None,
+    processed,
+  })
+}
+
+fn split_pool_transaction_records(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |pool_id, records| {
+      // Pools that took a new record during the migration were split then
+      if !STAKING_POOL_TRANSACTION_HEAD_MAP.with(|head_map| head_map.borrow().contains_key(pool_id)) {
+        split_legacy_pool_transaction_records(&records);
+      }
+      Ok(None)
+    })
+  })
+}
+
+fn clear_legacy_pool_transaction_records(_cursor: Option<Vec<u8>>, _budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  let processed = LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| map.borrow().len());
+  LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| map.borrow_mut().clear_new());
+
+  Ok(MigrationProgress {
+    next_cursor: None,
+    processed,
+  })
+}
+
+fn index_staking_accounts(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  STAKING_ACCOUNT_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |account_id, account| {
+      index_staking_account_status(*account_id, None, Some(account.get_status()));
+      Ok(None)
+    })
+  })
+}
+
+fn index_event_logs(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  STAKING_EVENT_LOG_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |_, event_log| {
+      event_log.add_indexes();
+      Ok(None)
+    })
+  })
+}
+
+fn register_pool_neurons(_cursor: Option<Vec<u8>>, _budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  register_legacy_pool_neurons();
+
+  Ok(MigrationProgress {
+    next_cursor: None,
+    processed: NNS_STAKING_POOL_NEURON_ID_MAP.with(|map| map.borrow().len()),
+  })
+}
+
+fn index_staking_addresses(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  // The staking pools are few, they are indexed in the first chunk
+  if cursor.is_none() {
+    STAKING_POOL_MAP.with(|map| map.borrow().keys().for_each(index_staking_pool_address));
+  }
+
+  STAKING_ACCOUNT_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |account_id, _| {
+      index_staking_account_address(*account_id);
+      Ok(None)
+    })
+  })
+}
+
+fn build_composite_account_indexes(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  STAKING_ACCOUNT_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |id, account| {
+      STAKING_POOL_ACCOUNT_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &account.get_pool_id(), *id));
+      STAKING_USER_ACCOUNT_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &account.get_owner(), *id));
+      Ok(None)
+    })
+  })
+}
+
+fn clear_legacy_account_indexes(_cursor: Option<Vec<u8>>, _budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  let processed =
+    LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP.with(|map| map.borrow().len()) + LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP.with(|map| map.borrow().len());
+
+  LEGACY_STAKING_POOL_ACCOUNT_INDEX_MAP.with(|map| map.borrow_mut().clear_new());
+  LEGACY_STAKING_USER_ACCOUNT_INDEX_MAP.with(|map| map.borrow_mut().clear_new());
+
+  Ok(MigrationProgress {
+    next_cursor: None,
+    processed,
+  })
+}
+
+fn index_nns_staking_execute_records(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  NNS_STAKING_EXECUTE_RECORD_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |_, execute_record| {
+      execute_record.add_pool_index();
+      Ok(None)
+    })
+  })
+}
+
+fn build_composite_record_type_index(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
+    migrate_map_chunk(&mut map.borrow_mut(), cursor, budget_exhausted, |key, record| {
+      let type_key = RecordTypeIndexKey(key.0, RecordTypeKey::from(&record.get_record_type()));
+      STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &type_key, record.get_id()));
+      Ok(None)
+    })
+  })
+}
+
+fn clear_legacy_record_type_index(_cursor: Option<Vec<u8>>, _budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String> {
+  let processed = LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|map| map.borrow().len());
+  LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|map| map.borrow_mut().clear_new());
+
+  Ok(MigrationProgress {
+    next_cursor: None,
+    processed,
+  })
+}
+
+/// Query the schema version and the progress of the running migration
+#[ic_cdk::query]
+fn get_schema_migration_status() -> SchemaMigrationStatusVo {
+  SCHEMA_MIGRATOR.status()
+}
+
+/// Continue a migration that stopped on an error
+#[ic_cdk::update]
+#[has_permission_result("staking::migration::manage", audit)]
+fn resume_schema_migration() -> Result<SchemaMigrationStatusVo, String> {
+  SCHEMA_MIGRATOR.resume();
+  Ok(SCHEMA_MIGRATOR.status())
+}
@@ -1,21 +1,46 @@
-use std::{cell::RefCell, collections::BTreeMap};
+use std::cell::RefCell;
 
 use ic_cdk::{query, update};
 use ic_stable_structures::{memory_manager::MemoryId, StableBTreeMap};
 use nns_governance_api::nns_governance_api::Neuron;
-use stable_structures::NnsStakeExecuteRecord;
+use stable_structures::{
+  NnsFolloweesConfig, NnsMaturityDisbursement, NnsMaturityDisbursementKey, NnsNeuronCache, NnsNeuronSnapshot, NnsNeuronSnapshotKey,
+  NnsPendingDisburseSettlement, NnsPoolNeuron, NnsStakeExecuteRecord, NnsStakeExecuteStatus, NnsUnmatchedMaturityMint,
+};
 use system_configs_macro::has_permission_result;
-use transport_structures::NnsStakeExecuteRecordVo;
-use types::{assets_management::ProposalId, stable_structures::Memory, staking::StakingPoolId, E8S};
-use utils::{nns_query::sync_nns_neuron, nns_update::refresh_nns_neuron_by_pool};
+use transport_structures::{NnsNeuronCacheVo, NnsNeuronSnapshotVo, NnsPoolNeuronVo, NnsStakeExecuteRecordVo};
+use types::{
+  assets_management::ProposalId, composite_entity_index::CompositeEntityIndex, entities::EntityIndex, stable_structures::Memory,
+  staking::StakingPoolId, TimestampNanos, E8S,
+};
+use utils::{
+  ledger_utils::query_transaction_by_block_height,
+  nns_query::{
+    get_active_pool_neuron, get_cached_nns_neuron, get_pool_primary_neuron_id, query_nns_neuron_by_pool_id, query_nns_neuron_snapshots,
+    sync_nns_neuron, sync_nns_neuron_by_id,
+  },
+  nns_update::{apply_pool_followees, refresh_nns_neuron_by_nonce, refresh_nns_neuron_by_pool, set_dissolve_delay_to},
+};
 
 use crate::{
-  guard_keys::get_stake_to_nns_guard_key,
-  memory_ids::{NNS_STAKING_EXECUTE_RECORD, NNS_STAKING_POOL_NEURON_ID},
-  on_chain::{address::generate_staking_pool_neuron_account, transfer::transfer_from_staking_pool_to_nns_neuron},
+  errors::StakingError,
+  guard_keys::{get_nns_disburse_guard_key, get_nns_maturity_guard_key, get_stake_to_nns_guard_key},
+  memory_ids::{
+    NNS_MATURITY_DISBURSEMENT, NNS_NEURON_CACHE, NNS_NEURON_SNAPSHOT, NNS_PENDING_DISBURSE_SETTLEMENT, NNS_POOL_FOLLOWEES, NNS_POOL_NEURON,
+    NNS_POOL_NEURON_INDEX, NNS_STAKING_EXECUTE_RECORD, NNS_STAKING_EXECUTE_RECORD_POOL_INDEX, NNS_STAKING_POOL_NEURON_ID,
+    NNS_UNMATCHED_MATURITY_MINT,
+  },
+  on_chain::{
+    address::{generate_neuron_account_with_nonce, generate_staking_pool_account_identifier, generate_staking_pool_neuron_nonce},
+    stable_structures::StakingAddressOwner,
+    transfer::transfer_from_staking_pool_to_nns_neuron,
+    STAKING_ADDRESS_INDEX_MAP,
+  },
   parallel_guard::EntryGuard,
   pool::crud_utils::query_staking_pool_by_id,
-  pool_transaction_record::utils::record_stake_to_neuron_transaction,
+  pool_transaction_record::utils::{
+    record_nns_maturity_disburse_transaction, record_nns_maturity_stake_transaction, record_stake_to_neuron_transaction,
+  },
   MEMORY_MANAGER,
 };
 
@@ -31,30 +56,142 @@ thread_local! {
     )
   );
 
-  pub static NNS_NEURON_MAP: RefCell<BTreeMap<StakingPoolId, Neuron>> = RefCell::new(
-    BTreeMap::new()
+  /// Index of the NNS staking execute records of each staking pool, by proposal ID
+  pub static NNS_STAKING_EXECUTE_RECORD_POOL_INDEX_MAP: RefCell<CompositeEntityIndex<StakingPoolId>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_STAKING_EXECUTE_RECORD_POOL_INDEX))),
+    )
+  );
+
+  /// Last synced NNS neuron by neuron ID, kept across upgrades
+  pub static NNS_NEURON_CACHE_MAP: RefCell<StableBTreeMap<u64, NnsNeuronCache, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_NEURON_CACHE))),
+    )
+  );
+
+  /// Bounded time series of NNS neuron snapshots per neuron
+  pub static NNS_NEURON_SNAPSHOT_MAP: RefCell<StableBTreeMap<NnsNeuronSnapshotKey, NnsNeuronSnapshot, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_NEURON_SNAPSHOT))),
+    )
   );
 
+  /// First neuron of the neuron ladder of each staking pool
   pub static NNS_STAKING_POOL_NEURON_ID_MAP: RefCell<StableBTreeMap<StakingPoolId, u64, Memory>> = RefCell::new(
     StableBTreeMap::init(
       MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_STAKING_POOL_NEURON_ID))),
     )
   );
+
+  /// Followee configuration of the NNS neuron per staking pool
+  pub static NNS_POOL_FOLLOWEES_MAP: RefCell<StableBTreeMap<StakingPoolId, NnsFolloweesConfig, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_POOL_FOLLOWEES))),
+    )
+  );
+
+  /// Neurons held by staking pools, by neuron ID
+  pub static NNS_POOL_NEURON_MAP: RefCell<StableBTreeMap<u64, NnsPoolNeuron, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_POOL_NEURON))),
+    )
+  );
+
+  /// Maturity disbursements of the pool neurons, waiting for or matched to their mint
+  pub static NNS_MATURITY_DISBURSEMENT_MAP: RefCell<StableBTreeMap<NnsMaturityDisbursementKey, NnsMaturityDisbursement, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_MATURITY_DISBURSEMENT))),
+    )
+  );
+
+  /// Mints into the staking pool accounts that no maturity disbursement was matched to, by block index
+  pub static NNS_UNMATCHED_MATURITY_MINT_MAP: RefCell<StableBTreeMap<u64, NnsUnmatchedMaturityMint, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_UNMATCHED_MATURITY_MINT))),
+    )
+  );
+
+  /// Neuron disburses waiting for their settlement, by the block height of the disburse transfer
+  pub static NNS_PENDING_DISBURSE_SETTLEMENT_MAP: RefCell<StableBTreeMap<u64, NnsPendingDisburseSettlement, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_PENDING_DISBURSE_SETTLEMENT))),
+    )
+  );
+
+  /// Index of the neuron ladder of each staking pool
+  pub static NNS_POOL_NEURON_INDEX_MAP: RefCell<StableBTreeMap<StakingPoolId, EntityIndex<StakingPoolId>, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(NNS_POOL_NEURON_INDEX))),
+    )
+  );
 }
 
 #[update]
-#[has_permission_result("staking::nns::stake_to_nns_neuron")]
+#[has_permission_result("staking::nns::stake_to_nns_neuron", audit)]
 async fn stake_to_nns_neuron(proposal_id: ProposalId, pool_id: StakingPoolId, amount: E8S) -> Result<u64, String> {
-  let mut execute_record = NnsStakeExecuteRecord::init_with(proposal_id, pool_id, amount);
+  stake_to_pool_neuron(proposal_id, pool_id, amount, 0, None)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::stake_to_nns_neuron", audit)]
+async fn stake_to_nns_neuron_v2(proposal_id: ProposalId, pool_id: StakingPoolId, amount: E8S) -> Result<u64, StakingError> {
+  stake_to_pool_neuron(proposal_id, pool_id, amount, 0, None).await
+}
+
+/// Stake into a neuron of the staking pool's neuron ladder.
+/// A new neuron is created for an unused ladder index, and its dissolve delay is raised to the given delay.
+#[update]
+#[has_permission_result("staking::nns::stake_to_nns_neuron", audit)]
+async fn stake_to_nns_ladder_neuron(
+  proposal_id: ProposalId,
+  pool_id: StakingPoolId,
+  amount: E8S,
+  ladder_index: u32,
+  dissolve_delay_seconds: u64,
+) -> Result<u64, String> {
+  stake_to_pool_neuron(proposal_id, pool_id, amount, ladder_index, Some(dissolve_delay_seconds))
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::stake_to_nns_neuron", audit)]
+async fn stake_to_nns_ladder_neuron_v2(
+  proposal_id: ProposalId,
+  pool_id: StakingPoolId,
+  amount: E8S,
+  ladder_index: u32,
+  dissolve_delay_seconds: u64,
+) -> Result<u64, StakingError> {
+  stake_to_pool_neuron(proposal_id, pool_id, amount, ladder_index, Some(dissolve_delay_seconds)).await
+}
+
+async fn stake_to_pool_neuron(
+  proposal_id: ProposalId,
+  pool_id: StakingPoolId,
+  amount: E8S,
+  ladder_index: u32,
+  dissolve_delay_seconds: Option<u64>,
+) -> Result<u64, StakingError> {
+  let mut execute_record = NnsStakeExecuteRecord::init_with(proposal_id, pool_id, amount, ladder_index, dissolve_delay_seconds);
+
+  if *execute_record.get_status() == NnsStakeExecuteStatus::Success && execute_record.neuron_id.is_some() {
+    return Ok(execute_record.get_neuron_id());
+  }
 
-  let _entry_guard = EntryGuard::new(get_stake_to_nns_guard_key(proposal_id));
-  let pool = query_staking_pool_by_id(pool_id)?;
-  let neuron_account = generate_staking_pool_neuron_account(pool_id);
+  let _entry_guard = EntryGuard::new(get_stake_to_nns_guard_key(proposal_id))
+    .map_err(|_| StakingError::already_processing(format!("Stake to NNS neuron for proposal {} is already processing", proposal_id)))?;
+  let pool = query_staking_pool_by_id(pool_id).map_err(|_| StakingError::PoolNotFound { pool_id })?;
+  let neuron_nonce = generate_staking_pool_neuron_nonce(pool_id, execute_record.get_ladder_index());
+  let neuron_account = generate_neuron_account_with_nonce(neuron_nonce);
 
   // Check if the execute record already has a transfer block index then skip transfer
   if execute_record.pool_to_neuron_transfer_block_index.is_none() {
     if amount < 1 {
-      return Err("Amount must be greater than or equal 1 ICP".to_string());
+      return Err(StakingError::invalid_argument("Amount must be greater than or equal 1 ICP"));
     }
 
     let pool_available_funds = pool.get_available_funds().unwrap_or_default();
@@ -65,10 +202,15 @@ async fn stake_to_nns_neuron(proposal_id: ProposalId, pool_id: StakingPoolId, am
         amount,
         pool_available_funds
       );
-      return Err("The staking pool does not have enough available funds.".to_string());
+      return Err(StakingError::InsufficientPoolFunds {
+        pool_id,
+        available_funds: pool_available_funds,
+      });
     }
 
-    let nns_transfer_block_index = transfer_from_staking_pool_to_nns_neuron(pool_id, amount).await?;
+    let nns_transfer_block_index = transfer_from_staking_pool_to_nns_neuron(proposal_id, pool_id, neuron_nonce, amount)
+      .await
+      .map_err(StakingError::ledger_transfer_failed)?;
 
     ic_cdk::println!(
       "Transferred {} ICP from staking pool {} to NNS neuron account: {}, block index: {}",
@@ -87,19 +229,50 @@ async fn stake_to_nns_neuron(proposal_id: ProposalId, pool_id: StakingPoolId, am
   }
 
   // update neuron status
-  match refresh_nns_neuron_by_pool(pool_id).await {
+  match refresh_nns_neuron_by_nonce(neuron_nonce).await {
     Ok(neuron_id) => {
       execute_record.update_to_success(neuron_id);
       record_stake_to_neuron_transaction(&execute_record)?;
 
-      NNS_STAKING_POOL_NEURON_ID_MAP.with(|map| {
-        let mut map = map.borrow_mut();
-        map.insert(pool_id, neuron_id);
+      let existing_pool_neuron = NnsPoolNeuron::get_with_neuron_id(neuron_id).filter(|pool_neuron| pool_neuron.is_active());
+      let is_new_neuron = existing_pool_neuron.is_none();
+
+      let mut pool_neuron = existing_pool_neuron.unwrap_or_else(|| {
+        NnsPoolNeuron::new(
+          neuron_id,
+          pool_id,
+          neuron_nonce,
+          execute_record.get_ladder_index(),
+          execute_record.dissolve_delay_seconds,
+        )
       });
+      pool_neuron.add_staked_principal(execute_record.get_amount());
+
+      if execute_record.get_ladder_index() == 0 {
+        NNS_STAKING_POOL_NEURON_ID_MAP.with(|map| {
+          let mut map = map.borrow_mut();
+          map.insert(pool_id, neuron_id);
+        });
+      }
+
+      // A new neuron was created for the pool, apply the pool's followees and the ladder dissolve delay to it.
+      // The stake is done at this point, followees that fail here are re-applied by the NNS sync task,
+      // dissolve delays by sync_nns_neuron_by_pool_id.
+      if is_new_neuron {
+        if let Err(e) = apply_pool_followees(pool_id, neuron_id).await {
+          ic_cdk::println!("Failed to apply followees to NNS neuron {} of pool {}: {}", neuron_id, pool_id, e);
+        }
+
+        if let Some(dissolve_delay_seconds) = execute_record.dissolve_delay_seconds {
+          if let Err(e) = set_dissolve_delay_to(neuron_id, dissolve_delay_seconds).await {
+            ic_cdk::println!("Failed to set dissolve delay of NNS neuron {} of pool {}: {}", neuron_id, pool_id, e);
+          }
+        }
+      }
 
       // Sync the NNS neuron to the local cache
       ic_cdk::futures::spawn(async move {
-        sync_nns_neuron(pool_id).await.unwrap_or_else(|e| {
+        sync_nns_neuron_by_id(pool_id, neuron_id).await.unwrap_or_else(|e| {
           ic_cdk::println!("Failed to sync NNS neuron for pool {}: {}", pool_id, e);
         });
       });
@@ -110,7 +283,7 @@ async fn stake_to_nns_neuron(proposal_id: ProposalId, pool_id: StakingPoolId, am
       ic_cdk::println!("Failed to refresh NNS neuron: {}", e);
 
       execute_record.update_to_error(neuron_account.to_hex(), e.to_string());
-      Err(format!("Failed to refresh NNS neuron: {}", e))
+      Err(StakingError::nns_governance_failed(format!("Failed to refresh NNS neuron: {}", e)))
     }
   }
 }
@@ -125,150 +298,437 @@ pub fn get_nns_staking_execute_record(proposal_id: ProposalId) -> Option<NnsStak
 
 #[query]
 pub fn get_nns_neuron_by_pool_id(pool_id: StakingPoolId) -> Option<Neuron> {
-  if let Some(neuron) = NNS_NEURON_MAP.with(|map| map.borrow().get(&pool_id).cloned()) {
-    Some(neuron)
-  } else {
-    None
-  }
+  get_cached_nns_neuron(pool_id)
+}
+
+#[query]
+pub fn get_nns_neuron_cache_by_pool_id(pool_id: StakingPoolId) -> Option<NnsNeuronCacheVo> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id).ok()?;
+  get_nns_neuron_cache(neuron_id)
+}
+
+#[query]
+pub fn get_nns_neuron_cache(neuron_id: u64) -> Option<NnsNeuronCacheVo> {
+  NNS_NEURON_CACHE_MAP.with(|map| map.borrow().get(&neuron_id).map(NnsNeuronCacheVo::from))
+}
+
+/// Query the neuron ladder of the staking pool
+#[query]
+pub fn get_nns_pool_neurons(pool_id: StakingPoolId) -> Vec<NnsPoolNeuronVo> {
+  NnsPoolNeuron::query_by_pool(pool_id).into_iter().map(NnsPoolNeuronVo::from).collect()
 }
 
+#[query]
+pub fn get_nns_neuron_snapshots(pool_id: StakingPoolId, start_time: TimestampNanos, end_time: TimestampNanos) -> Vec<NnsNeuronSnapshotVo> {
+  query_nns_neuron_snapshots(pool_id, start_time, end_time)
+    .into_iter()
+    .map(NnsNeuronSnapshotVo::from)
+    .collect()
+}
+
+/// Refresh the pool neurons from their accounts and sync them to the local cache
 #[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
 pub async fn sync_nns_neuron_by_pool_id(pool_id: StakingPoolId) -> Result<(), String> {
   refresh_nns_neuron_by_pool(pool_id).await?;
   sync_nns_neuron(pool_id).await
 }
 
 #[update]
-#[has_permission_result("staking::nns::update_nns_neuron")]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
 async fn add_nns_hotkey(pool_id: StakingPoolId, hotkey: String) -> Result<(), String> {
-  let neuron = NNS_NEURON_MAP.with(|map| {
-    let map = map.borrow();
-    map.get(&pool_id).cloned()
-  });
-
-  if let Some(neuron) = neuron {
-    let neuron_id = neuron.id.map_or_else(
-      || {
-        ic_cdk::println!("Neuron ID is not set for pool ID: {}", pool_id);
-        Err("Neuron ID is not set".to_string())
-      },
-      |id| Ok(id),
-    )?;
-
-    utils::nns_update::add_hot_key(neuron_id.id, hotkey).await
-  } else {
-    Err(format!("No NNS neuron found for pool ID: {}", pool_id))
-  }
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  utils::nns_update::add_hot_key(neuron_id, hotkey).await
 }
 
 #[update]
-#[has_permission_result("staking::nns::update_nns_neuron")]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
 async fn remove_nns_hotkey(pool_id: StakingPoolId, hotkey: String) -> Result<(), String> {
-  let neuron = NNS_NEURON_MAP.with(|map| {
-    let map = map.borrow();
-    map.get(&pool_id).cloned()
-  });
-  if let Some(neuron) = neuron {
-    let neuron_id = neuron.id.map_or_else(
-      || {
-        ic_cdk::println!("Neuron ID is not set for pool ID: {}", pool_id);
-        Err("Neuron ID is not set".to_string())
-      },
-      |id| Ok(id),
-    )?;
-
-    utils::nns_update::remove_hot_key(neuron_id.id, hotkey).await
-  } else {
-    Err(format!("No NNS neuron found for pool ID: {}", pool_id))
-  }
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  utils::nns_update::remove_hot_key(neuron_id, hotkey).await
 }
 
 #[update]
-#[has_permission_result("staking::nns::update_nns_neuron")]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
 async fn increase_nns_dissolve_delay(pool_id: StakingPoolId, additional_delay_seconds: u32) -> Result<(), String> {
-  let neuron = NNS_NEURON_MAP.with(|map| {
-    let map = map.borrow();
-    map.get(&pool_id).cloned()
-  });
-
-  if let Some(neuron) = neuron {
-    let neuron_id = neuron.id.map_or_else(
-      || {
-        ic_cdk::println!("Neuron ID is not set for pool ID: {}", pool_id);
-        Err("Neuron ID is not set".to_string())
-      },
-      |id| Ok(id),
-    )?;
-
-    utils::nns_update::increase_dissolve_delay(neuron_id.id, additional_delay_seconds).await
-  } else {
-    Err(format!("No NNS neuron found for pool ID: {}", pool_id))
-  }
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  utils::nns_update::increase_dissolve_delay(neuron_id, additional_delay_seconds).await
 }
 
 #[update]
-#[has_permission_result("staking::nns::update_nns_neuron")]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
 async fn nns_start_dissolve(pool_id: StakingPoolId) -> Result<(), String> {
-  let neuron = NNS_NEURON_MAP.with(|map| {
-    let map = map.borrow();
-    map.get(&pool_id).cloned()
-  });
-
-  if let Some(neuron) = neuron {
-    let neuron_id = neuron.id.map_or_else(
-      || {
-        ic_cdk::println!("Neuron ID is not set for pool ID: {}", pool_id);
-        Err("Neuron ID is not set".to_string())
-      },
-      |id| Ok(id),
-    )?;
-
-    utils::nns_update::start_dissolve(neuron_id.id).await
-  } else {
-    Err(format!("No NNS neuron found for pool ID: {}", pool_id))
-  }
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  utils::nns_update::start_dissolve(neuron_id).await
 }
 
 #[update]
-#[has_permission_result("staking::nns::update_nns_neuron")]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
 async fn nns_stop_dissolve(pool_id: StakingPoolId) -> Result<(), String> {
-  let neuron = NNS_NEURON_MAP.with(|map| {
-    let map = map.borrow();
-    map.get(&pool_id).cloned()
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  utils::nns_update::stop_dissolve(neuron_id).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn nns_unstake(pool_id: StakingPoolId) -> Result<(), String> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  disburse_pool_neuron(pool_id, neuron_id).await.map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn nns_unstake_v2(pool_id: StakingPoolId) -> Result<(), StakingError> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  disburse_pool_neuron(pool_id, neuron_id).await
+}
+
+/// Disburse a percentage of the pool neuron's maturity to the staking pool account, returns the disbursed amount
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_disburse_maturity(pool_id: StakingPoolId, percentage_to_disburse: u32) -> Result<E8S, String> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  disburse_pool_neuron_maturity(pool_id, neuron_id, percentage_to_disburse)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_disburse_maturity_v2(pool_id: StakingPoolId, percentage_to_disburse: u32) -> Result<E8S, StakingError> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  disburse_pool_neuron_maturity(pool_id, neuron_id, percentage_to_disburse).await
+}
+
+/// Stake a percentage of the pool neuron's maturity, returns the newly staked maturity
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_stake_maturity(pool_id: StakingPoolId, percentage_to_stake: u32) -> Result<E8S, String> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  stake_pool_neuron_maturity(pool_id, neuron_id, percentage_to_stake)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_stake_maturity_v2(pool_id: StakingPoolId, percentage_to_stake: u32) -> Result<E8S, StakingError> {
+  let neuron_id = get_pool_primary_neuron_id(pool_id)?;
+  stake_pool_neuron_maturity(pool_id, neuron_id, percentage_to_stake).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn add_nns_neuron_hotkey(neuron_id: u64, hotkey: String) -> Result<(), String> {
+  get_active_pool_neuron(neuron_id)?;
+  utils::nns_update::add_hot_key(neuron_id, hotkey).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn remove_nns_neuron_hotkey(neuron_id: u64, hotkey: String) -> Result<(), String> {
+  get_active_pool_neuron(neuron_id)?;
+  utils::nns_update::remove_hot_key(neuron_id, hotkey).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn increase_nns_neuron_dissolve_delay(neuron_id: u64, additional_delay_seconds: u32) -> Result<(), String> {
+  get_active_pool_neuron(neuron_id)?;
+  utils::nns_update::increase_dissolve_delay(neuron_id, additional_delay_seconds).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn nns_neuron_start_dissolve(neuron_id: u64) -> Result<(), String> {
+  get_active_pool_neuron(neuron_id)?;
+  utils::nns_update::start_dissolve(neuron_id).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn nns_neuron_stop_dissolve(neuron_id: u64) -> Result<(), String> {
+  get_active_pool_neuron(neuron_id)?;
+  utils::nns_update::stop_dissolve(neuron_id).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn nns_neuron_disburse(neuron_id: u64) -> Result<(), String> {
+  let pool_neuron = get_active_pool_neuron(neuron_id)?;
+  disburse_pool_neuron(pool_neuron.get_pool_id(), neuron_id)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn nns_neuron_disburse_v2(neuron_id: u64) -> Result<(), StakingError> {
+  let pool_neuron = get_active_pool_neuron(neuron_id)?;
+  disburse_pool_neuron(pool_neuron.get_pool_id(), neuron_id).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_neuron_disburse_maturity(neuron_id: u64, percentage_to_disburse: u32) -> Result<E8S, String> {
+  let pool_neuron = get_active_pool_neuron(neuron_id)?;
+  disburse_pool_neuron_maturity(pool_neuron.get_pool_id(), neuron_id, percentage_to_disburse)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_neuron_disburse_maturity_v2(neuron_id: u64, percentage_to_disburse: u32) -> Result<E8S, StakingError> {
+  let pool_neuron = get_active_pool_neuron(neuron_id)?;
+  disburse_pool_neuron_maturity(pool_neuron.get_pool_id(), neuron_id, percentage_to_disburse).await
+}
+
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_neuron_stake_maturity(neuron_id: u64, percentage_to_stake: u32) -> Result<E8S, String> {
+  let pool_neuron = get_active_pool_neuron(neuron_id)?;
+  stake_pool_neuron_maturity(pool_neuron.get_pool_id(), neuron_id, percentage_to_stake)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[update]
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn nns_neuron_stake_maturity_v2(neuron_id: u64, percentage_to_stake: u32) -> Result<E8S, StakingError> {
+  let pool_neuron = get_active_pool_neuron(neuron_id)?;
+  stake_pool_neuron_maturity(pool_neuron.get_pool_id(), neuron_id, percentage_to_stake).await
+}
+
+async fn disburse_pool_neuron(pool_id: StakingPoolId, neuron_id: u64) -> Result<(), StakingError> {
+  let _entry_guard = EntryGuard::new(get_nns_disburse_guard_key(pool_id))
+    .map_err(|_| StakingError::already_processing(format!("The NNS neuron of pool {} is being disbursed, please try again later", pool_id)))?;
+
+  utils::nns_update::nns_disburse(neuron_id, pool_id)
+    .await
+    .map_err(StakingError::nns_governance_failed)
+}
+
+async fn disburse_pool_neuron_maturity(pool_id: StakingPoolId, neuron_id: u64, percentage_to_disburse: u32) -> Result<E8S, StakingError> {
+  if percentage_to_disburse == 0 || percentage_to_disburse > 100 {
+    return Err(StakingError::invalid_argument("The percentage must be between 1 and 100"));
+  }
+
+  let _entry_guard = EntryGuard::new(get_nns_maturity_guard_key(pool_id))
+    .map_err(|_| StakingError::already_processing(format!("The maturity of pool {} is being processed, please try again later", pool_id)))?;
+
+  let amount = utils::nns_update::disburse_maturity(neuron_id, pool_id, percentage_to_disburse)
+    .await
+    .map_err(StakingError::nns_governance_failed)?;
+
+  // The yield is booked when the NNS mints the disbursement into the pool account
+  if amount > 0 {
+    let to_account = generate_staking_pool_account_identifier(pool_id).to_hex();
+    NnsMaturityDisbursement::new(pool_id, neuron_id, amount, ic_cdk::api::time(), to_account).update_to_stable();
+  }
+
+  // The maturity is already moved, a failed sync is left to the NNS sync task so that a retry does not move it again
+  sync_nns_neuron_by_id(pool_id, neuron_id).await.unwrap_or_else(|e| {
+    ic_cdk::println!("Failed to sync NNS neuron {} for pool {}: {}", neuron_id, pool_id, e);
   });
-  if let Some(neuron) = neuron {
-    let neuron_id = neuron.id.map_or_else(
-      || {
-        ic_cdk::println!("Neuron ID is not set for pool ID: {}", pool_id);
-        Err("Neuron ID is not set".to_string())
-      },
-      |id| Ok(id),
-    )?;
-
-    utils::nns_update::stop_dissolve(neuron_id.id).await
-  } else {
-    Err(format!("No NNS neuron found for pool ID: {}", pool_id))
+
+  Ok(amount)
+}
+
+/// Book the NNS mint of a maturity disbursement into the staking pool account as the pool's yield.
+/// The mint is matched on the receiving account and the amount, it is kept for an admin to settle
+/// when no pending disbursement fits it or when disbursements of several neurons do.
+pub fn settle_nns_maturity_mint(
+  pool_id: StakingPoolId,
+  block_index: u64,
+  to_account: String,
+  amount: E8S,
+  mint_time: TimestampNanos,
+) -> Result<(), String> {
+  if NnsUnmatchedMaturityMint::get(block_index).is_some() {
+    return Ok(());
+  }
+
+  let candidates = NnsMaturityDisbursement::query_mint_candidates(pool_id, &to_account, amount);
+  let reason = match candidates.first() {
+    None => Some("No pending maturity disbursement matches the minted amount"),
+    Some(first) if candidates.iter().any(|candidate| candidate.get_neuron_id() != first.get_neuron_id()) => {
+      Some("Pending maturity disbursements of several neurons match the minted amount")
+    }
+    Some(_) => None,
+  };
+
+  if let Some(reason) = reason {
+    NnsUnmatchedMaturityMint::new(pool_id, block_index, to_account, amount, mint_time, reason.to_string()).update_to_stable();
+    return Err(format!("{} for the mint in block {} into pool {}", reason, block_index, pool_id));
   }
+
+  let mut disbursement = candidates.into_iter().next().unwrap();
+  record_nns_maturity_disburse_transaction(pool_id, disbursement.get_neuron_id(), amount, block_index, mint_time)?;
+  disbursement.update_to_minted(block_index, amount);
+  Ok(())
 }
 
+/// Book a maturity mint the deposit scanner could not match, or a mint made before the scanner's first run, against a neuron of the pool.
+/// With a disburse time the mint settles that pending disbursement of the neuron, without one it is booked as the neuron's yield alone.
 #[update]
-#[has_permission_result("staking::nns::update_nns_neuron")]
-async fn nns_unstake(pool_id: StakingPoolId) -> Result<(), String> {
-  let neuron = NNS_NEURON_MAP.with(|map| {
-    let map = map.borrow();
-    map.get(&pool_id).cloned()
+#[has_permission_result("staking::nns::manage_maturity", audit)]
+async fn settle_nns_unmatched_maturity_mint(block_index: u64, neuron_id: u64, disburse_time: Option<TimestampNanos>) -> Result<(), String> {
+  let mut mint = match NnsUnmatchedMaturityMint::get(block_index) {
+    Some(mint) => mint,
+    None => fetch_maturity_mint(block_index).await?,
+  };
+
+  if mint.is_settled() {
+    return Err(format!("The mint in block {} is already settled", block_index));
+  }
+
+  let pool_id = mint.get_pool_id();
+  let pool_neuron = NnsPoolNeuron::get_with_neuron_id(neuron_id).ok_or_else(|| format!("Neuron {} is not a pool neuron", neuron_id))?;
+  if pool_neuron.get_pool_id() != pool_id {
+    return Err(format!("Neuron {} does not belong to pool {}", neuron_id, pool_id));
+  }
+
+  let disbursement = match disburse_time {
+    Some(disburse_time) => {
+      let disbursement = NnsMaturityDisbursement::get(pool_id, neuron_id, disburse_time)
+        .ok_or_else(|| format!("No maturity disbursement of neuron {} at {}", neuron_id, disburse_time))?;
+      if !disbursement.is_pending() {
+        return Err(format!("The maturity disbursement of neuron {} at {} is already minted", neuron_id, disburse_time));
+      }
+      Some(disbursement)
+    }
+    None => None,
+  };
+
+  record_nns_maturity_disburse_transaction(pool_id, neuron_id, mint.get_amount(), block_index, mint.get_mint_time())?;
+  if let Some(mut disbursement) = disbursement {
+    disbursement.update_to_minted(block_index, mint.get_amount());
+  }
+  mint.update_to_settled(neuron_id);
+
+  Ok(())
+}
+
+/// Read a mint the deposit scanner did not see from the ledger, it must be a mint into a staking pool account not booked yet
+async fn fetch_maturity_mint(block_index: u64) -> Result<NnsUnmatchedMaturityMint, String> {
+  let tx_info = query_transaction_by_block_height(block_index).await?;
+  let to = match (tx_info.operation_type.as_str(), tx_info.to) {
+    ("Mint", Some(to)) => to,
+    _ => return Err(format!("Block {} is not a mint", block_index)),
+  };
+
+  let pool_id = match STAKING_ADDRESS_INDEX_MAP.with(|map| map.borrow().get(&to.to_hex())) {
+    Some(StakingAddressOwner::StakingPool(pool_id)) => pool_id,
+    _ => return Err(format!("The mint in block {} is not into a staking pool account", block_index)),
+  };
+
+  let already_minted = NnsMaturityDisbursement::query_by_pool(pool_id)
+    .iter()
+    .any(|disbursement| disbursement.minted_block_index == Some(block_index));
+  if already_minted {
+    return Err(format!("The mint in block {} is already booked", block_index));
+  }
+
+  Ok(NnsUnmatchedMaturityMint::new(
+    pool_id,
+    block_index,
+    to.to_hex(),
+    tx_info.amount,
+    tx_info.timestamp,
+    "Minted before the deposit scanner's first run".to_string(),
+  ))
+}
+
+async fn stake_pool_neuron_maturity(pool_id: StakingPoolId, neuron_id: u64, percentage_to_stake: u32) -> Result<E8S, StakingError> {
+  if percentage_to_stake == 0 || percentage_to_stake > 100 {
+    return Err(StakingError::invalid_argument("The percentage must be between 1 and 100"));
+  }
+
+  let _entry_guard = EntryGuard::new(get_nns_maturity_guard_key(pool_id))
+    .map_err(|_| StakingError::already_processing(format!("The maturity of pool {} is being processed, please try again later", pool_id)))?;
+
+  let neuron = query_nns_neuron_by_pool_id(neuron_id)
+    .await
+    .map_err(StakingError::nns_governance_failed)?;
+  let staked_maturity_before = neuron.staked_maturity_e8s_equivalent.unwrap_or_default();
+
+  let resp = utils::nns_update::stake_maturity(neuron_id, percentage_to_stake)
+    .await
+    .map_err(StakingError::nns_governance_failed)?;
+  let staked_maturity = resp.staked_maturity_e8s.saturating_sub(staked_maturity_before);
+
+  if staked_maturity > 0 {
+    record_nns_maturity_stake_transaction(pool_id, neuron_id, staked_maturity, ic_cdk::api::time())?;
+  }
+
+  // The maturity is already moved, a failed sync is left to the NNS sync task so that a retry does not move it again
+  sync_nns_neuron_by_id(pool_id, neuron_id).await.unwrap_or_else(|e| {
+    ic_cdk::println!("Failed to sync NNS neuron {} for pool {}: {}", neuron_id, pool_id, e);
   });
-  if let Some(neuron) = neuron {
-    let neuron_id = neuron.id.map_or_else(
-      || {
-        ic_cdk::println!("Neuron ID is not set for pool ID: {}", pool_id);
-        Err("Neuron ID is not set".to_string())
-      },
-      |id| Ok(id),
-    )?;
-
-    utils::nns_update::nns_disburse(neuron_id.id, pool_id).await
-  } else {
-    Err(format!("No NNS neuron found for pool ID: {}", pool_id))
+
+  Ok(staked_maturity)
+}
+
+/// Set the followees of the pool neurons for the topic, returns the neurons that failed to take them.
+/// The configuration is kept whatever the failures, the NNS sync task re-applies it to the neurons that missed it.
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn set_nns_followees(pool_id: StakingPoolId, topic: i32, followees: Vec<u64>) -> Result<Vec<u64>, String> {
+  Ok(set_pool_topic_followees(pool_id, topic, followees).await)
+}
+
+/// Clear the followees of the pool neurons for the topic, returns the neurons that failed to clear them
+#[update]
+#[has_permission_result("staking::nns::update_nns_neuron", audit)]
+async fn clear_nns_followees(pool_id: StakingPoolId, topic: i32) -> Result<Vec<u64>, String> {
+  Ok(set_pool_topic_followees(pool_id, topic, vec![]).await)
+}
+
+async fn set_pool_topic_followees(pool_id: StakingPoolId, topic: i32, followees: Vec<u64>) -> Vec<u64> {
+  let mut failed_neuron_ids = Vec::new();
+
+  for pool_neuron in NnsPoolNeuron::query_by_pool(pool_id).into_iter().filter(|n| n.is_active()) {
+    let neuron_id = pool_neuron.get_neuron_id();
+    if let Err(e) = utils::nns_update::set_following(neuron_id, topic, followees.clone()).await {
+      ic_cdk::println!(
+        "Failed to set the followees of topic {} for NNS neuron {} of pool {}: {}",
+        topic,
+        neuron_id,
+        pool_id,
+        e
+      );
+      failed_neuron_ids.push(neuron_id);
+    }
   }
+
+  NnsFolloweesConfig::get_with_pool(pool_id).set_topic_followees(topic, followees);
+  failed_neuron_ids
+}
+
+/// Query the neuron disburses whose settlement failed and is retried by the NNS sync task
+#[query]
+fn get_nns_pending_disburse_settlements() -> Vec<NnsPendingDisburseSettlement> {
+  NnsPendingDisburseSettlement::query_all()
+}
+
+/// Query the mints into the staking pool accounts that no maturity disbursement was matched to, with the ones an admin settled
+#[query]
+fn get_nns_unmatched_maturity_mints() -> Vec<NnsUnmatchedMaturityMint> {
+  NnsUnmatchedMaturityMint::query_all()
+}
+
+/// Query the maturity disbursements of the staking pool, the pending ones have no minted block index yet
+#[query]
+fn get_nns_maturity_disbursements(pool_id: StakingPoolId) -> Vec<NnsMaturityDisbursement> {
+  NnsMaturityDisbursement::query_by_pool(pool_id)
+}
+
+#[query]
+fn get_nns_followees(pool_id: StakingPoolId) -> Vec<(i32, Vec<u64>)> {
+  NnsFolloweesConfig::get_with_pool(pool_id)
+    .get_followees()
+    .into_iter()
+    .filter(|(_, followees)| !followees.is_empty())
+    .collect()
 }
@@ -1,11 +1,23 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 
-use super::NNS_STAKING_EXECUTE_RECORD_MAP;
+use super::{
+  NNS_MATURITY_DISBURSEMENT_MAP, NNS_PENDING_DISBURSE_SETTLEMENT_MAP, NNS_POOL_FOLLOWEES_MAP, NNS_POOL_NEURON_INDEX_MAP, NNS_POOL_NEURON_MAP,
+  NNS_STAKING_EXECUTE_RECORD_MAP, NNS_STAKING_EXECUTE_RECORD_POOL_INDEX_MAP, NNS_UNMATCHED_MATURITY_MINT_MAP,
+};
+use crate::migrations::{NNS_STAKING_EXECUTE_RECORD_INDEX_SCHEMA_VERSION, SCHEMA_MIGRATOR};
 use candid::{CandidType, Decode, Encode};
 use ic_ledger_types::BlockIndex;
 use ic_stable_structures::{storable::Bound, Storable};
+use nns_governance_api::nns_governance_api::{DissolveState, Neuron};
 use serde::{Deserialize, Serialize};
-use types::{assets_management::ProposalId, stable_structures::MetaData, staking::StakingPoolId, E8S};
+use types::{
+  assets_management::ProposalId,
+  composite_entity_index,
+  entities::{add_indexed_id, get_indexed_ids},
+  stable_structures::MetaData,
+  staking::StakingPoolId,
+  TimestampNanos, E8S,
+};
 
 /// NNS neuron staking record status
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
@@ -14,6 +26,8 @@ pub enum NnsStakeExecuteStatus {
   RefreshToNnsNeuronError(String, String),
   /// Proposal executed successfully
   Success,
+  /// The neuron has been disbursed back to the staking pool, with the block index of the disburse transfer
+  Disbursed(BlockIndex),
 }
 
 /// NNS staking execute record, which is used to track the execution of staking operations
@@ -29,6 +43,10 @@ pub struct NnsStakeExecuteRecord {
   pub pool_to_neuron_transfer_block_index: Option<BlockIndex>,
   /// Staked amount (unit: e8s)
   pub amount: Option<E8S>,
+  /// Position of the neuron in the staking pool's neuron ladder
+  pub ladder_index: Option<u32>,
+  /// Dissolve delay the neuron is set to when it is created (unit: seconds)
+  pub dissolve_delay_seconds: Option<u64>,
   /// Record status
   pub status: Option<NnsStakeExecuteStatus>,
   /// Metadata
@@ -37,7 +55,7 @@ pub struct NnsStakeExecuteRecord {
 
 impl NnsStakeExecuteRecord {
   /// Creates a new NNS stake execute record
-  pub fn init_with(proposal_id: ProposalId, pool_id: StakingPoolId, amount: E8S) -> Self {
+  pub fn init_with(proposal_id: ProposalId, pool_id: StakingPoolId, amount: E8S, ladder_index: u32, dissolve_delay_seconds: Option<u64>) -> Self {
     let record = Self::get_with_proposal(proposal_id);
 
     if let Some(existing_record) = record {
@@ -50,6 +68,8 @@ impl NnsStakeExecuteRecord {
       neuron_id: None,
       pool_to_neuron_transfer_block_index: None,
       amount: Some(amount),
+      ladder_index: Some(ladder_index),
+      dissolve_delay_seconds,
       status: None,
       meta: Some(MetaData::init_create_scene()),
     }
@@ -77,6 +97,33 @@ impl NnsStakeExecuteRecord {
     self.update_to_stable();
   }
 
+  pub fn update_to_disbursed(&mut self, block_index: BlockIndex) {
+    self.status = Some(NnsStakeExecuteStatus::Disbursed(block_index));
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  /// Query the successfully executed records that staked into the neuron, through the pool index once the migration built it
+  pub fn query_success_with_neuron(pool_id: StakingPoolId, neuron_id: u64) -> Vec<Self> {
+    let is_staked_into_neuron = |record: &Self| {
+      record.get_pool_id() == pool_id && record.get_neuron_id() == neuron_id && *record.get_status() == NnsStakeExecuteStatus::Success
+    };
+
+    if !SCHEMA_MIGRATOR.is_migrated_to(NNS_STAKING_EXECUTE_RECORD_INDEX_SCHEMA_VERSION) {
+      return NNS_STAKING_EXECUTE_RECORD_MAP.with(|map| map.borrow().values().filter(is_staked_into_neuron).collect());
+    }
+
+    let proposal_ids = NNS_STAKING_EXECUTE_RECORD_POOL_INDEX_MAP.with(|index_map| composite_entity_index::get_indexed_ids(index_map, &pool_id));
+    NNS_STAKING_EXECUTE_RECORD_MAP.with(|map| {
+      let map = map.borrow();
+      proposal_ids
+        .into_iter()
+        .filter_map(|proposal_id| map.get(&proposal_id))
+        .filter(is_staked_into_neuron)
+        .collect()
+    })
+  }
+
   pub fn get_proposal_id(&self) -> ProposalId {
     self.proposal_id.unwrap_or_default()
   }
@@ -91,6 +138,13 @@ impl NnsStakeExecuteRecord {
     NNS_STAKING_EXECUTE_RECORD_MAP.with(|map| {
       map.borrow_mut().insert(self.get_proposal_id(), self.clone());
     });
+    self.add_pool_index();
+  }
+
+  /// Add the record to the pool index, adding an indexed record again leaves the index unchanged
+  pub fn add_pool_index(&self) {
+    NNS_STAKING_EXECUTE_RECORD_POOL_INDEX_MAP
+      .with(|index_map| composite_entity_index::add_indexed_id(index_map, &self.get_pool_id(), self.get_proposal_id()));
   }
 
   pub fn get_pool_id(&self) -> StakingPoolId {
@@ -105,6 +159,10 @@ impl NnsStakeExecuteRecord {
     self.neuron_id.unwrap_or_default()
   }
 
+  pub fn get_ladder_index(&self) -> u32 {
+    self.ladder_index.unwrap_or_default()
+  }
+
   pub fn get_status(&self) -> &NnsStakeExecuteStatus {
     self.status.as_ref().unwrap_or(&NnsStakeExecuteStatus::Success)
   }
@@ -136,3 +194,628 @@ impl Storable for NnsStakeExecuteRecord {
 
   const BOUND: Bound = Bound::Unbounded;
 }
+
+/// Last synced NNS neuron of a staking pool
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct NnsNeuronCache {
+  /// Associated staking pool ID
+  pub pool_id: Option<StakingPoolId>,
+  /// Full neuron as returned by the NNS governance canister
+  pub neuron: Option<Neuron>,
+  /// Time of the last successful sync
+  pub synced_at: Option<TimestampNanos>,
+}
+
+impl NnsNeuronCache {
+  pub fn new(pool_id: StakingPoolId, neuron: Neuron, synced_at: TimestampNanos) -> Self {
+    Self {
+      pool_id: Some(pool_id),
+      neuron: Some(neuron),
+      synced_at: Some(synced_at),
+    }
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_neuron(&self) -> Option<Neuron> {
+    self.neuron.clone()
+  }
+
+  pub fn get_synced_at(&self) -> TimestampNanos {
+    self.synced_at.unwrap_or_default()
+  }
+}
+
+impl Storable for NnsNeuronCache {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Point-in-time figures of an NNS neuron, taken on every sync to chart the neuron growth of a pool
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct NnsNeuronSnapshot {
+  /// Associated staking pool ID
+  pub pool_id: Option<StakingPoolId>,
+  /// NNS neuron ID
+  pub neuron_id: Option<u64>,
+  /// Cached neuron stake (unit: e8s)
+  pub cached_neuron_stake_e8s: Option<E8S>,
+  /// Maturity not yet staked (unit: e8s)
+  pub maturity_e8s_equivalent: Option<E8S>,
+  /// Staked maturity (unit: e8s)
+  pub staked_maturity_e8s_equivalent: Option<E8S>,
+  /// Dissolve state at the time of the snapshot
+  pub dissolve_state: Option<DissolveState>,
+  /// Potential voting power of the neuron
+  pub potential_voting_power: Option<u64>,
+  /// Deciding voting power of the neuron
+  pub deciding_voting_power: Option<u64>,
+  /// Time of the sync that produced this snapshot
+  pub synced_at: Option<TimestampNanos>,
+}
+
+impl NnsNeuronSnapshot {
+  pub fn from_neuron(pool_id: StakingPoolId, neuron: &Neuron, synced_at: TimestampNanos) -> Self {
+    Self {
+      pool_id: Some(pool_id),
+      neuron_id: neuron.id.as_ref().map(|id| id.id),
+      cached_neuron_stake_e8s: Some(neuron.cached_neuron_stake_e8s),
+      maturity_e8s_equivalent: Some(neuron.maturity_e8s_equivalent),
+      staked_maturity_e8s_equivalent: Some(neuron.staked_maturity_e8s_equivalent.unwrap_or_default()),
+      dissolve_state: neuron.dissolve_state.clone(),
+      potential_voting_power: neuron.potential_voting_power,
+      deciding_voting_power: neuron.deciding_voting_power,
+      synced_at: Some(synced_at),
+    }
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_neuron_id(&self) -> u64 {
+    self.neuron_id.unwrap_or_default()
+  }
+
+  pub fn get_synced_at(&self) -> TimestampNanos {
+    self.synced_at.unwrap_or_default()
+  }
+}
+
+impl Storable for NnsNeuronSnapshot {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Key of the neuron snapshot series: (neuron ID, sync time).
+/// Encoded as fixed size big-endian bytes so that the stable map keeps the snapshots of a neuron in time order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NnsNeuronSnapshotKey(pub u64, pub TimestampNanos);
+
+impl Storable for NnsNeuronSnapshotKey {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    let mut bytes = Vec::with_capacity(16);
+    bytes.extend_from_slice(&self.0.to_be_bytes());
+    bytes.extend_from_slice(&self.1.to_be_bytes());
+    Cow::Owned(bytes)
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    let neuron_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+    let synced_at = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+    Self(neuron_id, synced_at)
+  }
+
+  const BOUND: Bound = Bound::Bounded {
+    max_size: 16,
+    is_fixed_size: true,
+  };
+}
+
+/// Followee configuration of the staking pool's NNS neuron, re-applied whenever the pool gets a new neuron
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct NnsFolloweesConfig {
+  /// Associated staking pool ID
+  pub pool_id: Option<StakingPoolId>,
+  /// Followee neuron IDs by NNS proposal topic
+  pub followees: Option<BTreeMap<i32, Vec<u64>>>,
+  /// Metadata
+  pub meta: Option<MetaData>,
+}
+
+impl NnsFolloweesConfig {
+  pub fn get_with_pool(pool_id: StakingPoolId) -> Self {
+    NNS_POOL_FOLLOWEES_MAP.with(|map| map.borrow().get(&pool_id)).unwrap_or_else(|| Self {
+      pool_id: Some(pool_id),
+      followees: Some(BTreeMap::new()),
+      meta: Some(MetaData::init_create_scene()),
+    })
+  }
+
+  /// Set the followees of the topic, an empty list keeps the topic cleared on the pool's neurons
+  pub fn set_topic_followees(&mut self, topic: i32, followees: Vec<u64>) {
+    let mut topic_followees = self.get_followees();
+    topic_followees.insert(topic, followees);
+
+    self.followees = Some(topic_followees);
+    if let Some(meta) = &self.meta {
+      self.meta = Some(meta.update());
+    }
+    self.update_to_stable();
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_followees(&self) -> BTreeMap<i32, Vec<u64>> {
+    self.followees.clone().unwrap_or_default()
+  }
+
+  pub fn update_to_stable(&self) {
+    NNS_POOL_FOLLOWEES_MAP.with(|map| {
+      map.borrow_mut().insert(self.get_pool_id(), self.clone());
+    });
+  }
+}
+
+impl Storable for NnsFolloweesConfig {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Status of a neuron held by a staking pool
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum NnsPoolNeuronStatus {
+  /// The neuron holds funds of the staking pool
+  Active,
+  /// The neuron has been disbursed back to the staking pool, with the block index of the disburse transfer
+  Disbursed(BlockIndex),
+}
+
+/// A neuron in the staking pool's neuron ladder.
+/// Each neuron has its own nonce, and so its own staking subaccount, which lets a pool spread its funds over several dissolve delays.
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct NnsPoolNeuron {
+  /// NNS neuron ID
+  pub neuron_id: Option<u64>,
+  /// Associated staking pool ID
+  pub pool_id: Option<StakingPoolId>,
+  /// Nonce (memo) the neuron was claimed with
+  pub nonce: Option<u64>,
+  /// Position of the neuron in the staking pool's neuron ladder
+  pub ladder_index: Option<u32>,
+  /// Dissolve delay the neuron was set to when it was created (unit: seconds)
+  pub dissolve_delay_seconds: Option<u64>,
+  /// Principal the staking pool has staked into the neuron (unit: e8s)
+  pub staked_principal: Option<E8S>,
+  /// Neuron status
+  pub status: Option<NnsPoolNeuronStatus>,
+  /// Metadata
+  pub meta: Option<MetaData>,
+}
+
+impl NnsPoolNeuron {
+  pub fn new(neuron_id: u64, pool_id: StakingPoolId, nonce: u64, ladder_index: u32, dissolve_delay_seconds: Option<u64>) -> Self {
+    Self {
+      neuron_id: Some(neuron_id),
+      pool_id: Some(pool_id),
+      nonce: Some(nonce),
+      ladder_index: Some(ladder_index),
+      dissolve_delay_seconds,
+      staked_principal: Some(0),
+      status: Some(NnsPoolNeuronStatus::Active),
+      meta: Some(MetaData::init_create_scene()),
+    }
+  }
+
+  pub fn get_with_neuron_id(neuron_id: u64) -> Option<Self> {
+    NNS_POOL_NEURON_MAP.with(|map| map.borrow().get(&neuron_id))
+  }
+
+  /// Query all neurons of the staking pool, including the disbursed ones
+  pub fn query_by_pool(pool_id: StakingPoolId) -> Vec<Self> {
+    let neuron_ids = NNS_POOL_NEURON_INDEX_MAP.with(|index_map| get_indexed_ids(index_map, &pool_id));
+
+    NNS_POOL_NEURON_MAP.with(|map| {
+      let map = map.borrow();
+      neuron_ids.into_iter().filter_map(|neuron_id| map.get(&neuron_id)).collect()
+    })
+  }
+
+  pub fn add_staked_principal(&mut self, amount: E8S) {
+    self.staked_principal = Some(self.get_staked_principal().saturating_add(amount));
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn update_to_disbursed(&mut self, block_index: BlockIndex) {
+    self.staked_principal = Some(0);
+    self.status = Some(NnsPoolNeuronStatus::Disbursed(block_index));
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn get_neuron_id(&self) -> u64 {
+    self.neuron_id.unwrap_or_default()
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_nonce(&self) -> u64 {
+    self.nonce.unwrap_or_default()
+  }
+
+  pub fn get_ladder_index(&self) -> u32 {
+    self.ladder_index.unwrap_or_default()
+  }
+
+  pub fn get_dissolve_delay_seconds(&self) -> u64 {
+    self.dissolve_delay_seconds.unwrap_or_default()
+  }
+
+  pub fn get_staked_principal(&self) -> E8S {
+    self.staked_principal.unwrap_or_default()
+  }
+
+  pub fn get_status(&self) -> NnsPoolNeuronStatus {
+    self.status.clone().unwrap_or(NnsPoolNeuronStatus::Active)
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.get_status() == NnsPoolNeuronStatus::Active
+  }
+
+  pub fn get_meta(&self) -> MetaData {
+    self.meta.clone().unwrap_or_default()
+  }
+
+  fn update_meta(&mut self) {
+    if let Some(meta) = &mut self.meta {
+      self.meta = Some(meta.update());
+    }
+  }
+
+  pub fn update_to_stable(&self) {
+    NNS_POOL_NEURON_MAP.with(|map| {
+      map.borrow_mut().insert(self.get_neuron_id(), self.clone());
+    });
+
+    NNS_POOL_NEURON_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &self.get_pool_id(), self.get_neuron_id()));
+  }
+}
+
+impl Storable for NnsPoolNeuron {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Maturity modulation the NNS may apply between the disbursement and the mint, in basis points either way
+const MATURITY_MODULATION_BASIS_POINTS: u128 = 500;
+
+/// A maturity disbursement of a pool neuron.
+/// The NNS mints the disbursed ICP into the staking pool account about 7 days after the disbursement,
+/// with the amount modulated in the meantime, so the yield is booked when the deposit scanner sees the mint.
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct NnsMaturityDisbursement {
+  /// Associated staking pool ID
+  pub pool_id: Option<StakingPoolId>,
+  /// NNS neuron ID
+  pub neuron_id: Option<u64>,
+  /// Amount the NNS scheduled when the maturity was disbursed (unit: e8s)
+  pub scheduled_amount: Option<E8S>,
+  pub disburse_time: Option<TimestampNanos>,
+  /// Block index of the mint into the staking pool account, None while the mint is pending
+  pub minted_block_index: Option<BlockIndex>,
+  /// Amount actually minted (unit: e8s)
+  pub minted_amount: Option<E8S>,
+  /// Hex account identifier the neuron disbursed its maturity to
+  pub to_account: Option<String>,
+}
+
+impl NnsMaturityDisbursement {
+  pub fn new(pool_id: StakingPoolId, neuron_id: u64, scheduled_amount: E8S, disburse_time: TimestampNanos, to_account: String) -> Self {
+    Self {
+      pool_id: Some(pool_id),
+      neuron_id: Some(neuron_id),
+      scheduled_amount: Some(scheduled_amount),
+      disburse_time: Some(disburse_time),
+      minted_block_index: None,
+      minted_amount: None,
+      to_account: Some(to_account),
+    }
+  }
+
+  /// Query the maturity disbursements of the staking pool, by neuron and in disbursement order
+  pub fn query_by_pool(pool_id: StakingPoolId) -> Vec<Self> {
+    NNS_MATURITY_DISBURSEMENT_MAP.with(|map| {
+      map
+        .borrow()
+        .range(NnsMaturityDisbursementKey(pool_id, 0, 0)..=NnsMaturityDisbursementKey(pool_id, u64::MAX, TimestampNanos::MAX))
+        .map(|(_, disbursement)| disbursement)
+        .collect()
+    })
+  }
+
+  pub fn get(pool_id: StakingPoolId, neuron_id: u64, disburse_time: TimestampNanos) -> Option<Self> {
+    NNS_MATURITY_DISBURSEMENT_MAP.with(|map| map.borrow().get(&NnsMaturityDisbursementKey(pool_id, neuron_id, disburse_time)))
+  }
+
+  /// The pending disbursements of the staking pool the mint can belong to: disbursed to the receiving account,
+  /// with a scheduled amount the minted amount is within the maturity modulation of, oldest first
+  pub fn query_mint_candidates(pool_id: StakingPoolId, to_account: &str, minted_amount: E8S) -> Vec<Self> {
+    let mut candidates: Vec<Self> = Self::query_by_pool(pool_id)
+      .into_iter()
+      .filter(|disbursement| disbursement.is_pending())
+      .filter(|disbursement| disbursement.to_account.as_deref() == Some(to_account))
+      .filter(|disbursement| disbursement.accepts_minted_amount(minted_amount))
+      .collect();
+    candidates.sort_by_key(|disbursement| disbursement.get_disburse_time());
+    candidates
+  }
+
+  /// The minted amount is within the maturity modulation of the scheduled amount
+  fn accepts_minted_amount(&self, minted_amount: E8S) -> bool {
+    let scheduled = self.scheduled_amount.unwrap_or_default() as u128 * 10_000;
+    let minted = minted_amount as u128 * 10_000;
+    let tolerance = self.scheduled_amount.unwrap_or_default() as u128 * MATURITY_MODULATION_BASIS_POINTS;
+    minted >= scheduled.saturating_sub(tolerance) && minted <= scheduled + tolerance
+  }
+
+  pub fn update_to_minted(&mut self, block_index: BlockIndex, amount: E8S) {
+    self.minted_block_index = Some(block_index);
+    self.minted_amount = Some(amount);
+    self.update_to_stable();
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_neuron_id(&self) -> u64 {
+    self.neuron_id.unwrap_or_default()
+  }
+
+  pub fn get_disburse_time(&self) -> TimestampNanos {
+    self.disburse_time.unwrap_or_default()
+  }
+
+  pub fn is_pending(&self) -> bool {
+    self.minted_block_index.is_none()
+  }
+
+  pub fn update_to_stable(&self) {
+    NNS_MATURITY_DISBURSEMENT_MAP.with(|map| {
+      map.borrow_mut().insert(
+        NnsMaturityDisbursementKey(self.get_pool_id(), self.get_neuron_id(), self.get_disburse_time()),
+        self.clone(),
+      );
+    });
+  }
+}
+
+impl Storable for NnsMaturityDisbursement {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Key of the maturity disbursements: (pool ID, neuron ID, disburse time), big-endian so that a neuron's disbursements stay in time order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NnsMaturityDisbursementKey(pub StakingPoolId, pub u64, pub TimestampNanos);
+
+impl Storable for NnsMaturityDisbursementKey {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    let mut bytes = Vec::with_capacity(24);
+    bytes.extend_from_slice(&self.0.to_be_bytes());
+    bytes.extend_from_slice(&self.1.to_be_bytes());
+    bytes.extend_from_slice(&self.2.to_be_bytes());
+    Cow::Owned(bytes)
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    let pool_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+    let neuron_id = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+    let disburse_time = u64::from_be_bytes(bytes[16..24].try_into().unwrap());
+    Self(pool_id, neuron_id, disburse_time)
+  }
+
+  const BOUND: Bound = Bound::Bounded {
+    max_size: 24,
+    is_fixed_size: true,
+  };
+}
+
+/// A mint into a staking pool account that no pending maturity disbursement could be matched to,
+/// kept until an admin books it against a neuron
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct NnsUnmatchedMaturityMint {
+  pub pool_id: Option<StakingPoolId>,
+  /// Block index of the mint
+  pub block_index: Option<BlockIndex>,
+  /// Hex account identifier the mint went to
+  pub to_account: Option<String>,
+  /// Minted amount (unit: e8s)
+  pub amount: Option<E8S>,
+  pub mint_time: Option<TimestampNanos>,
+  /// Why the mint was not matched
+  pub reason: Option<String>,
+  /// Neuron the admin booked the mint against, None while the mint is unsettled
+  pub settled_neuron_id: Option<u64>,
+  pub settled_time: Option<TimestampNanos>,
+}
+
+impl NnsUnmatchedMaturityMint {
+  pub fn new(pool_id: StakingPoolId, block_index: BlockIndex, to_account: String, amount: E8S, mint_time: TimestampNanos, reason: String) -> Self {
+    Self {
+      pool_id: Some(pool_id),
+      block_index: Some(block_index),
+      to_account: Some(to_account),
+      amount: Some(amount),
+      mint_time: Some(mint_time),
+      reason: Some(reason),
+      settled_neuron_id: None,
+      settled_time: None,
+    }
+  }
+
+  pub fn get(block_index: BlockIndex) -> Option<Self> {
+    NNS_UNMATCHED_MATURITY_MINT_MAP.with(|map| map.borrow().get(&block_index))
+  }
+
+  pub fn query_all() -> Vec<Self> {
+    NNS_UNMATCHED_MATURITY_MINT_MAP.with(|map| map.borrow().values().collect())
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_block_index(&self) -> BlockIndex {
+    self.block_index.unwrap_or_default()
+  }
+
+  pub fn get_amount(&self) -> E8S {
+    self.amount.unwrap_or_default()
+  }
+
+  pub fn get_mint_time(&self) -> TimestampNanos {
+    self.mint_time.unwrap_or_default()
+  }
+
+  pub fn is_settled(&self) -> bool {
+    self.settled_neuron_id.is_some()
+  }
+
+  pub fn update_to_settled(&mut self, neuron_id: u64) {
+    self.settled_neuron_id = Some(neuron_id);
+    self.settled_time = Some(ic_cdk::api::time());
+    self.update_to_stable();
+  }
+
+  pub fn update_to_stable(&self) {
+    NNS_UNMATCHED_MATURITY_MINT_MAP.with(|map| map.borrow_mut().insert(self.get_block_index(), self.clone()));
+  }
+}
+
+impl Storable for NnsUnmatchedMaturityMint {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// A neuron disburse whose transfer still has to be settled into the pool's funds and transaction records.
+/// Saved as soon as the NNS returns the transfer block height, removed once the settlement succeeded.
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct NnsPendingDisburseSettlement {
+  pub pool_id: Option<StakingPoolId>,
+  pub neuron_id: Option<u64>,
+  /// Block height of the disburse transfer into the staking pool account
+  pub block_index: Option<BlockIndex>,
+  pub disburse_time: Option<TimestampNanos>,
+  pub retry_count: Option<u32>,
+  pub last_error: Option<String>,
+}
+
+impl NnsPendingDisburseSettlement {
+  pub fn new(pool_id: StakingPoolId, neuron_id: u64, block_index: BlockIndex, disburse_time: TimestampNanos) -> Self {
+    Self {
+      pool_id: Some(pool_id),
+      neuron_id: Some(neuron_id),
+      block_index: Some(block_index),
+      disburse_time: Some(disburse_time),
+      retry_count: Some(0),
+      last_error: None,
+    }
+  }
+
+  pub fn query_all() -> Vec<Self> {
+    NNS_PENDING_DISBURSE_SETTLEMENT_MAP.with(|map| map.borrow().values().collect())
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_neuron_id(&self) -> u64 {
+    self.neuron_id.unwrap_or_default()
+  }
+
+  pub fn get_block_index(&self) -> BlockIndex {
+    self.block_index.unwrap_or_default()
+  }
+
+  pub fn get_retry_count(&self) -> u32 {
+    self.retry_count.unwrap_or_default()
+  }
+
+  pub fn update_to_failed(&mut self, error: String) {
+    self.retry_count = Some(self.get_retry_count() + 1);
+    self.last_error = Some(error);
+    self.update_to_stable();
+  }
+
+  pub fn update_to_stable(&self) {
+    NNS_PENDING_DISBURSE_SETTLEMENT_MAP.with(|map| map.borrow_mut().insert(self.get_block_index(), self.clone()));
+  }
+
+  pub fn remove_from_stable(&self) {
+    NNS_PENDING_DISBURSE_SETTLEMENT_MAP.with(|map| map.borrow_mut().remove(&self.get_block_index()));
+  }
+}
+
+impl Storable for NnsPendingDisburseSettlement {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
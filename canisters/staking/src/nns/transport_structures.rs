@@ -1,9 +1,10 @@
 use candid::CandidType;
 use ic_ledger_types::BlockIndex;
+use nns_governance_api::nns_governance_api::{DissolveState, Neuron};
 use serde::{Deserialize, Serialize};
-use types::{assets_management::ProposalId, stable_structures::MetaData, staking::StakingPoolId, E8S};
+use types::{assets_management::ProposalId, stable_structures::MetaData, staking::StakingPoolId, TimestampNanos, E8S};
 
-use super::stable_structures::{NnsStakeExecuteRecord, NnsStakeExecuteStatus};
+use super::stable_structures::{NnsNeuronCache, NnsNeuronSnapshot, NnsPoolNeuron, NnsPoolNeuronStatus, NnsStakeExecuteRecord, NnsStakeExecuteStatus};
 
 /// NNS staking execute record for transfer layer (without Option wrappers)
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -18,6 +19,10 @@ pub struct NnsStakeExecuteRecordVo {
   pub pool_to_neuron_transfer_block_index: BlockIndex,
   /// Staked amount (unit: e8s)
   pub amount: E8S,
+  /// Position of the neuron in the staking pool's neuron ladder
+  pub ladder_index: u32,
+  /// Dissolve delay the neuron is set to when it is created (unit: seconds)
+  pub dissolve_delay_seconds: Option<u64>,
   /// Record status
   pub status: NnsStakeExecuteStatus,
   /// Metadata
@@ -32,8 +37,86 @@ impl From<NnsStakeExecuteRecord> for NnsStakeExecuteRecordVo {
       neuron_id: record.neuron_id.unwrap_or_default(),
       pool_to_neuron_transfer_block_index: record.pool_to_neuron_transfer_block_index.unwrap_or_default(),
       amount: record.amount.unwrap_or_default(),
+      ladder_index: record.ladder_index.unwrap_or_default(),
+      dissolve_delay_seconds: record.dissolve_delay_seconds,
       status: record.status.unwrap_or(NnsStakeExecuteStatus::Success),
       meta: record.meta.unwrap_or_default(),
     }
   }
 }
+
+/// Cached NNS neuron of a staking pool for transfer layer
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct NnsNeuronCacheVo {
+  pub pool_id: StakingPoolId,
+  pub neuron: Option<Neuron>,
+  pub synced_at: TimestampNanos,
+}
+
+impl From<NnsNeuronCache> for NnsNeuronCacheVo {
+  fn from(cache: NnsNeuronCache) -> Self {
+    Self {
+      pool_id: cache.get_pool_id(),
+      synced_at: cache.get_synced_at(),
+      neuron: cache.neuron,
+    }
+  }
+}
+
+/// NNS neuron snapshot for transfer layer
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct NnsNeuronSnapshotVo {
+  pub pool_id: StakingPoolId,
+  pub neuron_id: u64,
+  pub cached_neuron_stake_e8s: E8S,
+  pub maturity_e8s_equivalent: E8S,
+  pub staked_maturity_e8s_equivalent: E8S,
+  pub dissolve_state: Option<DissolveState>,
+  pub potential_voting_power: u64,
+  pub deciding_voting_power: u64,
+  pub synced_at: TimestampNanos,
+}
+
+impl From<NnsNeuronSnapshot> for NnsNeuronSnapshotVo {
+  fn from(snapshot: NnsNeuronSnapshot) -> Self {
+    Self {
+      pool_id: snapshot.pool_id.unwrap_or_default(),
+      neuron_id: snapshot.neuron_id.unwrap_or_default(),
+      cached_neuron_stake_e8s: snapshot.cached_neuron_stake_e8s.unwrap_or_default(),
+      maturity_e8s_equivalent: snapshot.maturity_e8s_equivalent.unwrap_or_default(),
+      staked_maturity_e8s_equivalent: snapshot.staked_maturity_e8s_equivalent.unwrap_or_default(),
+      dissolve_state: snapshot.dissolve_state,
+      potential_voting_power: snapshot.potential_voting_power.unwrap_or_default(),
+      deciding_voting_power: snapshot.deciding_voting_power.unwrap_or_default(),
+      synced_at: snapshot.synced_at.unwrap_or_default(),
+    }
+  }
+}
+
+/// Neuron of the staking pool's neuron ladder for transfer layer
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct NnsPoolNeuronVo {
+  pub neuron_id: u64,
+  pub pool_id: StakingPoolId,
+  pub nonce: u64,
+  pub ladder_index: u32,
+  pub dissolve_delay_seconds: u64,
+  pub staked_principal: E8S,
+  pub status: NnsPoolNeuronStatus,
+  pub meta: MetaData,
+}
+
+impl From<NnsPoolNeuron> for NnsPoolNeuronVo {
+  fn from(pool_neuron: NnsPoolNeuron) -> Self {
+    Self {
+      neuron_id: pool_neuron.get_neuron_id(),
+      pool_id: pool_neuron.get_pool_id(),
+      nonce: pool_neuron.get_nonce(),
+      ladder_index: pool_neuron.get_ladder_index(),
+      dissolve_delay_seconds: pool_neuron.get_dissolve_delay_seconds(),
+      staked_principal: pool_neuron.get_staked_principal(),
+      status: pool_neuron.get_status(),
+      meta: pool_neuron.get_meta(),
+    }
+  }
+}
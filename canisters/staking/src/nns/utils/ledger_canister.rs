@@ -130,7 +130,7 @@ pub struct TransferArg {
   pub amount: candid::Nat,
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Debug)]
 pub enum TransferError {
   GenericError { message: String, error_code: candid::Nat },
   TemporarilyUnavailable,
@@ -272,7 +272,7 @@ pub struct TransferFromArgs {
   pub amount: candid::Nat,
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Debug)]
 pub enum TransferFromError {
   GenericError { message: String, error_code: candid::Nat },
   TemporarilyUnavailable,
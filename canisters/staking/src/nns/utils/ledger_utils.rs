@@ -1,10 +1,10 @@
 #![allow(deprecated)]
 use crate::nns::utils::ledger_canister::Service as LedgerService;
-use candid::{CandidType, Deserialize, Func, Principal};
+use candid::{CandidType, Deserialize, Func};
 use ic_ledger_types::{ArchivedBlockRange, Block, BlockRange, GetBlocksArgs, GetBlocksResult, Operation}; // removed GetBlocksResult
-use types::E8S;
+use types::{sys::ExteralCanisterLabels, E8S};
 
-const ICP_LEDGER_CANISTER_ID: &str = "ryjl3-tyaaa-aaaaa-aaaba-cai";
+use crate::system_configs::get_exteral_canister_id;
 
 #[derive(CandidType, Deserialize, Debug, Clone)]
 pub struct TransactionInfo {
@@ -18,8 +18,7 @@ pub struct TransactionInfo {
 }
 
 pub async fn query_transaction_by_block_height(block_height: u64) -> Result<TransactionInfo, String> {
-  let ledger_principal = Principal::from_text(ICP_LEDGER_CANISTER_ID).map_err(|e| format!("Invalid ledger canister ID: {}", e))?;
-  let service = LedgerService(ledger_principal);
+  let service = LedgerService(get_exteral_canister_id(ExteralCanisterLabels::IcpLedger));
   let args = GetBlocksArgs {
     start: block_height,
     length: 1,
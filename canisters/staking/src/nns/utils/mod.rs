@@ -2,3 +2,13 @@ pub mod ledger_canister;
 pub mod ledger_utils;
 pub mod nns_query;
 pub mod nns_update;
+
+use nns_governance_api::nns_governance_api::Service;
+use types::sys::ExteralCanisterLabels;
+
+use crate::system_configs::get_exteral_canister_id;
+
+/// Get the NNS governance canister configured in the system config
+pub fn get_governance() -> Service {
+  nns_governance_api::get_governance(get_exteral_canister_id(ExteralCanisterLabels::NnsGovernance))
+}
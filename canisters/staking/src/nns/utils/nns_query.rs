@@ -1,7 +1,18 @@
-use nns_governance_api::{get_governance, nns_governance_api::Neuron};
-use types::staking::StakingPoolId;
+use nns_governance_api::nns_governance_api::Neuron;
+use types::{staking::StakingPoolId, TimestampNanos};
 
-use crate::nns::{NNS_NEURON_MAP, NNS_STAKING_POOL_NEURON_ID_MAP};
+use crate::{
+  nns::{
+    stable_structures::{NnsNeuronCache, NnsNeuronSnapshot, NnsNeuronSnapshotKey, NnsPoolNeuron},
+    utils::get_governance,
+    NNS_NEURON_CACHE_MAP, NNS_NEURON_SNAPSHOT_MAP, NNS_STAKING_POOL_NEURON_ID_MAP,
+  },
+  on_chain::address::generate_staking_pool_neuron_nonce,
+  pool::crud_utils::query_staking_pool_by_id,
+};
+
+/// Maximum number of snapshots kept per neuron (90 days of hourly syncs)
+const MAX_NEURON_SNAPSHOTS_PER_NEURON: usize = 24 * 90;
 
 pub async fn query_nns_neuron_by_pool_id(neuron_id: u64) -> Result<Neuron, String> {
   let governance = get_governance();
@@ -24,14 +35,124 @@ pub fn get_neuron_id_by_pool_id(pool_id: StakingPoolId) -> Option<u64> {
   })
 }
 
+/// Sync all active neurons of the staking pool to the local cache
 pub async fn sync_nns_neuron(pool_id: StakingPoolId) -> Result<(), String> {
-  let neuron_id = get_neuron_id_by_pool_id(pool_id).ok_or_else(|| format!("No NNS neuron found for pool ID: {}", pool_id))?;
+  let pool_neurons: Vec<NnsPoolNeuron> = NnsPoolNeuron::query_by_pool(pool_id).into_iter().filter(|n| n.is_active()).collect();
+
+  if pool_neurons.is_empty() {
+    return Err(format!("No NNS neuron found for pool ID: {}", pool_id));
+  }
+
+  for pool_neuron in pool_neurons {
+    sync_nns_neuron_by_id(pool_id, pool_neuron.get_neuron_id()).await?;
+  }
+
+  Ok(())
+}
+
+/// Sync a neuron of the staking pool to the local cache and append a snapshot to the pool's series
+pub async fn sync_nns_neuron_by_id(pool_id: StakingPoolId, neuron_id: u64) -> Result<(), String> {
   let neuron = query_nns_neuron_by_pool_id(neuron_id).await?;
+  let synced_at = ic_cdk::api::time();
+
+  save_nns_neuron_snapshot(NnsNeuronSnapshot::from_neuron(pool_id, &neuron, synced_at));
 
-  NNS_NEURON_MAP.with(|map| {
+  NNS_NEURON_CACHE_MAP.with(|map| {
     let mut map = map.borrow_mut();
-    map.insert(pool_id, neuron);
+    map.insert(neuron_id, NnsNeuronCache::new(pool_id, neuron, synced_at));
 
     Ok(())
   })
 }
+
+/// Get the last synced NNS neuron from stable memory
+pub fn get_cached_nns_neuron_by_id(neuron_id: u64) -> Option<Neuron> {
+  NNS_NEURON_CACHE_MAP.with(|map| map.borrow().get(&neuron_id).and_then(|cache| cache.get_neuron()))
+}
+
+/// Get the last synced first neuron of the pool's ladder from stable memory
+pub fn get_cached_nns_neuron(pool_id: StakingPoolId) -> Option<Neuron> {
+  get_neuron_id_by_pool_id(pool_id).and_then(get_cached_nns_neuron_by_id)
+}
+
+/// Get the ID of the first neuron of the pool's ladder, used by the endpoints that address a neuron by pool
+pub fn get_pool_primary_neuron_id(pool_id: StakingPoolId) -> Result<u64, String> {
+  get_neuron_id_by_pool_id(pool_id).ok_or_else(|| format!("No NNS neuron found for pool ID: {}", pool_id))
+}
+
+/// Get an active neuron held by a staking pool
+pub fn get_active_pool_neuron(neuron_id: u64) -> Result<NnsPoolNeuron, String> {
+  let pool_neuron = NnsPoolNeuron::get_with_neuron_id(neuron_id).ok_or_else(|| format!("Neuron {} is not held by any staking pool", neuron_id))?;
+
+  if !pool_neuron.is_active() {
+    return Err(format!("Neuron {} has been disbursed", neuron_id));
+  }
+
+  Ok(pool_neuron)
+}
+
+/// Register the neurons created before pools held a neuron ladder, as the first neuron of the pool's ladder.
+/// Run once by the schema migration.
+pub fn register_legacy_pool_neurons() {
+  let legacy_neurons: Vec<(StakingPoolId, u64)> = NNS_STAKING_POOL_NEURON_ID_MAP.with(|map| map.borrow().iter().collect());
+
+  for (pool_id, neuron_id) in legacy_neurons {
+    if NnsPoolNeuron::get_with_neuron_id(neuron_id).is_some() {
+      continue;
+    }
+
+    let staked_principal = query_staking_pool_by_id(pool_id)
+      .map(|pool| pool.get_nns_neuron_occupies_funds())
+      .unwrap_or_default();
+
+    let mut pool_neuron = NnsPoolNeuron::new(neuron_id, pool_id, generate_staking_pool_neuron_nonce(pool_id, 0), 0, None);
+    pool_neuron.add_staked_principal(staked_principal);
+
+    ic_cdk::println!("Registered legacy NNS neuron {} of pool {}", neuron_id, pool_id);
+  }
+}
+
+/// Append a snapshot to the neuron's series, dropping the oldest ones once the series is full
+pub fn save_nns_neuron_snapshot(snapshot: NnsNeuronSnapshot) {
+  let neuron_id = snapshot.get_neuron_id();
+
+  NNS_NEURON_SNAPSHOT_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    map.insert(NnsNeuronSnapshotKey(neuron_id, snapshot.get_synced_at()), snapshot);
+
+    let neuron_range = NnsNeuronSnapshotKey(neuron_id, 0)..=NnsNeuronSnapshotKey(neuron_id, u64::MAX);
+    let count = map.range(neuron_range.clone()).count();
+
+    if count > MAX_NEURON_SNAPSHOTS_PER_NEURON {
+      let expired_keys: Vec<NnsNeuronSnapshotKey> = map
+        .range(neuron_range)
+        .take(count - MAX_NEURON_SNAPSHOTS_PER_NEURON)
+        .map(|(key, _)| key)
+        .collect();
+
+      for key in expired_keys {
+        map.remove(&key);
+      }
+    }
+  });
+}
+
+/// Query the snapshots of the pool's neurons within the time range, ordered by sync time
+pub fn query_nns_neuron_snapshots(pool_id: StakingPoolId, start_time: TimestampNanos, end_time: TimestampNanos) -> Vec<NnsNeuronSnapshot> {
+  let mut snapshots: Vec<NnsNeuronSnapshot> = NNS_NEURON_SNAPSHOT_MAP.with(|map| {
+    let map = map.borrow();
+    NnsPoolNeuron::query_by_pool(pool_id)
+      .iter()
+      .flat_map(|pool_neuron| {
+        let neuron_id = pool_neuron.get_neuron_id();
+        map
+          .range(NnsNeuronSnapshotKey(neuron_id, start_time)..=NnsNeuronSnapshotKey(neuron_id, end_time))
+          .map(|(_, snapshot)| snapshot)
+          .collect::<Vec<_>>()
+      })
+      .collect()
+  });
+
+  snapshots.sort_by_key(|snapshot| (snapshot.get_synced_at(), snapshot.get_neuron_id()));
+  snapshots
+}
@@ -1,19 +1,49 @@
 use candid::Principal;
-use nns_governance_api::{
-  get_governance,
-  nns_governance_api::{
-    AccountIdentifier, AddHotKey, By, ClaimOrRefresh, ClaimOrRefreshNeuronFromAccount, Command1, Configure, Disburse, DisburseToNeuron,
-    IncreaseDissolveDelay, ManageNeuronCommandRequest, ManageNeuronRequest, NeuronId, NeuronIdOrSubaccount, Operation, RemoveHotKey,
-  },
+use ic_ledger_types::BlockIndex;
+use nns_governance_api::nns_governance_api::{
+  Account, AccountIdentifier, AddHotKey, By, ClaimOrRefresh, ClaimOrRefreshNeuronFromAccount, Command1, Configure, Disburse, DisburseMaturity,
+  DisburseToNeuron, DissolveState, Follow, IncreaseDissolveDelay, ManageNeuronCommandRequest, ManageNeuronRequest, NeuronId, NeuronIdOrSubaccount,
+  Operation, RemoveHotKey, StakeMaturity, StakeMaturityResponse,
 };
-use types::{staking::StakingPoolId, E8S};
+use types::{staking::StakingPoolId, TimestampNanos, E8S};
 
 use crate::{
-  nns::utils::ledger_utils::query_transaction_by_block_height,
-  on_chain::address::{generate_staking_pool_account_identifier, generate_staking_pool_neuron_account},
+  guard_keys::get_nns_disburse_guard_key,
+  nns::{
+    stable_structures::{NnsFolloweesConfig, NnsPendingDisburseSettlement, NnsPoolNeuron, NnsStakeExecuteRecord},
+    utils::{
+      get_governance,
+      ledger_utils::query_transaction_by_block_height,
+      nns_query::{get_cached_nns_neuron_by_id, query_nns_neuron_by_pool_id},
+    },
+    NNS_NEURON_CACHE_MAP, NNS_STAKING_POOL_NEURON_ID_MAP,
+  },
+  on_chain::{
+    address::{generate_neuron_account_with_nonce, generate_staking_pool_account_identifier, generate_staking_pool_subaccount},
+    transfer::{get_pool_ledger, load_pool_ledger},
+  },
+  parallel_guard::EntryGuard,
+  pool::crud_utils::query_staking_pool_by_id,
+  pool_transaction_record::{
+    stable_structures::FeeBearer,
+    utils::{record_ledger_fee, record_nns_unstake_transaction},
+  },
 };
 
-pub async fn refresh_nns_neuron_by_pool(pool_id: StakingPoolId) -> Result<u64, String> {
+/// Claim or refresh the active neurons of the pool and re-apply the ladder dissolve delays to the neurons that are not dissolving,
+/// so that a neuron that missed its dissolve delay when it was created catches up
+pub async fn refresh_nns_neuron_by_pool(pool_id: StakingPoolId) -> Result<(), String> {
+  for pool_neuron in NnsPoolNeuron::query_by_pool(pool_id).into_iter().filter(|n| n.is_active()) {
+    let neuron_id = refresh_nns_neuron_by_nonce(pool_neuron.get_nonce()).await?;
+    if pool_neuron.dissolve_delay_seconds.is_some() {
+      set_dissolve_delay_to(neuron_id, pool_neuron.get_dissolve_delay_seconds()).await?;
+    }
+  }
+  Ok(())
+}
+
+/// Claim or refresh the neuron staked with the nonce, returns the neuron ID
+pub async fn refresh_nns_neuron_by_nonce(nonce: u64) -> Result<u64, String> {
   let governance = get_governance();
 
   let (resp,) = governance
@@ -22,11 +52,11 @@ pub async fn refresh_nns_neuron_by_pool(pool_id: StakingPoolId) -> Result<u64, S
       command: Some(ManageNeuronCommandRequest::ClaimOrRefresh(ClaimOrRefresh {
         by: Some(By::MemoAndController(ClaimOrRefreshNeuronFromAccount {
           controller: Some(ic_cdk::api::canister_self()),
-          memo: pool_id,
+          memo: nonce,
         })),
       })),
       neuron_id_or_subaccount: Some(NeuronIdOrSubaccount::Subaccount(serde_bytes::ByteBuf::from(
-        generate_staking_pool_neuron_account(pool_id).as_bytes(),
+        generate_neuron_account_with_nonce(nonce).as_bytes(),
       ))),
     })
     .await
@@ -39,7 +69,7 @@ pub async fn refresh_nns_neuron_by_pool(pool_id: StakingPoolId) -> Result<u64, S
 
   match resp.command {
     Some(Command1::ClaimOrRefresh(resp)) => {
-      ic_cdk::println!("Successfully created NNS neuron by pool: {:?}", pool_id);
+      ic_cdk::println!("Successfully created NNS neuron by nonce: {:?}", nonce);
 
       match resp.refreshed_neuron_id {
         Some(neuron_id) => {
@@ -53,11 +83,11 @@ pub async fn refresh_nns_neuron_by_pool(pool_id: StakingPoolId) -> Result<u64, S
       }
     }
     None => {
-      ic_cdk::println!("Failed to create NNS neuron none by pool: {}", pool_id);
+      ic_cdk::println!("Failed to create NNS neuron none by nonce: {}", nonce);
       Err("Failed to create NNS neuron none".to_string())
     }
     _ => {
-      ic_cdk::println!("Failed to create NNS neuron by pool: {}", pool_id);
+      ic_cdk::println!("Failed to create NNS neuron by nonce: {}", nonce);
       Err("Failed to create NNS neuron".to_string())
     }
   }
@@ -321,12 +351,11 @@ pub async fn nns_disburse(neuron_id: u64, pool_id: StakingPoolId) -> Result<(),
 
       ic_cdk::println!("Successfully disbursed for neuron {} with tx_id {}", neuron_id, tx_id);
 
-      let tx_info = query_transaction_by_block_height(tx_id).await?;
+      // Persisted before the ledger lookup so that a failed settlement is retried by the NNS sync task
+      let settlement = NnsPendingDisburseSettlement::new(pool_id, neuron_id, tx_id, ic_cdk::api::time());
+      settlement.update_to_stable();
 
-      // Record the unstake transaction of the NNS neuron
-      crate::pool_transaction_record::utils::record_nns_unstake_transaction(pool_id, neuron_id, tx_info.amount, tx_id, tx_info.timestamp)?;
-
-      Ok(())
+      settle_pending_nns_disburse(settlement).await
     }
     _ => {
       ic_cdk::println!("Failed to disburse for neuron: {:?}", neuron_id);
@@ -334,3 +363,297 @@ pub async fn nns_disburse(neuron_id: u64, pool_id: StakingPoolId) -> Result<(),
     }
   }
 }
+
+/// Disburse a percentage of the neuron's maturity to the staking pool account.
+/// The NNS mints the disbursed ICP after its disbursement delay, the returned amount is the amount scheduled.
+pub async fn disburse_maturity(neuron_id: u64, pool_id: StakingPoolId, percentage_to_disburse: u32) -> Result<E8S, String> {
+  let governance = get_governance();
+
+  let (resp,) = governance
+    .manage_neuron(ManageNeuronRequest {
+      id: Some(NeuronId { id: neuron_id }),
+      command: Some(ManageNeuronCommandRequest::DisburseMaturity(DisburseMaturity {
+        to_account: Some(Account {
+          owner: Some(ic_cdk::api::canister_self()),
+          subaccount: Some(serde_bytes::ByteBuf::from(generate_staking_pool_subaccount(pool_id).0.to_vec())),
+        }),
+        percentage_to_disburse,
+      })),
+      neuron_id_or_subaccount: None,
+    })
+    .await
+    .map_err(|e| {
+      ic_cdk::println!("Failed to disburse maturity for neuron: {:?}", e);
+      "Failed to disburse maturity for neuron".to_string()
+    })?;
+
+  if resp.command.is_none() {
+    ic_cdk::println!("Failed to disburse maturity for neuron: No command returned");
+    return Err("Failed to disburse maturity for neuron: No command returned".to_string());
+  }
+
+  match resp.command.unwrap() {
+    Command1::DisburseMaturity(disburse_resp) => {
+      let amount = disburse_resp.amount_disbursed_e8s.unwrap_or_default();
+      ic_cdk::println!("Successfully disbursed maturity {} for neuron {}", amount, neuron_id);
+      Ok(amount)
+    }
+    Command1::Error(err) => {
+      ic_cdk::println!("Failed to disburse maturity for neuron: {:?}", err);
+      Err(format!("Failed to disburse maturity for neuron: {}", err.error_message))
+    }
+    _ => {
+      ic_cdk::println!("Failed to disburse maturity for neuron: {:?}", neuron_id);
+      Err("Failed to disburse maturity for neuron".to_string())
+    }
+  }
+}
+
+/// Stake a percentage of the neuron's maturity, the staked maturity stays in the neuron and adds to its voting power
+pub async fn stake_maturity(neuron_id: u64, percentage_to_stake: u32) -> Result<StakeMaturityResponse, String> {
+  let governance = get_governance();
+
+  let (resp,) = governance
+    .manage_neuron(ManageNeuronRequest {
+      id: Some(NeuronId { id: neuron_id }),
+      command: Some(ManageNeuronCommandRequest::StakeMaturity(StakeMaturity {
+        percentage_to_stake: Some(percentage_to_stake),
+      })),
+      neuron_id_or_subaccount: None,
+    })
+    .await
+    .map_err(|e| {
+      ic_cdk::println!("Failed to stake maturity for neuron: {:?}", e);
+      "Failed to stake maturity for neuron".to_string()
+    })?;
+
+  if resp.command.is_none() {
+    ic_cdk::println!("Failed to stake maturity for neuron: No command returned");
+    return Err("Failed to stake maturity for neuron: No command returned".to_string());
+  }
+
+  match resp.command.unwrap() {
+    Command1::StakeMaturity(stake_resp) => {
+      ic_cdk::println!(
+        "Successfully staked maturity for neuron {}, staked maturity: {}",
+        neuron_id,
+        stake_resp.staked_maturity_e8s
+      );
+      Ok(stake_resp)
+    }
+    Command1::Error(err) => {
+      ic_cdk::println!("Failed to stake maturity for neuron: {:?}", err);
+      Err(format!("Failed to stake maturity for neuron: {}", err.error_message))
+    }
+    _ => {
+      ic_cdk::println!("Failed to stake maturity for neuron: {:?}", neuron_id);
+      Err("Failed to stake maturity for neuron".to_string())
+    }
+  }
+}
+
+/// Look up the disburse transfer and settle it, the settlement stays pending with the error when this fails
+async fn settle_pending_nns_disburse(mut settlement: NnsPendingDisburseSettlement) -> Result<(), String> {
+  let block_index = settlement.get_block_index();
+  let result = async {
+    let tx_info = query_transaction_by_block_height(block_index).await?;
+    // The disburse fee is taken from the ledger, make sure it is fetched before settling
+    load_pool_ledger(settlement.get_pool_id()).await?;
+    settle_nns_disburse(settlement.get_pool_id(), settlement.get_neuron_id(), tx_info.amount, block_index, tx_info.timestamp)
+  }
+  .await;
+
+  match result {
+    Ok(()) => {
+      settlement.remove_from_stable();
+      Ok(())
+    }
+    Err(e) => {
+      settlement.update_to_failed(e.clone());
+      Err(e)
+    }
+  }
+}
+
+/// Retry the settlement of the neuron disburses that failed after the NNS disbursed the neuron
+pub async fn retry_pending_nns_disburse_settlements() {
+  for settlement in NnsPendingDisburseSettlement::query_all() {
+    let (pool_id, neuron_id) = (settlement.get_pool_id(), settlement.get_neuron_id());
+    // A disburse of the pool in flight settles its own transfer
+    let Ok(_entry_guard) = EntryGuard::new(get_nns_disburse_guard_key(pool_id)) else {
+      continue;
+    };
+    match settle_pending_nns_disburse(settlement).await {
+      Ok(()) => ic_cdk::println!("Settled the disburse of neuron {} for pool {}", neuron_id, pool_id),
+      Err(e) => ic_cdk::println!("Failed to settle the disburse of neuron {} for pool {}: {}", neuron_id, pool_id, e),
+    }
+  }
+}
+
+/// Settle the staking pool after its neuron has been disbursed.
+/// The neuron stake covers the disburse fee, so the principal returned is the received amount plus the fee,
+/// capped by the principal the pool staked into the neuron. Anything beyond that is the staked maturity, booked as NNS yield.
+fn settle_nns_disburse(
+  pool_id: StakingPoolId,
+  neuron_id: u64,
+  received_amount: E8S,
+  block_index: BlockIndex,
+  disburse_time: TimestampNanos,
+) -> Result<(), String> {
+  let pool = query_staking_pool_by_id(pool_id)?;
+  let pool_neuron = NnsPoolNeuron::get_with_neuron_id(neuron_id);
+
+  let disburse_fee = get_pool_ledger(pool_id)?.get_fee();
+  let returned_amount = received_amount + disburse_fee;
+  let neuron_principal = pool_neuron
+    .as_ref()
+    .map_or(returned_amount, |pool_neuron| pool_neuron.get_staked_principal());
+  let principal_amount = pool.release_nns_neuron_occupies_funds(returned_amount.min(neuron_principal))?;
+  let yield_amount = returned_amount - principal_amount;
+
+  ic_cdk::println!(
+    "Settle NNS disburse of neuron {} for pool {}: principal {}, yield {}",
+    neuron_id,
+    pool_id,
+    principal_amount,
+    yield_amount
+  );
+
+  // The disburse fee is charged to the neuron stake, which is the pool's funds
+  record_ledger_fee(pool_id, block_index, disburse_fee, FeeBearer::Pool, format!("nns_disburse_{}", neuron_id));
+  record_nns_unstake_transaction(pool_id, neuron_id, principal_amount, yield_amount, block_index, disburse_time)?;
+
+  for mut execute_record in NnsStakeExecuteRecord::query_success_with_neuron(pool_id, neuron_id) {
+    execute_record.update_to_disbursed(block_index);
+  }
+
+  if let Some(mut pool_neuron) = pool_neuron {
+    pool_neuron.update_to_disbursed(block_index);
+  }
+
+  NNS_STAKING_POOL_NEURON_ID_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    if map.get(&pool_id) == Some(neuron_id) {
+      map.remove(&pool_id);
+    }
+  });
+  NNS_NEURON_CACHE_MAP.with(|map| map.borrow_mut().remove(&neuron_id));
+
+  Ok(())
+}
+
+/// Raise the dissolve delay of the neuron to the target, a neuron already at or above the target is left untouched.
+/// A dissolving neuron is left untouched too, increasing its delay would undo the dissolve an admin started.
+pub async fn set_dissolve_delay_to(neuron_id: u64, target_delay_seconds: u64) -> Result<(), String> {
+  let neuron = query_nns_neuron_by_pool_id(neuron_id).await?;
+
+  let current_delay_seconds = match neuron.dissolve_state {
+    Some(DissolveState::DissolveDelaySeconds(delay)) => delay,
+    Some(DissolveState::WhenDissolvedTimestampSeconds(_)) => {
+      ic_cdk::println!("NNS neuron {} is dissolving, its dissolve delay is left as it is", neuron_id);
+      return Ok(());
+    }
+    None => 0,
+  };
+
+  if current_delay_seconds >= target_delay_seconds {
+    return Ok(());
+  }
+
+  let additional_delay_seconds = u32::try_from(target_delay_seconds - current_delay_seconds).map_err(|_| "Dissolve delay is too long".to_string())?;
+
+  increase_dissolve_delay(neuron_id, additional_delay_seconds).await
+}
+
+/// Set the followees of the neuron for the topic, an empty list clears the following of the topic
+pub async fn set_following(neuron_id: u64, topic: i32, followees: Vec<u64>) -> Result<(), String> {
+  let governance = get_governance();
+
+  let (resp,) = governance
+    .manage_neuron(ManageNeuronRequest {
+      id: Some(NeuronId { id: neuron_id }),
+      command: Some(ManageNeuronCommandRequest::Follow(Follow {
+        topic,
+        followees: followees.into_iter().map(|id| NeuronId { id }).collect(),
+      })),
+      neuron_id_or_subaccount: None,
+    })
+    .await
+    .map_err(|e| {
+      ic_cdk::println!("Failed to set following for neuron: {:?}", e);
+      "Failed to set following for neuron".to_string()
+    })?;
+
+  if resp.command.is_none() {
+    ic_cdk::println!("Failed to set following for neuron: No command returned");
+    return Err("Failed to set following for neuron: No command returned".to_string());
+  }
+
+  match resp.command.unwrap() {
+    Command1::Follow {} => {
+      ic_cdk::println!("Successfully set following of topic {} for neuron {}", topic, neuron_id);
+      Ok(())
+    }
+    Command1::Error(err) => {
+      ic_cdk::println!("Failed to set following for neuron: {:?}", err);
+      Err(format!("Failed to set following for neuron: {}", err.error_message))
+    }
+    _ => {
+      ic_cdk::println!("Failed to set following for neuron: {:?}", neuron_id);
+      Err("Failed to set following for neuron".to_string())
+    }
+  }
+}
+
+/// Apply the stored followee configuration of the pool to the neuron
+pub async fn apply_pool_followees(pool_id: StakingPoolId, neuron_id: u64) -> Result<(), String> {
+  let config = NnsFolloweesConfig::get_with_pool(pool_id);
+
+  for (topic, followees) in config.get_followees() {
+    set_following(neuron_id, topic, followees).await?;
+  }
+
+  Ok(())
+}
+
+/// Re-apply the pool's followees to the active neurons whose last synced following differs from the configuration,
+/// so that a neuron that missed them when it was created or when they were set catches up. Called by the NNS sync task.
+pub async fn apply_missing_pool_followees(pool_id: StakingPoolId) {
+  let config = NnsFolloweesConfig::get_with_pool(pool_id).get_followees();
+  if config.is_empty() {
+    return;
+  }
+
+  for pool_neuron in NnsPoolNeuron::query_by_pool(pool_id).into_iter().filter(|n| n.is_active()) {
+    let neuron_id = pool_neuron.get_neuron_id();
+    let Some(neuron) = get_cached_nns_neuron_by_id(neuron_id) else {
+      continue;
+    };
+
+    for (topic, followees) in &config {
+      let mut expected = followees.clone();
+      expected.sort_unstable();
+      let mut current: Vec<u64> = neuron
+        .followees
+        .iter()
+        .find(|(neuron_topic, _)| neuron_topic == topic)
+        .map(|(_, neuron_followees)| neuron_followees.followees.iter().map(|followee| followee.id).collect())
+        .unwrap_or_default();
+      current.sort_unstable();
+
+      if current == expected {
+        continue;
+      }
+
+      if let Err(e) = set_following(neuron_id, *topic, followees.clone()).await {
+        ic_cdk::println!(
+          "Failed to re-apply the followees of topic {} to NNS neuron {} of pool {}: {}",
+          topic,
+          neuron_id,
+          pool_id,
+          e
+        );
+      }
+    }
+  }
+}
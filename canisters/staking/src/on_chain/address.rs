@@ -1,11 +1,16 @@
 use candid::Principal;
-use ic_ledger_types::{AccountIdentifier, Subaccount, MAINNET_GOVERNANCE_CANISTER_ID};
+use ic_ledger_types::{AccountIdentifier, Subaccount};
 use sha2::{Digest, Sha256};
 use types::{
   staking::{StakingAccountId, StakingPoolId},
+  sys::ExteralCanisterLabels,
   EntityId,
 };
 
+use crate::system_configs::get_exteral_canister_id;
+
+use super::{stable_structures::StakingAddressOwner, STAKING_ADDRESS_INDEX_MAP};
+
 const STAKING_POOL_ADDRESS_PREFIX: &str = "staking_pool_";
 
 const STAKING_ACCOUNT_ADDRESS_PREFIX: &str = "staking_account_";
@@ -36,6 +41,38 @@ pub fn generate_staking_account_chain_address(account_id: StakingAccountId) -> S
   generate_address(canister_id, &sub_account_id)
 }
 
+/// Staking pool address in the ICRC-1 account text encoding
+pub fn generate_staking_pool_icrc1_address(pool_id: StakingPoolId) -> String {
+  encode_icrc1_account(ic_cdk::api::canister_self(), &generate_staking_pool_subaccount(pool_id))
+}
+
+/// Staking account address in the ICRC-1 account text encoding
+pub fn generate_staking_account_icrc1_address(account_id: StakingAccountId) -> String {
+  encode_icrc1_account(ic_cdk::api::canister_self(), &generate_staking_account_subaccount(account_id))
+}
+
+/// Index the on-chain address of a staking pool, so the address can be resolved back to the pool
+pub fn index_staking_pool_address(pool_id: StakingPoolId) {
+  let account_identifier = generate_staking_pool_account_identifier(pool_id).to_hex();
+  STAKING_ADDRESS_INDEX_MAP.with(|map| map.borrow_mut().insert(account_identifier, StakingAddressOwner::StakingPool(pool_id)));
+}
+
+/// Index the on-chain address of a staking account, so the address can be resolved back to the account
+pub fn index_staking_account_address(account_id: StakingAccountId) {
+  let account_identifier = generate_staking_account_account_identifier(account_id).to_hex();
+  STAKING_ADDRESS_INDEX_MAP.with(|map| {
+    map
+      .borrow_mut()
+      .insert(account_identifier, StakingAddressOwner::StakingAccount(account_id))
+  });
+}
+
+/// Remove the on-chain address of a deleted staking account from the index
+pub fn remove_staking_account_address(account_id: StakingAccountId) {
+  let account_identifier = generate_staking_account_account_identifier(account_id).to_hex();
+  STAKING_ADDRESS_INDEX_MAP.with(|map| map.borrow_mut().remove(&account_identifier));
+}
+
 pub fn generate_staking_account_account_identifier(account_id: StakingAccountId) -> AccountIdentifier {
   let canister_id = ic_cdk::api::canister_self();
   let sub_account_id = format!("{}{}", STAKING_ACCOUNT_ADDRESS_PREFIX, account_id);
@@ -78,9 +115,31 @@ fn compute_neuron_staking_subaccount_bytes(controller: Principal, nonce: u64) ->
 
 /// Generate a nns neuron account identifier for staking pool
 pub fn generate_staking_pool_neuron_account(pool_id: StakingPoolId) -> AccountIdentifier {
+  generate_neuron_account_with_nonce(generate_staking_pool_neuron_nonce(pool_id, 0))
+}
+
+/// Generate the nonce of a neuron in the staking pool's neuron ladder.
+/// The first neuron of the ladder keeps the pool ID as its nonce, so neurons created before the ladder keep their account.
+/// The other rungs hash (pool ID, ladder index) and set the top bit, which keeps them apart from the pool ID nonces.
+pub fn generate_staking_pool_neuron_nonce(pool_id: StakingPoolId, ladder_index: u32) -> u64 {
+  if ladder_index == 0 {
+    return pool_id;
+  }
+
+  let mut hasher = Sha256::new();
+  hasher.update(b"staking-pool-neuron");
+  hasher.update(pool_id.to_be_bytes());
+  hasher.update(ladder_index.to_be_bytes());
+  let hash: [u8; 32] = hasher.finalize().into();
+  u64::from_be_bytes(hash[0..8].try_into().unwrap()) | (1 << 63)
+}
+
+/// Generate a nns neuron account identifier for the neuron nonce
+pub fn generate_neuron_account_with_nonce(nonce: u64) -> AccountIdentifier {
   let canister_id = ic_cdk::api::canister_self();
-  let account_buf = compute_neuron_staking_subaccount_bytes(canister_id, pool_id);
-  AccountIdentifier::new(&MAINNET_GOVERNANCE_CANISTER_ID, &Subaccount(account_buf))
+  let account_buf = compute_neuron_staking_subaccount_bytes(canister_id, nonce);
+  let governance_canister_id = get_exteral_canister_id(ExteralCanisterLabels::NnsGovernance);
+  AccountIdentifier::new(&governance_canister_id, &Subaccount(account_buf))
 }
 
 /// Generate a standard ICP Account address
@@ -115,3 +174,129 @@ fn generate_subaccount(subaccount_id: &str) -> Subaccount {
   // Create a sub-account object
   Subaccount(subaccount)
 }
+
+/// Encode an ICRC-1 account as text.
+/// The default subaccount is encoded as the owner principal alone, any other subaccount as
+/// `<owner>-<checksum>.<subaccount hex without leading zeros>`, where the checksum is the base32 CRC-32 of the owner and subaccount bytes.
+pub fn encode_icrc1_account(owner: Principal, subaccount: &Subaccount) -> String {
+  if subaccount.0 == [0; 32] {
+    return owner.to_text();
+  }
+
+  let subaccount_hex = subaccount.0.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+
+  format!(
+    "{}-{}.{}",
+    owner.to_text(),
+    icrc1_account_checksum(owner, subaccount),
+    subaccount_hex.trim_start_matches('0')
+  )
+}
+
+/// Decode an ICRC-1 account from its text encoding
+pub fn decode_icrc1_account(text: &str) -> Result<(Principal, Subaccount), String> {
+  let Some((owner_and_checksum, subaccount_hex)) = text.rsplit_once('.') else {
+    let owner = Principal::from_text(text).map_err(|e| format!("Invalid account owner: {}", e))?;
+    return Ok((owner, Subaccount([0; 32])));
+  };
+
+  let (owner_text, checksum) = owner_and_checksum
+    .rsplit_once('-')
+    .ok_or_else(|| "Invalid ICRC-1 account: missing checksum".to_string())?;
+
+  if subaccount_hex.is_empty() || subaccount_hex.len() > 64 || subaccount_hex.starts_with('0') {
+    return Err("Invalid ICRC-1 account: the subaccount must be non-zero hex without leading zeros".to_string());
+  }
+  // The subaccount is padded by characters and sliced by bytes, which only line up for ASCII hex
+  if !subaccount_hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+    return Err("Invalid ICRC-1 account: the subaccount is not hex".to_string());
+  }
+
+  let owner = Principal::from_text(owner_text).map_err(|e| format!("Invalid account owner: {}", e))?;
+
+  let padded_hex = format!("{:0>64}", subaccount_hex);
+  let mut subaccount = [0; 32];
+  for (i, byte) in subaccount.iter_mut().enumerate() {
+    *byte = u8::from_str_radix(&padded_hex[i * 2..i * 2 + 2], 16).map_err(|_| "Invalid ICRC-1 account: the subaccount is not hex".to_string())?;
+  }
+  let subaccount = Subaccount(subaccount);
+
+  if checksum != icrc1_account_checksum(owner, &subaccount) {
+    return Err("Invalid ICRC-1 account: checksum mismatch".to_string());
+  }
+
+  Ok((owner, subaccount))
+}
+
+fn icrc1_account_checksum(owner: Principal, subaccount: &Subaccount) -> String {
+  let mut hasher = crc32fast::Hasher::new();
+  hasher.update(owner.as_slice());
+  hasher.update(&subaccount.0);
+  data_encoding::BASE32_NOPAD.encode(&hasher.finalize().to_be_bytes()).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const OWNER: &str = "k2t6j-2nvnp-4zjm3-25dtz-6xhaa-c7boj-5gayf-oj3xs-i43lp-teztq-6ae";
+
+  fn owner() -> Principal {
+    Principal::from_text(OWNER).unwrap()
+  }
+
+  fn subaccount_one() -> Subaccount {
+    let mut subaccount = [0; 32];
+    subaccount[31] = 1;
+    Subaccount(subaccount)
+  }
+
+  fn subaccount_sequence() -> Subaccount {
+    let mut subaccount = [0; 32];
+    for (i, byte) in subaccount.iter_mut().enumerate() {
+      *byte = i as u8 + 1;
+    }
+    Subaccount(subaccount)
+  }
+
+  #[test]
+  fn encodes_the_default_subaccount_as_the_owner() {
+    assert_eq!(encode_icrc1_account(owner(), &Subaccount([0; 32])), OWNER);
+    assert_eq!(decode_icrc1_account(OWNER), Ok((owner(), Subaccount([0; 32]))));
+  }
+
+  #[test]
+  fn encodes_the_known_vectors() {
+    let one = format!("{}-6cc627i.1", OWNER);
+    let sequence = format!("{}-dfxgiyy.102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20", OWNER);
+
+    assert_eq!(encode_icrc1_account(owner(), &subaccount_one()), one);
+    assert_eq!(decode_icrc1_account(&one), Ok((owner(), subaccount_one())));
+    assert_eq!(encode_icrc1_account(owner(), &subaccount_sequence()), sequence);
+    assert_eq!(decode_icrc1_account(&sequence), Ok((owner(), subaccount_sequence())));
+  }
+
+  #[test]
+  fn round_trips_the_staking_subaccounts() {
+    for subaccount in [
+      generate_staking_pool_subaccount(1),
+      generate_staking_account_subaccount(42),
+      subaccount_one(),
+    ] {
+      let text = encode_icrc1_account(owner(), &subaccount);
+      assert_eq!(decode_icrc1_account(&text), Ok((owner(), subaccount)));
+    }
+  }
+
+  #[test]
+  fn rejects_a_bad_checksum() {
+    assert!(decode_icrc1_account(&format!("{}-7cc627i.1", OWNER)).is_err());
+    assert!(decode_icrc1_account(&format!("{}.1", OWNER)).is_err());
+  }
+
+  #[test]
+  fn rejects_leading_zeros() {
+    assert!(decode_icrc1_account(&format!("{}-6cc627i.01", OWNER)).is_err());
+    assert!(decode_icrc1_account(&format!("{}-6cc627i.0", OWNER)).is_err());
+  }
+}
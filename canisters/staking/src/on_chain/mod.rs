@@ -1,3 +1,138 @@
+use std::{cell::RefCell, collections::HashMap, str::FromStr};
+
+use candid::Principal;
+
+use address::{decode_icrc1_account, generate_staking_account_icrc1_address, generate_staking_pool_icrc1_address};
+use ic_ledger_types::{AccountIdentifier, BlockIndex};
+use ic_stable_structures::{memory_manager::MemoryId, StableBTreeMap};
+use stable_structures::{LedgerConfig, LedgerProtocol, LedgerTransferRecord, StakingAddressOwner};
+use system_configs_macro::{has_permission, has_permission_result};
+use transport_structures::{LedgerConfigDto, LedgerConfigVo, StakingAddressOwnerVo};
+use types::{stable_structures::Memory, Crypto, TimestampNanos, E8S};
+
+use crate::{
+  account::STAKING_ACCOUNT_MAP,
+  memory_ids::{LEDGER_REGISTRY, LEDGER_TRANSFER_RECORD, STAKING_ADDRESS_INDEX},
+  MEMORY_MANAGER,
+};
+
 pub mod address;
 pub mod query;
+pub mod stable_structures;
 pub mod transfer;
+pub mod transport_structures;
+
+thread_local! {
+  /// Outgoing ledger transfers by business operation key, used to make retried transfers idempotent
+  pub static LEDGER_TRANSFER_RECORD_MAP: RefCell<StableBTreeMap<String, LedgerTransferRecord, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(LEDGER_TRANSFER_RECORD))),
+    )
+  );
+
+  /// Token ledger of each crypto
+  pub static LEDGER_REGISTRY_MAP: RefCell<StableBTreeMap<String, LedgerConfig, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(LEDGER_REGISTRY))),
+    )
+  );
+
+  /// Staking pool or staking account of each on-chain address, keyed by the account identifier hex
+  pub static STAKING_ADDRESS_INDEX_MAP: RefCell<StableBTreeMap<String, StakingAddressOwner, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_ADDRESS_INDEX))),
+    )
+  );
+
+  /// Transfer fee fetched from each ledger canister, with the time it was fetched
+  pub static LEDGER_FEE_CACHE: RefCell<HashMap<Principal, (E8S, TimestampNanos)>> = RefCell::new(HashMap::new());
+
+  /// Payment center address on the ICP ledger, with the payment center canister that reported it
+  pub static PAY_CENTER_ADDRESS_CACHE: RefCell<Option<(Principal, AccountIdentifier)>> = const { RefCell::new(None) };
+
+  /// Highest block seen in each ledger canister, a lower bound of the chain length that a transfer lookup starts from
+  pub static LEDGER_KNOWN_BLOCK_CACHE: RefCell<HashMap<Principal, BlockIndex>> = RefCell::new(HashMap::new());
+}
+
+/// Register the token ledger of a crypto, staking pools of the crypto transfer on it
+#[ic_cdk::update]
+#[has_permission_result("staking::ledger::manage", audit)]
+fn set_crypto_ledger(dto: LedgerConfigDto) -> Result<(), String> {
+  let crypto = Crypto::from_str(&dto.crypto).map_err(|_| format!("Invalid crypto: {}", dto.crypto))?;
+  let protocol = LedgerProtocol::from_str(&dto.protocol).map_err(|_| format!("Invalid ledger protocol: {}", dto.protocol))?;
+
+  if crypto == Crypto::ICP && protocol != LedgerProtocol::Legacy {
+    return Err("The ICP ledger must use the legacy protocol, NNS neurons are staked by account identifier".to_string());
+  }
+
+  LedgerConfig::new(crypto, dto.canister_id, dto.fee, dto.decimals, protocol).update_to_stable();
+
+  // The fee of the ledger is fetched again on the next transfer
+  LEDGER_FEE_CACHE.with(|cache| cache.borrow_mut().remove(&dto.canister_id));
+
+  Ok(())
+}
+
+/// Query the registered token ledgers
+#[ic_cdk::query]
+#[has_permission("staking::ledger::query")]
+fn get_crypto_ledgers() -> Vec<LedgerConfigVo> {
+  LedgerConfig::query_all().into_iter().map(LedgerConfigVo::from).collect()
+}
+
+/// Parse an on-chain address of the staking canister into its account identifier.
+/// The address can be an account identifier hex or an ICRC-1 account text.
+pub fn parse_staking_address(address: &str) -> Result<AccountIdentifier, String> {
+  let address = address.trim();
+
+  match AccountIdentifier::from_hex(address) {
+    Ok(account_identifier) => Ok(account_identifier),
+    Err(_) => {
+      let (owner, subaccount) = decode_icrc1_account(address)?;
+      if owner != ic_cdk::api::canister_self() {
+        return Err(format!("The address is not owned by the staking canister: {}", owner));
+      }
+      Ok(AccountIdentifier::new(&owner, &subaccount))
+    }
+  }
+}
+
+/// Resolve an on-chain address back to its owner through the address index.
+/// The address can be an account identifier hex or an ICRC-1 account text.
+pub fn resolve_staking_address_owner(address: &str) -> Result<(AccountIdentifier, StakingAddressOwner), String> {
+  let account_identifier = parse_staking_address(address)?;
+
+  let owner = STAKING_ADDRESS_INDEX_MAP
+    .with(|map| map.borrow().get(&account_identifier.to_hex()))
+    .ok_or_else(|| format!("No staking pool or staking account found for the address: {}", address))?;
+
+  Ok((account_identifier, owner))
+}
+
+/// Resolve an on-chain address back to its staking pool or staking account.
+/// The address can be an account identifier hex or an ICRC-1 account text.
+#[ic_cdk::query]
+fn resolve_staking_address(address: String) -> Result<StakingAddressOwnerVo, String> {
+  let (account_identifier, owner) = resolve_staking_address_owner(&address)?;
+
+  match owner {
+    StakingAddressOwner::StakingPool(pool_id) => Ok(StakingAddressOwnerVo {
+      pool_id,
+      account_id: None,
+      account_identifier: account_identifier.to_hex(),
+      icrc1_account: generate_staking_pool_icrc1_address(pool_id),
+    }),
+    StakingAddressOwner::StakingAccount(account_id) => {
+      let account = STAKING_ACCOUNT_MAP
+        .with(|map| map.borrow().get(&account_id))
+        .ok_or_else(|| format!("Staking account {} not found", account_id))?;
+
+      Ok(StakingAddressOwnerVo {
+        pool_id: account.get_pool_id(),
+        account_id: Some(account_id),
+        account_identifier: account_identifier.to_hex(),
+        icrc1_account: generate_staking_account_icrc1_address(account_id),
+      })
+    }
+  }
+}
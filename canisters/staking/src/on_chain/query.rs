@@ -1,9 +1,390 @@
-use ic_ledger_types::{account_balance, AccountBalanceArgs, AccountIdentifier, MAINNET_LEDGER_CANISTER_ID};
+use candid::{define_function, CandidType, Deserialize, Nat, Principal};
+use ic_ledger_types::{
+  account_balance, query_archived_blocks, query_blocks, AccountBalanceArgs, AccountIdentifier, Block, BlockIndex, BlockRange, GetBlocksArgs, Memo,
+  Operation, Subaccount, DEFAULT_SUBACCOUNT,
+};
+use types::{TimestampNanos, E8S};
 
-/// Fetches the balance of a given account on the mainnet ledger.
-pub async fn balance_of(target: &AccountIdentifier) -> Result<u64, String> {
-  account_balance(MAINNET_LEDGER_CANISTER_ID, &AccountBalanceArgs { account: *target })
+use crate::nns::utils::ledger_canister::{Account, Service as LedgerService, TransferFeeArg};
+
+use super::{
+  stable_structures::{LedgerConfig, LedgerProtocol},
+  LEDGER_FEE_CACHE, LEDGER_KNOWN_BLOCK_CACHE,
+};
+
+/// Number of blocks requested from the ledger per query when searching for a transfer
+const BLOCK_SEARCH_BATCH_SIZE: u64 = 1_000;
+
+/// The ledger takes a transfer until 24 hours after its created_at_time, plus the clock drift it permits
+const LEDGER_TRANSFER_WINDOW_NANOS: TimestampNanos = (24 * 60 + 1) * 60 * 1_000_000_000;
+
+/// How long a transfer fee fetched from a ledger is used before it is fetched again
+const LEDGER_FEE_CACHE_TTL_NANOS: TimestampNanos = 60 * 60 * 1_000_000_000;
+
+/// Fetches the transfer fee of the token ledger
+pub async fn query_ledger_fee(ledger: &LedgerConfig) -> Result<E8S, String> {
+  let ledger_service = LedgerService(ledger.get_canister_id());
+
+  match ledger.get_protocol() {
+    LedgerProtocol::Legacy => {
+      let (fee,) = ledger_service
+        .transfer_fee(TransferFeeArg {})
+        .await
+        .map_err(|e| format!("Failed to get transfer fee: code = {:?}, message = {}", e.0, e.1))?;
+
+      Ok(fee.transfer_fee.e8s)
+    }
+    LedgerProtocol::Icrc1 => {
+      let (fee,) = ledger_service
+        .icrc_1_fee()
+        .await
+        .map_err(|e| format!("Failed to get transfer fee: code = {:?}, message = {}", e.0, e.1))?;
+
+      u64::try_from(fee.0).map_err(|e| format!("Invalid transfer fee: {}", e))
+    }
+  }
+}
+
+/// Fetch the transfer fee of the token ledger again once the cached fee is stale.
+/// When the ledger cannot be reached the cached or registered fee stays in use.
+pub async fn refresh_ledger_fee(ledger: &LedgerConfig) {
+  let canister_id = ledger.get_canister_id();
+  let now = ic_cdk::api::time();
+
+  let is_fresh = LEDGER_FEE_CACHE.with(|cache| {
+    cache
+      .borrow()
+      .get(&canister_id)
+      .is_some_and(|(_, fetched_at)| now.saturating_sub(*fetched_at) < LEDGER_FEE_CACHE_TTL_NANOS)
+  });
+
+  if is_fresh {
+    return;
+  }
+
+  match query_ledger_fee(ledger).await {
+    Ok(fee) => LEDGER_FEE_CACHE.with(|cache| {
+      cache.borrow_mut().insert(canister_id, (fee, now));
+    }),
+    Err(e) => ic_cdk::println!("Failed to refresh the transfer fee of ledger {}: {}", canister_id, e),
+  }
+}
+
+/// Fetches the balance of a given account on the token ledger.
+pub async fn balance_of(ledger: &LedgerConfig, owner: Principal, subaccount: Subaccount) -> Result<u64, String> {
+  match ledger.get_protocol() {
+    LedgerProtocol::Legacy => account_balance(
+      ledger.get_canister_id(),
+      &AccountBalanceArgs {
+        account: AccountIdentifier::new(&owner, &subaccount),
+      },
+    )
     .await
     .map_err(|e| format!("Failed to get balance: {:?}", e))
-    .and_then(|balance| Ok(balance.e8s()))
+    .map(|balance| balance.e8s()),
+    LedgerProtocol::Icrc1 => {
+      let (balance,): (Nat,) = LedgerService(ledger.get_canister_id())
+        .icrc_1_balance_of(Account {
+          owner,
+          subaccount: if subaccount == DEFAULT_SUBACCOUNT {
+            None
+          } else {
+            Some(serde_bytes::ByteBuf::from(subaccount.0.to_vec()))
+          },
+        })
+        .await
+        .map_err(|e| format!("Failed to get balance: code = {:?}, message = {}", e.0, e.1))?;
+
+      u64::try_from(balance.0).map_err(|e| format!("Invalid balance: {}", e))
+    }
+  }
+}
+
+/// Fetches the current chain length of a legacy ledger, that is the index the next block will get
+pub async fn query_chain_length(ledger_canister_id: Principal) -> Result<u64, String> {
+  let chain_length = query_blocks(ledger_canister_id, &GetBlocksArgs { start: 0, length: 0 })
+    .await
+    .map(|resp| resp.chain_length)
+    .map_err(|e| format!("Failed to query ledger chain length: {:?}", e))?;
+
+  note_ledger_block(ledger_canister_id, chain_length);
+  Ok(chain_length)
+}
+
+/// Remember a block index seen in the ledger, blocks only grow so the highest one is kept
+pub fn note_ledger_block(ledger_canister_id: Principal, block_index: BlockIndex) {
+  LEDGER_KNOWN_BLOCK_CACHE.with(|cache| {
+    let mut cache = cache.borrow_mut();
+    let known_block = cache.entry(ledger_canister_id).or_default();
+    *known_block = (*known_block).max(block_index);
+  });
+}
+
+/// Highest block seen in the ledger, the chain length is fetched once when no block was seen since the last upgrade
+pub async fn get_known_ledger_block(ledger: &LedgerConfig) -> Result<BlockIndex, String> {
+  let canister_id = ledger.get_canister_id();
+  if let Some(block_index) = LEDGER_KNOWN_BLOCK_CACHE.with(|cache| cache.borrow().get(&canister_id).copied()) {
+    return Ok(block_index);
+  }
+
+  match ledger.get_protocol() {
+    LedgerProtocol::Legacy => query_chain_length(canister_id).await,
+    LedgerProtocol::Icrc1 => query_icrc1_log_length(canister_id).await,
+  }
+}
+
+/// Search a legacy ledger from the start block for a transfer, returns its block index if the ledger has it.
+/// The search stops at the first block past the ledger's transfer window, the ledger no longer takes the transfer after that time.
+pub async fn find_transfer_block(
+  ledger_canister_id: Principal,
+  from: &AccountIdentifier,
+  to: &AccountIdentifier,
+  amount: E8S,
+  memo: Memo,
+  created_at_time: TimestampNanos,
+  start: BlockIndex,
+) -> Result<Option<BlockIndex>, String> {
+  let search_until = created_at_time + LEDGER_TRANSFER_WINDOW_NANOS;
+  let is_transfer = |block: &Block| {
+    block.transaction.memo == memo
+      && block.transaction.created_at_time.timestamp_nanos == created_at_time
+      && match &block.transaction.operation {
+        Some(Operation::Transfer {
+          from: f, to: t, amount: a, ..
+        })
+        | Some(Operation::TransferFrom {
+          from: f, to: t, amount: a, ..
+        }) => f == from && t == to && a.e8s() == amount,
+        _ => false,
+      }
+  };
+
+  let mut next = start;
+
+  loop {
+    let (blocks, chain_length) = query_block_batch(ledger_canister_id, next, BLOCK_SEARCH_BATCH_SIZE).await?;
+
+    for (block_index, block) in &blocks {
+      if block.timestamp.timestamp_nanos > search_until {
+        return Ok(None);
+      }
+      if is_transfer(block) {
+        return Ok(Some(*block_index));
+      }
+    }
+
+    let searched_to = blocks.last().map_or(next, |(block_index, _)| block_index + 1).max(next);
+
+    // Stop at the chain tip, or when the ledger returned nothing further
+    if searched_to >= chain_length || searched_to == next {
+      return Ok(None);
+    }
+
+    next = searched_to;
+  }
+}
+
+/// Search an ICRC-1 ledger from the start transaction for a transfer, returns its block index if the ledger has it.
+/// A transfer_from is recorded without a memo, it is looked up with `memo` None.
+/// The search stops at the first transaction past the ledger's transfer window, the ledger no longer takes the transfer after that time.
+pub async fn find_icrc1_transfer_block(
+  ledger_canister_id: Principal,
+  from: &Account,
+  to: &Account,
+  amount: E8S,
+  memo: Option<serde_bytes::ByteBuf>,
+  created_at_time: TimestampNanos,
+  start: BlockIndex,
+) -> Result<Option<BlockIndex>, String> {
+  let search_until = created_at_time + LEDGER_TRANSFER_WINDOW_NANOS;
+  let amount = Nat::from(amount);
+  let is_transfer = |transaction: &Icrc1Transaction| {
+    transaction.transfer.as_ref().is_some_and(|transfer| {
+      transfer.memo == memo
+        && transfer.created_at_time == Some(created_at_time)
+        && transfer.amount == amount
+        && is_same_account(&transfer.from, from)
+        && is_same_account(&transfer.to, to)
+    })
+  };
+
+  let mut next = start;
+
+  loop {
+    let (transactions, log_length) = query_icrc1_transaction_batch(ledger_canister_id, next, BLOCK_SEARCH_BATCH_SIZE).await?;
+
+    for (block_index, transaction) in &transactions {
+      if transaction.timestamp > search_until {
+        return Ok(None);
+      }
+      if is_transfer(transaction) {
+        return Ok(Some(*block_index));
+      }
+    }
+
+    let searched_to = transactions.last().map_or(next, |(block_index, _)| block_index + 1).max(next);
+
+    // Stop at the log tip, or when the ledger returned nothing further
+    if searched_to >= log_length || searched_to == next {
+      return Ok(None);
+    }
+
+    next = searched_to;
+  }
+}
+
+/// Fetches the current log length of an ICRC-1 ledger, that is the index the next transaction will get
+pub async fn query_icrc1_log_length(ledger_canister_id: Principal) -> Result<u64, String> {
+  let (_, log_length) = query_icrc1_transaction_batch(ledger_canister_id, 0, 0).await?;
+  Ok(log_length)
+}
+
+/// Fetches up to `length` transactions of an ICRC-1 ledger from the start index, including archived ones, with the ledger log length
+async fn query_icrc1_transaction_batch(
+  ledger_canister_id: Principal,
+  start: BlockIndex,
+  length: u64,
+) -> Result<(Vec<(BlockIndex, Icrc1Transaction)>, u64), String> {
+  let resp = ic_cdk::call::Call::bounded_wait(ledger_canister_id, "get_transactions")
+    .with_arg(GetTransactionsRequest {
+      start: Nat::from(start),
+      length: Nat::from(length),
+    })
+    .await
+    .map_err(|e| format!("Failed to query ledger transactions: {:?}", e))?
+    .candid::<GetTransactionsResponse>()
+    .map_err(|e| format!("Failed to decode ledger transactions: {:?}", e))?;
+
+  let mut transactions = Vec::new();
+
+  for range in &resp.archived_transactions {
+    let range_start = nat_to_u64(&range.start)?;
+    let archived = ic_cdk::call::Call::bounded_wait(range.callback.0.principal, &range.callback.0.method)
+      .with_arg(GetTransactionsRequest {
+        start: range.start.clone(),
+        length: range.length.clone(),
+      })
+      .await
+      .map_err(|e| format!("Failed to query archived transactions: {:?}", e))?
+      .candid::<Icrc1TransactionRange>()
+      .map_err(|e| format!("Failed to decode archived transactions: {:?}", e))?;
+
+    transactions.extend(
+      archived
+        .transactions
+        .into_iter()
+        .enumerate()
+        .map(|(i, transaction)| (range_start + i as u64, transaction)),
+    );
+  }
+
+  let first_index = nat_to_u64(&resp.first_index)?;
+  transactions.extend(
+    resp
+      .transactions
+      .into_iter()
+      .enumerate()
+      .map(|(i, transaction)| (first_index + i as u64, transaction)),
+  );
+
+  let log_length = nat_to_u64(&resp.log_length)?;
+  note_ledger_block(ledger_canister_id, log_length);
+  Ok((transactions, log_length))
+}
+
+fn nat_to_u64(value: &Nat) -> Result<u64, String> {
+  u64::try_from(value.0.clone()).map_err(|_| format!("Invalid transaction index: {}", value))
+}
+
+/// An account without a subaccount is the same as the account with the default subaccount
+fn is_same_account(a: &Account, b: &Account) -> bool {
+  let subaccount = |account: &Account| {
+    account
+      .subaccount
+      .as_ref()
+      .map_or(DEFAULT_SUBACCOUNT.0.to_vec(), |subaccount| subaccount.to_vec())
+  };
+  a.owner == b.owner && subaccount(a) == subaccount(b)
+}
+
+/// The parts of the ICRC-1 ledger `get_transactions` interface read by the transfer lookup
+#[derive(CandidType, Deserialize)]
+struct GetTransactionsRequest {
+  start: Nat,
+  length: Nat,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Icrc1Transfer {
+  from: Account,
+  to: Account,
+  amount: Nat,
+  memo: Option<serde_bytes::ByteBuf>,
+  created_at_time: Option<u64>,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Icrc1Transaction {
+  transfer: Option<Icrc1Transfer>,
+  timestamp: u64,
+}
+
+#[derive(CandidType, Deserialize)]
+struct Icrc1TransactionRange {
+  transactions: Vec<Icrc1Transaction>,
+}
+
+define_function!(QueryArchivedTransactionsFn : (GetTransactionsRequest) -> (Icrc1TransactionRange) query);
+
+#[derive(CandidType, Deserialize)]
+struct ArchivedTransactionRange {
+  start: Nat,
+  length: Nat,
+  callback: QueryArchivedTransactionsFn,
+}
+
+#[derive(CandidType, Deserialize)]
+struct GetTransactionsResponse {
+  log_length: Nat,
+  first_index: Nat,
+  transactions: Vec<Icrc1Transaction>,
+  archived_transactions: Vec<ArchivedTransactionRange>,
+}
+
+/// Fetches up to `length` blocks of a legacy ledger from the start block, including archived ones, with the ledger chain length
+pub async fn query_block_batch(ledger_canister_id: Principal, start: BlockIndex, length: u64) -> Result<(Vec<(BlockIndex, Block)>, u64), String> {
+  let resp = query_blocks(ledger_canister_id, &GetBlocksArgs { start, length })
+    .await
+    .map_err(|e| format!("Failed to query ledger blocks: {:?}", e))?;
+
+  let mut blocks = Vec::new();
+
+  for range in &resp.archived_blocks {
+    let archived_blocks = match query_archived_blocks(
+      &range.callback,
+      &GetBlocksArgs {
+        start: range.start,
+        length: range.length,
+      },
+    )
+    .await
+    .map_err(|e| format!("Failed to query archived blocks: {:?}", e))?
+    {
+      Ok(BlockRange { blocks }) => blocks,
+      Err(e) => return Err(format!("Archive canister returned error: {:?}", e)),
+    };
+
+    blocks.extend(archived_blocks.into_iter().enumerate().map(|(i, block)| (range.start + i as u64, block)));
+  }
+
+  blocks.extend(
+    resp
+      .blocks
+      .into_iter()
+      .enumerate()
+      .map(|(i, block)| (resp.first_block_index + i as u64, block)),
+  );
+
+  note_ledger_block(ledger_canister_id, resp.chain_length);
+  Ok((blocks, resp.chain_length))
 }
@@ -0,0 +1,285 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_ledger_types::BlockIndex;
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use types::{
+  stable_structures::MetaData,
+  staking::{StakingAccountId, StakingPoolId},
+  sys::ExteralCanisterLabels,
+  Crypto, TimestampNanos, E8S,
+};
+
+use crate::system_configs::get_exteral_canister_id;
+
+use super::{LEDGER_FEE_CACHE, LEDGER_REGISTRY_MAP, LEDGER_TRANSFER_RECORD_MAP};
+
+/// Outgoing ledger transfer status
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum LedgerTransferStatus {
+  /// The transfer has been sent, but the ledger has not confirmed the outcome
+  Pending,
+  /// The transfer is in the ledger, with its block index
+  Completed(BlockIndex),
+  /// The ledger rejected the transfer
+  Failed(String),
+}
+
+/// Outgoing ledger transfer of a business operation.
+/// The memo and created_at_time are kept, so a retry sends the same transfer and the ledger deduplicates it.
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LedgerTransferRecord {
+  /// Business operation key
+  pub op_key: Option<String>,
+  /// Transfer memo
+  pub memo: Option<u64>,
+  /// created_at_time sent to the ledger
+  pub created_at_time: Option<TimestampNanos>,
+  /// Transfer amount (unit: e8s)
+  pub amount: Option<E8S>,
+  /// Transfer fee sent to the ledger, a retry sends the same fee so the ledger deduplicates it
+  pub fee: Option<E8S>,
+  /// Ledger chain length before the transfer was sent, the transfer can only be in a block from here
+  pub search_from_block: Option<BlockIndex>,
+  /// Transfer status
+  pub status: Option<LedgerTransferStatus>,
+  /// Metadata
+  pub meta: Option<MetaData>,
+}
+
+impl LedgerTransferRecord {
+  pub fn new(op_key: String, memo: u64, amount: E8S, fee: E8S, search_from_block: BlockIndex) -> Self {
+    Self {
+      op_key: Some(op_key),
+      memo: Some(memo),
+      created_at_time: Some(ic_cdk::api::time()),
+      amount: Some(amount),
+      fee: Some(fee),
+      search_from_block: Some(search_from_block),
+      status: Some(LedgerTransferStatus::Pending),
+      meta: Some(MetaData::init_create_scene()),
+    }
+  }
+
+  pub fn get_with_op_key(op_key: &str) -> Option<Self> {
+    LEDGER_TRANSFER_RECORD_MAP.with(|map| map.borrow().get(&op_key.to_string()))
+  }
+
+  /// Start a new attempt of the transfer with a new created_at_time, used when the previous one can no longer be deduplicated by the ledger
+  pub fn renew(&mut self, amount: E8S, fee: E8S, search_from_block: BlockIndex) {
+    self.created_at_time = Some(ic_cdk::api::time());
+    self.amount = Some(amount);
+    self.fee = Some(fee);
+    self.search_from_block = Some(search_from_block);
+    self.status = Some(LedgerTransferStatus::Pending);
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn update_to_pending(&mut self) {
+    self.status = Some(LedgerTransferStatus::Pending);
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn update_to_completed(&mut self, block_index: BlockIndex) {
+    self.status = Some(LedgerTransferStatus::Completed(block_index));
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn update_to_failed(&mut self, error: String) {
+    self.status = Some(LedgerTransferStatus::Failed(error));
+    self.update_meta();
+    self.update_to_stable();
+  }
+
+  pub fn get_op_key(&self) -> String {
+    self.op_key.clone().unwrap_or_default()
+  }
+
+  pub fn get_memo(&self) -> u64 {
+    self.memo.unwrap_or_default()
+  }
+
+  pub fn get_created_at_time(&self) -> TimestampNanos {
+    self.created_at_time.unwrap_or_default()
+  }
+
+  pub fn get_amount(&self) -> E8S {
+    self.amount.unwrap_or_default()
+  }
+
+  /// Transfer fee sent to the ledger, transfers recorded before the fee was kept paid the default ICP fee
+  pub fn get_fee(&self) -> E8S {
+    self.fee.unwrap_or(10_000)
+  }
+
+  pub fn get_search_from_block(&self) -> BlockIndex {
+    self.search_from_block.unwrap_or_default()
+  }
+
+  pub fn get_status(&self) -> LedgerTransferStatus {
+    self.status.clone().unwrap_or(LedgerTransferStatus::Pending)
+  }
+
+  fn update_meta(&mut self) {
+    if let Some(meta) = &mut self.meta {
+      self.meta = Some(meta.update());
+    }
+  }
+
+  pub fn update_to_stable(&self) {
+    LEDGER_TRANSFER_RECORD_MAP.with(|map| {
+      map.borrow_mut().insert(self.get_op_key(), self.clone());
+    });
+  }
+}
+
+impl Storable for LedgerTransferRecord {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Staking pool or staking account that an on-chain address belongs to
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum StakingAddressOwner {
+  StakingPool(StakingPoolId),
+  StakingAccount(StakingAccountId),
+}
+
+impl Storable for StakingAddressOwner {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Interface used to transfer on a token ledger
+#[derive(EnumString, Display, Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum LedgerProtocol {
+  /// ICP ledger `transfer` method, addressed by account identifier
+  #[strum(serialize = "0")]
+  Legacy,
+  /// ICRC-1 `icrc1_transfer` method, addressed by account
+  #[strum(serialize = "1")]
+  Icrc1,
+}
+
+/// Token ledger of a crypto, staking pools transfer on the ledger of their crypto
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LedgerConfig {
+  /// Crypto of the ledger
+  pub crypto: Option<Crypto>,
+  /// Ledger canister ID
+  pub canister_id: Option<Principal>,
+  /// Transfer fee (unit: smallest unit of the token)
+  pub fee: Option<u64>,
+  /// Number of decimals of the token
+  pub decimals: Option<u8>,
+  /// Interface used to transfer
+  pub protocol: Option<LedgerProtocol>,
+  /// Metadata
+  pub meta: Option<MetaData>,
+}
+
+impl LedgerConfig {
+  pub fn new(crypto: Crypto, canister_id: Principal, fee: u64, decimals: u8, protocol: LedgerProtocol) -> Self {
+    Self {
+      crypto: Some(crypto),
+      canister_id: Some(canister_id),
+      fee: Some(fee),
+      decimals: Some(decimals),
+      protocol: Some(protocol),
+      meta: Some(MetaData::init_create_scene()),
+    }
+  }
+
+  /// Get the ledger of the crypto, ICP falls back to the ICP ledger of the system config when it is not registered.
+  /// The fallback ledger has no registered fee, its transfer fee is fetched from the ledger before a transfer.
+  pub fn get_with_crypto(crypto: &Crypto) -> Result<Self, String> {
+    LEDGER_REGISTRY_MAP
+      .with(|map| map.borrow().get(&crypto.to_string()))
+      .or_else(|| match crypto {
+        Crypto::ICP => Some(Self {
+          fee: None,
+          ..Self::new(Crypto::ICP, get_exteral_canister_id(ExteralCanisterLabels::IcpLedger), 0, 8, LedgerProtocol::Legacy)
+        }),
+        _ => None,
+      })
+      .ok_or_else(|| format!("No ledger is registered for crypto {:?}", crypto))
+  }
+
+  /// Query all registered ledgers
+  pub fn query_all() -> Vec<Self> {
+    LEDGER_REGISTRY_MAP.with(|map| map.borrow().iter().map(|(_, config)| config).collect())
+  }
+
+  pub fn get_crypto(&self) -> Crypto {
+    self.crypto.clone().unwrap_or(Crypto::ICP)
+  }
+
+  pub fn get_canister_id(&self) -> Principal {
+    self
+      .canister_id
+      .unwrap_or_else(|| get_exteral_canister_id(ExteralCanisterLabels::IcpLedger))
+  }
+
+  /// Transfer fee of the ledger, the fee last fetched from the ledger takes precedence over the registered fee
+  pub fn get_fee(&self) -> u64 {
+    LEDGER_FEE_CACHE
+      .with(|cache| cache.borrow().get(&self.get_canister_id()).map(|(fee, _)| *fee))
+      .or(self.fee)
+      .unwrap_or_default()
+  }
+
+  /// Whether the transfer fee of the ledger is known, either fetched from the ledger or registered
+  pub fn is_fee_known(&self) -> bool {
+    self.fee.is_some() || LEDGER_FEE_CACHE.with(|cache| cache.borrow().contains_key(&self.get_canister_id()))
+  }
+
+  /// Registered transfer fee of the ledger, used until the fee is fetched from the ledger
+  pub fn get_registered_fee(&self) -> u64 {
+    self.fee.unwrap_or_default()
+  }
+
+  pub fn get_decimals(&self) -> u8 {
+    self.decimals.unwrap_or(8)
+  }
+
+  pub fn get_protocol(&self) -> LedgerProtocol {
+    self.protocol.clone().unwrap_or(LedgerProtocol::Legacy)
+  }
+
+  pub fn update_to_stable(&self) {
+    LEDGER_REGISTRY_MAP.with(|map| {
+      map.borrow_mut().insert(self.get_crypto().to_string(), self.clone());
+    });
+  }
+}
+
+impl Storable for LedgerConfig {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
@@ -1,15 +1,30 @@
-use ic_ledger_types::{AccountIdentifier, BlockIndex, Memo, Subaccount, Tokens, TransferArgs, TransferError, MAINNET_LEDGER_CANISTER_ID};
+use candid::{Nat, Principal};
+use ic_ledger_types::{AccountIdentifier, BlockIndex, Memo, Subaccount, Timestamp, Tokens, TransferArgs, TransferError, DEFAULT_SUBACCOUNT};
+use sha2::{Digest, Sha256};
 use types::{
-  staking::{StakingAccountId, StakingPoolId},
+  assets_management::ProposalId,
+  staking::{ExternalDepositId, StakingAccountId, StakingPoolId},
   sys::ExteralCanisterLabels,
-  E8S,
+  Crypto, EntityId, TimestampNanos, E8S,
 };
 
-use crate::system_configs::get_exteral_canister_id;
+use crate::{
+  account::stable_structures::{StakeMode, StakingAccount},
+  errors::StakingError,
+  nns::utils::ledger_canister::{
+    Account, AllowanceArgs, Result3 as TransferFromResult, Result_ as Icrc1TransferResult, Service as LedgerService, TransferArg,
+    TransferError as Icrc1TransferError, TransferFromArgs, TransferFromError,
+  },
+  pool::crud_utils::query_staking_pool_by_id,
+  pool_transaction_record::{stable_structures::FeeBearer, utils::record_ledger_fee},
+  system_configs::get_exteral_canister_id,
+};
 
-use super::address::{
-  generate_staking_account_account_identifier, generate_staking_account_subaccount, generate_staking_pool_account_identifier,
-  generate_staking_pool_neuron_account, generate_staking_pool_subaccount,
+use super::{
+  address::{generate_neuron_account_with_nonce, generate_staking_account_subaccount, generate_staking_pool_subaccount},
+  query::{find_icrc1_transfer_block, find_transfer_block, get_known_ledger_block, note_ledger_block, refresh_ledger_fee},
+  stable_structures::{LedgerConfig, LedgerProtocol, LedgerTransferRecord, LedgerTransferStatus},
+  PAY_CENTER_ADDRESS_CACHE,
 };
 
 /// Parsing from string AccountIdentifier
@@ -27,21 +42,132 @@ pub const TRANSFER_SCENE_UNSTAKE: u64 = 2;
 pub const TRANSFER_SCENE_PAY_CENTER: u64 = 3;
 pub const TRANSFER_SCENE_UNSTAKE_PENALTY: u64 = 4;
 pub const TRANSFER_SCENE_NNS_STAKE: u64 = 5;
+pub const TRANSFER_SCENE_STAKE_APPROVE: u64 = 6;
+pub const TRANSFER_SCENE_DISSOLVE_TO_WALLET: u64 = 7;
+pub const TRANSFER_SCENE_DEPOSIT_REFUND: u64 = 8;
+pub const TRANSFER_SCENE_DEPOSIT_ADOPT: u64 = 9;
+
+/// The ledger deduplicates transfers created in the last 24 hours, a created_at_time is reused only within this window
+const TRANSFER_DEDUP_WINDOW_NANOS: TimestampNanos = 23 * 60 * 60 * 1_000_000_000;
+
+/// Key of the business operation a transfer belongs to, each operation transfers at most once
+pub fn transfer_op_key(scene: u64, entity_id: EntityId) -> String {
+  format!("transfer_{}_{}", scene, entity_id)
+}
+
+/// Memo of the transfer of a business operation, derived from the operation key so that each operation has its own memo.
+/// Two operations with the same accounts, amount and created_at_time would otherwise be deduplicated into one transfer by the ledger.
+/// The payment center is told the block index of each transfer it receives, it does not reconcile them by memo.
+pub fn transfer_memo(op_key: &str) -> Memo {
+  let hash: [u8; 32] = Sha256::digest(op_key.as_bytes()).into();
+  Memo(u64::from_be_bytes(hash[0..8].try_into().unwrap()))
+}
+
+/// Get the token ledger of the staking pool's crypto, with the last fetched transfer fee
+pub fn get_pool_ledger(pool_id: StakingPoolId) -> Result<LedgerConfig, String> {
+  LedgerConfig::get_with_crypto(&query_staking_pool_by_id(pool_id)?.get_crypto())
+}
+
+/// Get the token ledger of the staking pool's crypto before a transfer, fetching its transfer fee again when it is stale, it fails while the fee is neither fetched nor registered
+pub async fn load_pool_ledger(pool_id: StakingPoolId) -> Result<LedgerConfig, String> {
+  let ledger = get_pool_ledger(pool_id)?;
+  refresh_ledger_fee(&ledger).await;
+
+  if !ledger.is_fee_known() {
+    return Err(format!("The transfer fee of ledger {} could not be fetched", ledger.get_canister_id()));
+  }
+  Ok(ledger)
+}
+
+/// Destination of an outgoing transfer
+enum TransferDestination {
+  /// Account of a principal, legacy ledgers receive its account identifier
+  Account(Principal, Subaccount),
+  /// Account identifier, only legacy ledgers can transfer to it
+  AccountIdentifier(AccountIdentifier),
+}
+
+impl TransferDestination {
+  fn to_account_identifier(&self) -> AccountIdentifier {
+    match self {
+      TransferDestination::Account(owner, subaccount) => AccountIdentifier::new(owner, subaccount),
+      TransferDestination::AccountIdentifier(account_identifier) => *account_identifier,
+    }
+  }
+
+  fn to_icrc_account(&self) -> Result<Account, String> {
+    match self {
+      TransferDestination::Account(owner, subaccount) => Ok(icrc_account(*owner, subaccount)),
+      TransferDestination::AccountIdentifier(account_identifier) => Err(format!(
+        "Account identifier {} can only receive transfers from legacy ledgers",
+        account_identifier.to_hex()
+      )),
+    }
+  }
+}
+
+fn icrc_account(owner: Principal, subaccount: &Subaccount) -> Account {
+  Account {
+    owner,
+    subaccount: if *subaccount == DEFAULT_SUBACCOUNT {
+      None
+    } else {
+      Some(serde_bytes::ByteBuf::from(subaccount.0.to_vec()))
+    },
+  }
+}
+
+/// Get the payment center account on the ledger, legacy ledgers use the address reported by the payment center
+async fn get_pay_center_destination(ledger: &LedgerConfig) -> Result<TransferDestination, String> {
+  let pay_center_canister_id = get_exteral_canister_id(ExteralCanisterLabels::PayCenter);
+
+  if ledger.get_protocol() == LedgerProtocol::Icrc1 {
+    return Ok(TransferDestination::Account(pay_center_canister_id, DEFAULT_SUBACCOUNT));
+  }
+
+  Ok(TransferDestination::AccountIdentifier(query_pay_center_account_identifier().await?))
+}
+
+/// Get the payment center address on the ICP ledger, as reported by the payment center.
+/// The address is fetched once per payment center canister and kept until the next upgrade.
+pub async fn query_pay_center_account_identifier() -> Result<AccountIdentifier, String> {
+  let pay_center_canister_id = get_exteral_canister_id(ExteralCanisterLabels::PayCenter);
+  if let Some((canister_id, account_identifier)) = PAY_CENTER_ADDRESS_CACHE.with(|cache| *cache.borrow()) {
+    if canister_id == pay_center_canister_id {
+      return Ok(account_identifier);
+    }
+  }
+
+  let pay_center = common_canisters::pay_center::Service(pay_center_canister_id);
+
+  let (pay_center_address,) = pay_center
+    .get_address()
+    .await
+    .map_err(|e| format!("Failed to obtain payment center address: {:?}", e))?;
+
+  let account_identifier = parse_account_id(&pay_center_address)?;
+  PAY_CENTER_ADDRESS_CACHE.with(|cache| *cache.borrow_mut() = Some((pay_center_canister_id, account_identifier)));
+  Ok(account_identifier)
+}
 
 pub async fn transfer_from_staking_pool_to_staking_account(
   pool_id: StakingPoolId,
   account_id: StakingAccountId,
   amount: E8S,
 ) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+
   // Transfer out of the account
   let from_account = generate_staking_pool_subaccount(pool_id);
 
   // Transfer to the account
-  let to_account = generate_staking_account_account_identifier(account_id);
+  let to_account = TransferDestination::Account(ic_cdk::api::canister_self(), generate_staking_account_subaccount(account_id));
 
   // Perform a transfer, the transfer amount needs to increase a handling fee, these amounts are paid in advance from the payment center
-  transfer(&from_account, &to_account, amount + 10_000, Memo(TRANSFER_SCENE_UNSTAKE)).await
-  // Use a valid u64 value for Memo
+  let op_key = transfer_op_key(TRANSFER_SCENE_UNSTAKE, account_id);
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, amount + ledger.get_fee()).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, get_unstake_fee_bearer(account_id)?))
 }
 
 /// Transfer from staking account to staking pool (Stake)
@@ -50,101 +176,487 @@ pub async fn transfer_from_staking_account_to_staking_pool(
   pool_id: StakingPoolId,
   amount: E8S,
 ) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+
   // Transfer out of the account
   let from_account = generate_staking_account_subaccount(account_id);
 
   // Transfer to the account
-  let to_account = generate_staking_pool_account_identifier(pool_id);
+  let to_account = TransferDestination::Account(ic_cdk::api::canister_self(), generate_staking_pool_subaccount(pool_id));
 
   // Perform a transfer. The Transfer amount needs to add two handling fee amounts. These amounts are paid in advance from the payment center
-  transfer(&from_account, &to_account, amount + 20_000, Memo(TRANSFER_SCENE_STAKE)).await
+  let op_key = transfer_op_key(TRANSFER_SCENE_STAKE, account_id);
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, amount + ledger.get_fee() * 2).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::Treasury))
 }
 
 /// Transfer funds from the Pledge account to the payment center, that is, the released funds in the staking account are transferred to the payment center
 pub async fn transfer_from_staking_account_to_pay_center(from_account_id: StakingAccountId, amount: E8S) -> Result<BlockIndex, String> {
+  let pool_id = StakingAccount::query_by_id(from_account_id)?.get_pool_id();
+  let ledger = load_pool_ledger(pool_id).await?;
+
   // Transfer out of the account
   let from_account = generate_staking_account_subaccount(from_account_id);
 
-  let pay_center_canister_id = get_exteral_canister_id(ExteralCanisterLabels::PayCenter);
-  let pay_center = common_canisters::pay_center::Service(pay_center_canister_id);
-
-  let (pay_center_address,) = pay_center
-    .get_address()
-    .await
-    .map_err(|e| format!("Failed to obtain payment center address: {:?}", e))?;
-
   // Transfer to the account
-  let to_account = parse_account_id(&pay_center_address)?;
+  let to_account = get_pay_center_destination(&ledger).await?;
 
   // Perform a transfer
-  transfer(&from_account, &to_account, amount, Memo(TRANSFER_SCENE_PAY_CENTER)).await
+  let op_key = transfer_op_key(TRANSFER_SCENE_PAY_CENTER, from_account_id);
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, amount).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::Treasury))
 }
 
 /// Transfer from the Pledge pool to the payment center. The current scenario is that when a user initiates early release of the Pledge, the resulting penalty is directly transferred to the payment center through the Pledge pool
-pub async fn transfer_from_staking_pool_to_pay_center(pool_id: StakingPoolId, amount: E8S) -> Result<BlockIndex, String> {
+pub async fn transfer_from_staking_pool_to_pay_center(
+  pool_id: StakingPoolId,
+  account_id: StakingAccountId,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+
   // Transfer out of the account
   let from_account = generate_staking_pool_subaccount(pool_id);
 
-  let pay_center_canister_id = get_exteral_canister_id(ExteralCanisterLabels::PayCenter);
-  let pay_center = common_canisters::pay_center::Service(pay_center_canister_id);
-
-  let (pay_center_address,) = pay_center
-    .get_address()
-    .await
-    .map_err(|e| format!("Failed to obtain payment center address: {:?}", e))?;
-
   // Transfer to the account
-  let to_account = parse_account_id(&pay_center_address)?;
+  let to_account = get_pay_center_destination(&ledger).await?;
 
   // Perform a transfer
-  transfer(&from_account, &to_account, amount - 10_000, Memo(TRANSFER_SCENE_UNSTAKE_PENALTY)).await
+  let op_key = transfer_op_key(TRANSFER_SCENE_UNSTAKE_PENALTY, account_id);
+  let penalty_amount = amount
+    .checked_sub(ledger.get_fee())
+    .filter(|penalty_amount| *penalty_amount > 0)
+    .ok_or_else(|| format!("The penalty amount {} does not cover the handling fee {}", amount, ledger.get_fee()))?;
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, penalty_amount).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::Treasury))
+}
+
+/// Record the fee the ledger charged for the transfer of the business operation, and who bore it
+fn record_transfer_fee(op_key: &str, pool_id: StakingPoolId, block_index: BlockIndex, bearer: FeeBearer) -> BlockIndex {
+  if let Some(record) = LedgerTransferRecord::get_with_op_key(op_key) {
+    record_ledger_fee(pool_id, block_index, record.get_fee(), bearer, op_key.to_string());
+  }
+  block_index
+}
+
+/// The unstake transfer fee is prepaid with the stake, by the payment center or by the user's wallet
+fn get_unstake_fee_bearer(account_id: StakingAccountId) -> Result<FeeBearer, String> {
+  match StakingAccount::query_by_id(account_id)?.get_stake_mode() {
+    StakeMode::PayCenter => Ok(FeeBearer::Treasury),
+    StakeMode::Icrc2Approve(_) => Ok(FeeBearer::User),
+  }
+}
+
+/// Whether the ledger rejected the transfer of the business operation, as opposed to a transfer with an unknown outcome
+pub fn is_transfer_failed(op_key: &str) -> bool {
+  LedgerTransferRecord::get_with_op_key(op_key).is_some_and(|record| matches!(record.get_status(), LedgerTransferStatus::Failed(_)))
+}
+
+/// Next step of a business operation's transfer
+enum TransferAttempt {
+  /// The transfer is already in the ledger
+  Done(BlockIndex),
+  /// The transfer has to be sent with the record's memo and created_at_time
+  Send(LedgerTransferRecord),
+}
+
+/// Block the ledger search of a new transfer starts from.
+/// Any block seen before the transfer is sent lies below it, the highest one seen is used.
+async fn get_search_start(ledger: &LedgerConfig) -> Result<BlockIndex, String> {
+  get_known_ledger_block(ledger).await
+}
+
+/// ICRC-1 memo of a transfer, the memo 0 stands for a transfer without a memo such as a transfer_from
+fn icrc1_memo(memo: u64) -> Option<serde_bytes::ByteBuf> {
+  (memo != 0).then(|| serde_bytes::ByteBuf::from(memo.to_be_bytes().to_vec()))
+}
+
+/// Load or create the transfer record of the business operation.
+/// A pending transfer the ledger can no longer deduplicate is looked up in the ledger first, since the previous attempt may have reached it.
+async fn begin_transfer(
+  op_key: &str,
+  ledger: &LedgerConfig,
+  from_account: (Principal, Subaccount),
+  to_account: &TransferDestination,
+  amount: E8S,
+  memo: Memo,
+) -> Result<TransferAttempt, String> {
+  let mut record = match LedgerTransferRecord::get_with_op_key(op_key) {
+    Some(record) => record,
+    None => {
+      let record = LedgerTransferRecord::new(op_key.to_string(), memo.0, amount, ledger.get_fee(), get_search_start(ledger).await?);
+      record.update_to_stable();
+      return Ok(TransferAttempt::Send(record));
+    }
+  };
+
+  let can_deduplicate =
+    record.get_amount() == amount && ic_cdk::api::time().saturating_sub(record.get_created_at_time()) <= TRANSFER_DEDUP_WINDOW_NANOS;
+
+  match record.get_status() {
+    LedgerTransferStatus::Completed(block_index) => {
+      ic_cdk::println!("Transfer {} is already completed in block {}", op_key, block_index);
+      return Ok(TransferAttempt::Done(block_index));
+    }
+    // While the ledger can deduplicate the transfer, sending it again returns the block of a transfer that went through
+    LedgerTransferStatus::Pending if !can_deduplicate => {
+      let found_block = match ledger.get_protocol() {
+        LedgerProtocol::Legacy => {
+          find_transfer_block(
+            ledger.get_canister_id(),
+            &AccountIdentifier::new(&from_account.0, &from_account.1),
+            &to_account.to_account_identifier(),
+            record.get_amount(),
+            Memo(record.get_memo()),
+            record.get_created_at_time(),
+            record.get_search_from_block(),
+          )
+          .await?
+        }
+        LedgerProtocol::Icrc1 => {
+          find_icrc1_transfer_block(
+            ledger.get_canister_id(),
+            &icrc_account(from_account.0, &from_account.1),
+            &to_account.to_icrc_account()?,
+            record.get_amount(),
+            icrc1_memo(record.get_memo()),
+            record.get_created_at_time(),
+            record.get_search_from_block(),
+          )
+          .await?
+        }
+      };
+
+      if let Some(block_index) = found_block {
+        ic_cdk::println!("Transfer {} found in block {}", op_key, block_index);
+        record.update_to_completed(block_index);
+        return Ok(TransferAttempt::Done(block_index));
+      }
+    }
+    LedgerTransferStatus::Pending | LedgerTransferStatus::Failed(_) => {}
+  }
+
+  // Reuse the created_at_time while the ledger can still deduplicate it
+  if can_deduplicate {
+    record.update_to_pending();
+  } else {
+    record.renew(amount, ledger.get_fee(), get_search_start(ledger).await?);
+  }
+
+  Ok(TransferAttempt::Send(record))
 }
 
 /// Transfer function
-/// Transfer money from one sub-account to another
+/// Transfer money from one sub-account to another on the token ledger, at most once per business operation
 ///
 /// # Arguments
+/// * `op_key` - Key of the business operation the transfer belongs to
+/// * `ledger` - Token ledger to transfer on
 /// * `from_account` - Sub-accounts transferred out of the account
 /// * `to_account` - Transfer to the account
-/// * `amount` - Transfer amount，The unit is E8S
+/// * `amount` - Transfer amount，The unit is the smallest unit of the token
+///
+/// The memo is derived from the operation key.
 ///
 /// # Returns
 /// Return one Result，Block index or error message containing transfers
 ///
 /// # Errors
-/// Return one Result，Block index or error message containing transfers
-async fn transfer(from_account: &Subaccount, to_account: &AccountIdentifier, amount: E8S, memo: Memo) -> Result<BlockIndex, String> {
-  // Call ICP Ledger canister Make a transfer
-  let ledger_canister_id = MAINNET_LEDGER_CANISTER_ID;
+/// Return an error when the ledger rejects the transfer, or when the outcome is unknown.
+/// A transfer with an unknown outcome is looked up in the ledger before it is sent again.
+async fn transfer(
+  op_key: String,
+  ledger: &LedgerConfig,
+  from_account: &Subaccount,
+  to_account: &TransferDestination,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let memo = transfer_memo(&op_key);
 
+  let mut record = match begin_transfer(&op_key, ledger, (ic_cdk::api::canister_self(), *from_account), to_account, amount, memo).await? {
+    TransferAttempt::Done(block_index) => return Ok(block_index),
+    TransferAttempt::Send(record) => record,
+  };
+
+  match ledger.get_protocol() {
+    LedgerProtocol::Legacy => legacy_transfer(&mut record, ledger, from_account, to_account.to_account_identifier(), amount).await,
+    LedgerProtocol::Icrc1 => icrc1_transfer(&mut record, ledger, from_account, to_account.to_icrc_account()?, amount).await,
+  }
+}
+
+/// Transfer through the ICP ledger `transfer` method
+async fn legacy_transfer(
+  record: &mut LedgerTransferRecord,
+  ledger: &LedgerConfig,
+  from_account: &Subaccount,
+  to_account: AccountIdentifier,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
   // Create transfer parameters
   let transfer_args = TransferArgs {
-    memo, // Memo information can be set
+    memo: Memo(record.get_memo()),
     amount: e8s_to_tokens(amount),
-    fee: e8s_to_tokens(10_000), // ICP The standard transfer fee is 10000 E8S
-    from_subaccount: Some(from_account.clone()),
-    to: to_account.clone(),
-    created_at_time: None, // Current time of using the system
+    fee: e8s_to_tokens(record.get_fee()),
+    from_subaccount: Some(*from_account),
+    to: to_account,
+    created_at_time: Some(Timestamp {
+      timestamp_nanos: record.get_created_at_time(),
+    }),
   };
 
-  let result = ic_cdk::call::Call::unbounded_wait(ledger_canister_id, "transfer")
+  // A failed call leaves the record pending, the outcome is unknown until the transfer is looked up in the ledger
+  let result = ic_cdk::call::Call::unbounded_wait(ledger.get_canister_id(), "transfer")
     .with_arg(transfer_args)
     .await
-    .map_err(|e| format!("Call Ledger failed: {:?}", e))?;
+    .map_err(|e| format!("Call Ledger failed, transfer outcome unknown: {:?}", e))?;
 
   match result.candid_tuple::<(Result<BlockIndex, TransferError>,)>() {
-    Ok((Ok(block_index),)) => Ok(block_index),
-    Ok((Err(error),)) => Err(format!("Transfer failed: {:?}", error)),
+    Ok((Ok(block_index),)) => {
+      note_ledger_block(ledger.get_canister_id(), block_index);
+      record.update_to_completed(block_index);
+      Ok(block_index)
+    }
+    Ok((Err(TransferError::TxDuplicate { duplicate_of }),)) => {
+      ic_cdk::println!("Transfer {} is a duplicate of block {}", record.get_op_key(), duplicate_of);
+      note_ledger_block(ledger.get_canister_id(), duplicate_of);
+      record.update_to_completed(duplicate_of);
+      Ok(duplicate_of)
+    }
+    Ok((Err(error),)) => {
+      let error = format!("Transfer failed: {:?}", error);
+      record.update_to_failed(error.clone());
+      Err(error)
+    }
     Err(error) => Err(format!("Transfer failed: {:?}", error)),
   }
 }
 
-/// Transfer from the Pledge pool to the payment center. The current scenario is that when a user initiates early release of the Pledge, the resulting penalty is directly transferred to the payment center through the Pledge pool
-pub async fn transfer_from_staking_pool_to_nns_neuron(pool_id: StakingPoolId, amount: E8S) -> Result<BlockIndex, String> {
+/// Transfer through the ICRC-1 `icrc1_transfer` method
+async fn icrc1_transfer(
+  record: &mut LedgerTransferRecord,
+  ledger: &LedgerConfig,
+  from_account: &Subaccount,
+  to_account: Account,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let ledger_service = LedgerService(ledger.get_canister_id());
+
+  // A failed call leaves the record pending, the outcome is unknown until the ledger deduplicates the transfer
+  let (result,) = ledger_service
+    .icrc_1_transfer(TransferArg {
+      to: to_account,
+      fee: Some(Nat::from(record.get_fee())),
+      memo: icrc1_memo(record.get_memo()),
+      from_subaccount: Some(serde_bytes::ByteBuf::from(from_account.0.to_vec())),
+      created_at_time: Some(record.get_created_at_time()),
+      amount: Nat::from(amount),
+    })
+    .await
+    .map_err(|e| format!("Call Ledger failed, transfer outcome unknown: code = {:?}, message = {}", e.0, e.1))?;
+
+  match result {
+    Icrc1TransferResult::Ok(block_index) => {
+      let block_index = nat_to_block_index(block_index)?;
+      note_ledger_block(ledger.get_canister_id(), block_index);
+      record.update_to_completed(block_index);
+      Ok(block_index)
+    }
+    Icrc1TransferResult::Err(Icrc1TransferError::Duplicate { duplicate_of }) => {
+      let block_index = nat_to_block_index(duplicate_of)?;
+      ic_cdk::println!("Transfer {} is a duplicate of block {}", record.get_op_key(), block_index);
+      note_ledger_block(ledger.get_canister_id(), block_index);
+      record.update_to_completed(block_index);
+      Ok(block_index)
+    }
+    Icrc1TransferResult::Err(error) => {
+      let error = format!("Transfer failed: {:?}", error);
+      record.update_to_failed(error.clone());
+      Err(error)
+    }
+  }
+}
+
+/// Transfer from the staking pool to the staking account of the NNS neuron with the nonce, once per proposal
+pub async fn transfer_from_staking_pool_to_nns_neuron(
+  proposal_id: ProposalId,
+  pool_id: StakingPoolId,
+  neuron_nonce: u64,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+
+  if ledger.get_crypto() != Crypto::ICP {
+    return Err("Only ICP staking pools can stake into NNS neurons".to_string());
+  }
+
   // Transfer out of the account
   let from_account = generate_staking_pool_subaccount(pool_id);
-  let to_account = generate_staking_pool_neuron_account(pool_id);
+  let to_account = TransferDestination::AccountIdentifier(generate_neuron_account_with_nonce(neuron_nonce));
+
+  // Perform a transfer
+  let op_key = transfer_op_key(TRANSFER_SCENE_NNS_STAKE, proposal_id);
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, amount).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::Pool))
+}
+
+/// Pull the stake from the user's wallet into the staking pool, using the allowance the wallet approved to the staking canister (Stake with approve)
+pub async fn transfer_from_wallet_to_staking_pool(
+  wallet: Principal,
+  account_id: StakingAccountId,
+  pool_id: StakingPoolId,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+  let canister_id = ic_cdk::api::canister_self();
+  let to_subaccount = generate_staking_pool_subaccount(pool_id);
+  let to_account = TransferDestination::Account(canister_id, to_subaccount);
+
+  // The pool receives the same amount as a stake through the staking account, the extra handling fees cover the unstake transfer and the transfer back to the wallet
+  let amount = amount + ledger.get_fee() * 2;
+
+  // The ledger records a transfer_from without an ICRC-1 memo with the memo 0
+  let op_key = transfer_op_key(TRANSFER_SCENE_STAKE_APPROVE, account_id);
+  let mut record = match begin_transfer(&op_key, &ledger, (wallet, DEFAULT_SUBACCOUNT), &to_account, amount, Memo(0)).await? {
+    TransferAttempt::Done(block_index) => return Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::User)),
+    TransferAttempt::Send(record) => record,
+  };
+
+  let ledger_service = LedgerService(ledger.get_canister_id());
+
+  let (result,) = ledger_service
+    .icrc_2_transfer_from(TransferFromArgs {
+      to: icrc_account(canister_id, &to_subaccount),
+      fee: Some(Nat::from(record.get_fee())),
+      spender_subaccount: None,
+      from: icrc_account(wallet, &DEFAULT_SUBACCOUNT),
+      memo: None,
+      created_at_time: Some(record.get_created_at_time()),
+      amount: Nat::from(amount),
+    })
+    .await
+    .map_err(|e| format!("Call Ledger failed, transfer outcome unknown: code = {:?}, message = {}", e.0, e.1))?;
+
+  let block_index = match result {
+    TransferFromResult::Ok(block_index) => nat_to_block_index(block_index)?,
+    TransferFromResult::Err(TransferFromError::Duplicate { duplicate_of }) => {
+      let block_index = nat_to_block_index(duplicate_of)?;
+      ic_cdk::println!("Transfer from wallet {} is a duplicate of block {}", wallet, block_index);
+      block_index
+    }
+    TransferFromResult::Err(error) => {
+      let error = format!("Transfer from wallet failed: {:?}", error);
+      record.update_to_failed(error.clone());
+      return Err(error);
+    }
+  };
+
+  note_ledger_block(ledger.get_canister_id(), block_index);
+  record.update_to_completed(block_index);
+
+  // The transfer_from fee is charged to the wallet
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::User))
+}
+
+/// Check that the wallet approved the staking canister enough to stake the amount into the staking pool
+/// The transfer from the wallet pays one handling fee, the stake pool keeps the other two for the unstake transfer and the transfer back to the wallet
+pub async fn check_wallet_allowance(wallet: Principal, pool_id: StakingPoolId, amount: E8S) -> Result<(), StakingError> {
+  let ledger = load_pool_ledger(pool_id).await?;
+  let required_amount = amount + ledger.get_fee() * 3;
+  let ledger_service = LedgerService(ledger.get_canister_id());
+
+  let (allowance,) = ledger_service
+    .icrc_2_allowance(AllowanceArgs {
+      account: icrc_account(wallet, &DEFAULT_SUBACCOUNT),
+      spender: icrc_account(ic_cdk::api::canister_self(), &DEFAULT_SUBACCOUNT),
+    })
+    .await
+    .map_err(|e| StakingError::internal(format!("Failed to query allowance: code = {:?}, message = {}", e.0, e.1)))?;
+
+  if allowance.allowance < required_amount {
+    return Err(StakingError::InsufficientAllowance {
+      message: format!("Insufficient allowance: approved {}, required {}", allowance.allowance, required_amount),
+    });
+  }
+
+  Ok(())
+}
+
+/// Transfer the released funds of the staking account back to the user's wallet (Dissolve of a stake with approve)
+pub async fn transfer_from_staking_account_to_wallet(account_id: StakingAccountId, wallet: Principal, amount: E8S) -> Result<BlockIndex, String> {
+  let pool_id = StakingAccount::query_by_id(account_id)?.get_pool_id();
+  let ledger = load_pool_ledger(pool_id).await?;
+
+  // Transfer out of the account
+  let from_account = generate_staking_account_subaccount(account_id);
+
+  // Transfer to the account
+  let to_account = TransferDestination::Account(wallet, DEFAULT_SUBACCOUNT);
 
   // Perform a transfer
-  transfer(&from_account, &to_account, amount, Memo(TRANSFER_SCENE_NNS_STAKE)).await
+  let op_key = transfer_op_key(TRANSFER_SCENE_DISSOLVE_TO_WALLET, account_id);
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, amount).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::User))
+}
+
+/// Refund an external deposit to the account it came from, the handling fee is taken out of the refund
+pub async fn transfer_external_deposit_refund(
+  deposit_id: ExternalDepositId,
+  pool_id: StakingPoolId,
+  from_subaccount: Subaccount,
+  to: AccountIdentifier,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+
+  if amount <= ledger.get_fee() {
+    return Err(format!("The deposit amount {} does not cover the handling fee {}", amount, ledger.get_fee()));
+  }
+
+  // Perform a transfer
+  let op_key = transfer_op_key(TRANSFER_SCENE_DEPOSIT_REFUND, deposit_id);
+  let block_index = transfer(
+    op_key.clone(),
+    &ledger,
+    &from_subaccount,
+    &TransferDestination::AccountIdentifier(to),
+    amount - ledger.get_fee(),
+  )
+  .await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::User))
+}
+
+/// Move an external deposit in a staking account into the staking pool, the handling fee is taken out of the deposit
+pub async fn transfer_external_deposit_to_staking_pool(
+  deposit_id: ExternalDepositId,
+  account_id: StakingAccountId,
+  pool_id: StakingPoolId,
+  amount: E8S,
+) -> Result<BlockIndex, String> {
+  let ledger = load_pool_ledger(pool_id).await?;
+
+  if amount <= ledger.get_fee() {
+    return Err(format!("The deposit amount {} does not cover the handling fee {}", amount, ledger.get_fee()));
+  }
+
+  // Transfer out of the account
+  let from_account = generate_staking_account_subaccount(account_id);
+
+  // Transfer to the account
+  let to_account = TransferDestination::Account(ic_cdk::api::canister_self(), generate_staking_pool_subaccount(pool_id));
+
+  // Perform a transfer
+  let op_key = transfer_op_key(TRANSFER_SCENE_DEPOSIT_ADOPT, deposit_id);
+  let block_index = transfer(op_key.clone(), &ledger, &from_account, &to_account, amount - ledger.get_fee()).await?;
+
+  Ok(record_transfer_fee(&op_key, pool_id, block_index, FeeBearer::Pool))
+}
+
+fn nat_to_block_index(block_index: Nat) -> Result<BlockIndex, String> {
+  u64::try_from(block_index.0.clone()).map_err(|_| format!("Invalid block index: {}", block_index))
 }
@@ -0,0 +1,63 @@
+use candid::{CandidType, Principal};
+use serde::{Deserialize, Serialize};
+
+use types::staking::{StakingAccountId, StakingPoolId};
+
+use super::stable_structures::LedgerConfig;
+
+/// Register the token ledger of a crypto
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LedgerConfigDto {
+  /// Crypto，refer to Crypto enumerate
+  pub crypto: String,
+  /// Ledger canister ID
+  pub canister_id: Principal,
+  /// Transfer fee (unit: smallest unit of the token)
+  pub fee: u64,
+  /// Number of decimals of the token
+  pub decimals: u8,
+  /// Interface used to transfer，refer to LedgerProtocol enumerate
+  pub protocol: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LedgerConfigVo {
+  /// Crypto，refer to Crypto enumerate
+  pub crypto: String,
+  /// Ledger canister ID
+  pub canister_id: Principal,
+  /// Registered transfer fee (unit: smallest unit of the token)
+  pub fee: u64,
+  /// Transfer fee in use, fetched from the ledger (unit: smallest unit of the token)
+  pub current_fee: u64,
+  /// Number of decimals of the token
+  pub decimals: u8,
+  /// Interface used to transfer，refer to LedgerProtocol enumerate
+  pub protocol: String,
+}
+
+impl From<LedgerConfig> for LedgerConfigVo {
+  fn from(config: LedgerConfig) -> Self {
+    Self {
+      crypto: config.get_crypto().to_string(),
+      canister_id: config.get_canister_id(),
+      fee: config.get_registered_fee(),
+      current_fee: config.get_fee(),
+      decimals: config.get_decimals(),
+      protocol: config.get_protocol().to_string(),
+    }
+  }
+}
+
+/// Staking pool or staking account resolved from an on-chain address
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct StakingAddressOwnerVo {
+  /// Staking poolID
+  pub pool_id: StakingPoolId,
+  /// stake accountID，None when the address is the staking pool address
+  pub account_id: Option<StakingAccountId>,
+  /// Address in the account identifier hex encoding
+  pub account_identifier: String,
+  /// Address in the ICRC-1 account text encoding
+  pub icrc1_account: String,
+}
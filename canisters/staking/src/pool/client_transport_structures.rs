@@ -8,6 +8,7 @@ use types::{
 };
 
 use crate::account::{crud_utils::query_current_user_staking_accounts, stable_structures::StakingAccountStatus};
+use crate::on_chain::address::generate_staking_pool_icrc1_address;
 
 use super::{
   stable_structures::StakingPool,
@@ -21,6 +22,8 @@ pub struct ClientStakingPoolVo {
   pub id: StakingPoolId,
   /// Staking pool on-chain address
   pub address: String,
+  /// Staking pool address in the ICRC-1 account text encoding
+  pub icrc1_address: String,
   /// Staking pool maximum capacity
   pub pool_size: E8S,
   /// Staked amount of the staking pool
@@ -114,6 +117,7 @@ impl ClientStakingPoolVo {
     Self {
       id: pool.get_id(),
       address: pool.get_address(),
+      icrc1_address: generate_staking_pool_icrc1_address(pool.get_id()),
       pool_size,
       pool_staked_amount,
       pool_remain_amount: pool_size - pool_staked_amount,
@@ -1,4 +1,4 @@
-use crate::event_log::staking_pool_events::save_create_staking_pool_event_log;
+use crate::{event_log::staking_pool_events::save_create_staking_pool_event_log, on_chain::address::index_staking_pool_address};
 
 use super::{stable_structures::StakingPool, STAKING_POOL_MAP};
 
@@ -14,6 +14,7 @@ pub fn add_staking_pool_to_stable_memory(pool: &StakingPool) -> Option<String> {
     }
     // Save the stake pool to stable memory
     map.insert(pool.get_id(), pool.clone());
+    index_staking_pool_address(pool.get_id());
     // Save the new staking pool event log to stable memory
     save_create_staking_pool_event_log(&pool);
     None
@@ -3,16 +3,17 @@ use std::{cell::RefCell, str::FromStr};
 use crud_utils::add_staking_pool_to_stable_memory;
 use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
 use stable_structures::{StakingPool, StakingPoolStatus};
-use system_configs_macro::{has_permission, has_permission_option};
+use system_configs_macro::{has_permission, has_permission_option, has_permission_result};
 use transport_structures::{StakingPoolAddDto, StakingPoolUpdateDto, StakingPoolVo};
 use types::{stable_structures::Memory, staking::StakingPoolId, EntityId};
 
 use crate::{
+  errors::StakingError,
   event_log::staking_pool_events::{
     save_change_staking_pool_status_event_log, save_change_staking_pool_visible_event_log, save_update_staking_pool_event_log,
   },
   memory_ids::{STAKING_POOL, STAKING_POOL_SEQ},
-  on_chain::address::{generate_staking_pool_account_identifier, generate_staking_pool_neuron_account},
+  on_chain::address::{generate_staking_pool_account_identifier, generate_staking_pool_icrc1_address, generate_staking_pool_neuron_account},
   pool::transport_structures::StakingPoolAccountIds,
   MEMORY_MANAGER,
 };
@@ -37,12 +38,26 @@ thread_local! {
 
 /// Added a stake pool
 #[ic_cdk::update]
-#[has_permission_option("staking::pool::add")]
+#[has_permission_option("staking::pool::add", audit)]
 fn add_staking_pool(pool: StakingPoolAddDto) -> Option<String> {
+  save_new_staking_pool(pool).err().map(|e| e.to_string())
+}
+
+/// Added a stake pool, failures are returned as a structured error
+#[ic_cdk::update]
+#[has_permission_result("staking::pool::add", audit)]
+fn add_staking_pool_v2(pool: StakingPoolAddDto) -> Result<(), StakingError> {
+  save_new_staking_pool(pool)
+}
+
+fn save_new_staking_pool(pool: StakingPoolAddDto) -> Result<(), StakingError> {
   let staking_pool = StakingPool::from_add_dto(&pool);
 
   // Save the staking pool to stable memory
-  add_staking_pool_to_stable_memory(&staking_pool)
+  match add_staking_pool_to_stable_memory(&staking_pool) {
+    Some(message) => Err(StakingError::invalid_argument(message)),
+    None => Ok(()),
+  }
 }
 
 /// Query all stake pools
@@ -56,22 +71,25 @@ fn get_all_staking_pools() -> Vec<StakingPoolVo> {
 }
 
 #[ic_cdk::update]
-#[has_permission_option("staking::pool::update")]
+#[has_permission_option("staking::pool::update", audit)]
 fn set_staking_pool_client_visible(id: StakingPoolId, visible: bool) -> Option<String> {
-  STAKING_POOL_MAP.with(|map| {
-    let mut map = map.borrow_mut();
-    let pool = map.get(&id);
-
-    if pool.is_none() {
-      return Some("Staking poll not found".to_string()); // Pool not found
-    }
+  change_staking_pool_client_visible(id, visible).err().map(|e| e.to_string())
+}
 
-    let mut pool = pool.unwrap();
+#[ic_cdk::update]
+#[has_permission_result("staking::pool::update", audit)]
+fn set_staking_pool_client_visible_v2(id: StakingPoolId, visible: bool) -> Result<(), StakingError> {
+  change_staking_pool_client_visible(id, visible)
+}
 
-    let result = pool.set_client_visible(visible);
+fn change_staking_pool_client_visible(id: StakingPoolId, visible: bool) -> Result<(), StakingError> {
+  STAKING_POOL_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    let mut pool = map.get(&id).ok_or(StakingError::PoolNotFound { pool_id: id })?;
 
-    if result.is_some() {
-      return result; // If there was an error in setting visibility, return the error message
+    // If there was an error in setting visibility, return the error message
+    if let Some(message) = pool.set_client_visible(visible) {
+      return Err(StakingError::invalid_argument(message));
     }
 
     map.insert(pool.get_id(), pool.clone());
@@ -79,65 +97,67 @@ fn set_staking_pool_client_visible(id: StakingPoolId, visible: bool) -> Option<S
     // Save update stake pool event log to stable memory
     save_change_staking_pool_visible_event_log(id, visible);
 
-    None
+    Ok(())
   })
 }
 
 #[ic_cdk::update]
-#[has_permission_option("staking::pool::update")]
+#[has_permission_option("staking::pool::update", audit)]
 fn set_staking_pool_status(id: StakingPoolId, status: String) -> Option<String> {
-  STAKING_POOL_MAP.with(|map| {
-    let mut map = map.borrow_mut();
-    let pool = map.get(&id);
+  change_staking_pool_status(id, status).err().map(|e| e.to_string())
+}
 
-    if pool.is_none() {
-      return Some("Staking pool not found".to_string()); // Pool not found
-    }
+#[ic_cdk::update]
+#[has_permission_result("staking::pool::update", audit)]
+fn set_staking_pool_status_v2(id: StakingPoolId, status: String) -> Result<(), StakingError> {
+  change_staking_pool_status(id, status)
+}
 
-    let mut pool = pool.unwrap();
+fn change_staking_pool_status(id: StakingPoolId, status: String) -> Result<(), StakingError> {
+  STAKING_POOL_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    let mut pool = map.get(&id).ok_or(StakingError::PoolNotFound { pool_id: id })?;
 
-    match StakingPoolStatus::from_str(&status) {
-      Ok(new_status) => {
-        let result = pool.set_status(new_status);
+    let new_status = StakingPoolStatus::from_str(&status).map_err(|_| StakingError::invalid_argument("Invalid status"))?;
 
-        if result.is_some() {
-          return result; // If there was an error in setting status, return the error message
-        }
+    // If there was an error in setting status, return the error message
+    if let Some(message) = pool.set_status(new_status) {
+      return Err(StakingError::invalid_argument(message));
+    }
 
-        map.insert(pool.get_id(), pool.clone());
+    map.insert(pool.get_id(), pool.clone());
 
-        save_change_staking_pool_status_event_log(pool.get_id(), pool.get_status());
-        None
-      }
-      Err(_) => Some("Invalid status".to_string()), // Invalid status string
-    }
+    save_change_staking_pool_status_event_log(pool.get_id(), pool.get_status());
+    Ok(())
   })
 }
 
 #[ic_cdk::update]
-#[has_permission_option("staking::pool::update")]
+#[has_permission_option("staking::pool::update", audit)]
 fn update_staking_pool(dto: StakingPoolUpdateDto) -> Option<String> {
-  STAKING_POOL_MAP.with(|map| {
-    let mut map = map.borrow_mut();
-    let existing_pool = map.get(&dto.id);
+  apply_staking_pool_update(dto).err().map(|e| e.to_string())
+}
 
-    if existing_pool.is_none() {
-      return Some("Staking pool not found".to_string()); // Pool not found
-    }
+#[ic_cdk::update]
+#[has_permission_result("staking::pool::update", audit)]
+fn update_staking_pool_v2(dto: StakingPoolUpdateDto) -> Result<(), StakingError> {
+  apply_staking_pool_update(dto)
+}
 
-    let mut staking_pool = existing_pool.unwrap();
+fn apply_staking_pool_update(dto: StakingPoolUpdateDto) -> Result<(), StakingError> {
+  STAKING_POOL_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    let mut staking_pool = map.get(&dto.id).ok_or(StakingError::PoolNotFound { pool_id: dto.id })?;
 
     // Update the pool with new values from the DTO
-    let result = staking_pool.update(&dto.add_dto);
-
-    if result.is_some() {
-      return result; // If there was an error in updating, return the error message
+    if let Some(message) = staking_pool.update(&dto.add_dto) {
+      return Err(StakingError::invalid_argument(message));
     }
 
     map.insert(staking_pool.get_id(), staking_pool.clone());
 
     save_update_staking_pool_event_log(&staking_pool);
-    None
+    Ok(())
   })
 }
 
@@ -145,10 +165,12 @@ fn update_staking_pool(dto: StakingPoolUpdateDto) -> Option<String> {
 fn query_pool_account_ids(pool_id: StakingPoolId) -> StakingPoolAccountIds {
   let nns_neuron_account_id = generate_staking_pool_neuron_account(pool_id).to_hex();
   let staking_pool_account_id = generate_staking_pool_account_identifier(pool_id).to_hex();
+  let staking_pool_icrc1_account = generate_staking_pool_icrc1_address(pool_id);
 
   StakingPoolAccountIds {
     pool_id,
     nns_neuron_account_id,
     staking_pool_account_id,
+    staking_pool_icrc1_account,
   }
 }
@@ -12,6 +12,7 @@ use types::{
 
 use crate::{
   account::{badge_utils::add_staker_badge, stable_structures::StakingAccount},
+  errors::StakingError,
   on_chain::address::generate_staking_pool_chain_address,
   pool_transaction_record::utils::record_stake_transaction,
 };
@@ -174,13 +175,13 @@ impl StakingPool {
   }
 
   /// Verify whether the stake pool can currently accept stakes of the corresponding amount
-  pub fn validate_and_lock_size(&mut self, staking_amount: E8S) -> Result<Self, String> {
+  pub fn validate_and_lock_size(&mut self, staking_amount: E8S) -> Result<Self, StakingError> {
     STAKING_POOL_MAP.with(|map| {
       let mut map = map.borrow_mut();
       let pool = map.get(&self.get_id());
 
       if pool.is_none() {
-        return Err(format!("Staking pool with ID {} not found", self.get_id()));
+        return Err(StakingError::PoolNotFound { pool_id: self.get_id() });
       }
 
       let mut pool = pool.unwrap();
@@ -190,21 +191,21 @@ impl StakingPool {
 
       // The staking pool is not visible on the client, or not in open state
       if status != StakingPoolStatus::Open || !client_visible {
-        return Err(format!(
-          "Staking pool is not open, current status: {:?}, and client visible is {}",
-          status, client_visible
-        ));
+        return Err(StakingError::PoolNotOpen {
+          pool_id: pool.get_id(),
+          status: status.to_string(),
+          client_visible,
+        });
       }
 
       let remain_size = pool.get_pool_size() - pool.get_staked_amount() - pool.get_locked_size();
 
       // Try to Lock the staking pool amount
       if remain_size < staking_amount {
-        return Err(format!(
-          "Staking pool size is not enough, current remain size: {}, and staking amount: {}",
-          e8s_to_value(remain_size).to_string(),
-          e8s_to_value(staking_amount).to_string()
-        ));
+        return Err(StakingError::PoolFull {
+          pool_id: pool.get_id(),
+          max_stakeable_amount: remain_size,
+        });
       }
 
       // Lock the stake pool amount
@@ -391,6 +392,24 @@ impl StakingPool {
     })
   }
 
+  /// Release funds returned from the NNS neuron, returns the amount actually released
+  pub fn release_nns_neuron_occupies_funds(&self, amount: E8S) -> Result<E8S, String> {
+    STAKING_POOL_MAP.with(|map| {
+      let mut map = map.borrow_mut();
+
+      let mut pool = map.get(&self.get_id()).ok_or_else(|| "Staking pool not found".to_string())?;
+
+      let occupies_funds = pool.get_nns_neuron_occupies_funds();
+      let released_amount = amount.min(occupies_funds);
+
+      pool.set_nns_neuron_occupies_funds(occupies_funds - released_amount);
+
+      map.insert(pool.get_id(), pool);
+
+      Ok(released_amount)
+    })
+  }
+
   pub fn get_jackpot_occupies_funds(&self) -> E8S {
     self.jackpot_occupies_funds.unwrap_or_default()
   }
@@ -594,15 +613,14 @@ impl TermConfig {
   }
 
   /// Verify the stake period
-  pub fn validate_term(&self, term: u16) -> Result<(), String> {
+  pub fn validate_term(&self, term: u16) -> Result<(), StakingError> {
     // Verify the stake period
-    if self.get_min_term() > term {
-      return Err(format!("Minimum staking term is {} days", self.get_min_term()));
-    }
-
-    // Verify the stake period
-    if self.get_max_term() < term {
-      return Err(format!("Maximum staking term is {} days", self.get_max_term()));
+    if self.get_min_term() > term || self.get_max_term() < term {
+      return Err(StakingError::TermInvalid {
+        staking_days: term,
+        min_term: self.get_min_term(),
+        max_term: self.get_max_term(),
+      });
     }
 
     Ok(())
@@ -632,36 +650,51 @@ impl LimitConfig {
     self.step_amount.unwrap_or_default()
   }
 
-  pub fn validate_stake_amount(&self, amount: E8S, current_user_in_stake_in_this_pool_accounts: &Vec<StakingAccount>) -> Result<(), String> {
+  pub fn validate_stake_amount(&self, amount: E8S, current_user_in_stake_in_this_pool_accounts: &[StakingAccount]) -> Result<(), StakingError> {
+    let already_staked_amount = current_user_in_stake_in_this_pool_accounts
+      .iter()
+      .map(|account| account.get_staked_amount())
+      .sum::<E8S>();
+
+    let invalid_amount = |message: String| StakingError::StakeAmountInvalid {
+      amount,
+      min_stake_amount: self.get_min_stake_amount_per_user(),
+      max_stake_amount: self.get_max_stake_amount_per_user(),
+      step_amount: self.get_step_amount(),
+      max_stakeable_amount: self.get_max_stake_amount_per_user().saturating_sub(already_staked_amount),
+      message,
+    };
+
     // Check the minimum stake amount
     if self.get_min_stake_amount_per_user() > amount {
-      return Err(format!("Minimum staking amount is {} ICP", e8s_to_value(self.get_min_stake_amount_per_user())));
+      return Err(invalid_amount(format!(
+        "Minimum staking amount is {} ICP",
+        e8s_to_value(self.get_min_stake_amount_per_user())
+      )));
     }
 
     // Verify the maximum amount of stake
     if self.get_max_stake_amount_per_user() < amount {
-      return Err(format!("Maximum staking amount is {} ICP", e8s_to_value(self.get_max_stake_amount_per_user())));
+      return Err(invalid_amount(format!(
+        "Maximum staking amount is {} ICP",
+        e8s_to_value(self.get_max_stake_amount_per_user())
+      )));
     }
 
     // Verify the stake amount step
     if (amount - self.get_min_stake_amount_per_user()) % self.get_step_amount() != 0 {
-      return Err(format!(
+      return Err(invalid_amount(format!(
         "The amount exceeding the minimum staking amount must be a multiple of {} ICP.",
         e8s_to_value(self.get_step_amount())
-      ));
+      )));
     }
 
-    let already_staked_amount = current_user_in_stake_in_this_pool_accounts
-      .iter()
-      .map(|account| account.get_staked_amount())
-      .sum::<E8S>();
-
     // Verify whether the sum of the amount that the user has staked in the Staking pool and the current staked amount exceeds the maximum staked amount
     if already_staked_amount + amount > self.get_max_stake_amount_per_user() {
-      return Err(format!(
+      return Err(invalid_amount(format!(
         "The total staking amount exceeds the maximum staking amount of {} ICP",
         e8s_to_value(self.get_max_stake_amount_per_user())
-      ));
+      )));
     }
 
     Ok(())
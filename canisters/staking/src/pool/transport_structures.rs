@@ -2,6 +2,8 @@ use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use types::{staking::StakingPoolId, EntityId, TimestampNanos, E8S};
 
+use crate::on_chain::address::generate_staking_pool_icrc1_address;
+
 use super::stable_structures::{LimitConfig, RewardConfig, StakingPool, TermConfig};
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -100,6 +102,8 @@ pub struct StakingPoolVo {
   pub id: EntityId,
   /// Staking pool on-chain address
   pub address: String,
+  /// Staking pool address in the ICRC-1 account text encoding
+  pub icrc1_address: String,
   /// Target financing amount，The maximum amount of stake that the stake pool can accommodate
   pub pool_size: E8S,
   /// Staked amount of the staking pool
@@ -147,6 +151,7 @@ impl StakingPoolVo {
     Self {
       id: pool.get_id(),
       address: pool.get_address(),
+      icrc1_address: generate_staking_pool_icrc1_address(pool.get_id()),
       pool_size: pool.get_pool_size(),
       staked_amount: pool.get_staked_amount(),
       stake_user_count: pool.get_stake_user_count(),
@@ -196,4 +201,5 @@ pub struct StakingPoolAccountIds {
   pub pool_id: StakingPoolId,
   pub nns_neuron_account_id: String,
   pub staking_pool_account_id: String,
+  pub staking_pool_icrc1_account: String,
 }
@@ -1,15 +1,20 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, str::FromStr};
 
 use ic_cdk::{api::is_controller, query, update};
 use ic_stable_structures::{memory_manager::MemoryId, StableBTreeMap};
-use stable_structures::{PoolTransactionRecord, PoolTransactionRecords, RecordTypeIndexKey, RecordTypeKey};
-use transport_structures::PoolTransactionQueryParams;
+use stable_structures::{
+  FeeBearer, LedgerFeeRecord, LedgerFeeRecordKey, PoolTransactionHead, PoolTransactionRecord, PoolTransactionRecordKey, PoolTransactionRecords,
+  RecordTypeIndexKey, RecordTypeKey,
+};
+use system_configs_macro::{has_permission, has_permission_result};
+use transport_structures::{LedgerFeeRecordVo, PoolLedgerFeeQueryParams, PoolLedgerFeeTotalsVo, PoolTransactionQueryParams};
 use types::{
   btree_set_entity_index::BTreeSetEntityIndex,
-  pagination::{PageRequest, PageResponse},
+  composite_entity_index::CompositeEntityIndex,
+  pagination::{collect_page, PageRequest, PageResponse},
   stable_structures::Memory,
   staking::StakingPoolId,
-  EntityId, TimestampNanos,
+  TimestampNanos,
 };
 
 use crate::{
@@ -17,8 +22,14 @@ use crate::{
     crud_utils::query_staking_account_with_pool_id,
     stable_structures::{StakingAccount, StakingAccountStatus},
   },
-  memory_ids::{STAKING_POOL_TRANSACTION_RECORD, STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX},
-  pool_transaction_record::utils::{record_stake_transaction, record_unstake_transaction},
+  memory_ids::{
+    STAKING_POOL_LEDGER_FEE_RECORD, STAKING_POOL_TRANSACTION_HEAD, STAKING_POOL_TRANSACTION_RECORD, STAKING_POOL_TRANSACTION_RECORD_ENTRY,
+    STAKING_POOL_TRANSACTION_RECORD_TYPE_COMPOSITE_INDEX, STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX,
+  },
+  pool_transaction_record::utils::{
+    get_pool_transaction_page, get_pool_transaction_page_by_ids, get_record_type_ids, record_stake_transaction, record_unstake_transaction,
+    remove_pool_transaction_records,
+  },
   MEMORY_MANAGER,
 };
 
@@ -27,19 +38,121 @@ pub mod transport_structures;
 pub mod utils;
 
 thread_local! {
-  /// Original data of staked account
-  pub static STAKING_POOL_TRANSACTION_RECORD_MAP: RefCell<StableBTreeMap<StakingPoolId, PoolTransactionRecords, Memory>> = RefCell::new(
+  /// Transaction records of the staking pools, one entry per record
+  pub static STAKING_POOL_TRANSACTION_RECORD_MAP: RefCell<StableBTreeMap<PoolTransactionRecordKey, PoolTransactionRecord, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_TRANSACTION_RECORD_ENTRY))),
+    )
+  );
+
+  /// Head of the transaction records of each staking pool
+  pub static STAKING_POOL_TRANSACTION_HEAD_MAP: RefCell<StableBTreeMap<StakingPoolId, PoolTransactionHead, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_TRANSACTION_HEAD))),
+    )
+  );
+
+  /// All transaction records of each staking pool in a single value, read until they are split by schema version 4
+  pub static LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP: RefCell<StableBTreeMap<StakingPoolId, PoolTransactionRecords, Memory>> = RefCell::new(
     StableBTreeMap::init(
       MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_TRANSACTION_RECORD))),
     )
   );
 
   /// Transaction record type index
-  pub static STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP: RefCell<StableBTreeMap<RecordTypeIndexKey, BTreeSetEntityIndex<RecordTypeIndexKey>, Memory>> = RefCell::new(
+  pub static STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP: RefCell<CompositeEntityIndex<RecordTypeIndexKey>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_TRANSACTION_RECORD_TYPE_COMPOSITE_INDEX))),
+    )
+  );
+
+  /// Set index of the transaction record types, read until the composite index is built
+  pub static LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP: RefCell<StableBTreeMap<RecordTypeIndexKey, BTreeSetEntityIndex<RecordTypeIndexKey>, Memory>> = RefCell::new(
     StableBTreeMap::init(
       MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX))),
     )
   );
+
+  /// Fees charged by the ledger for the staking pool's transfers, by block index
+  pub static STAKING_POOL_LEDGER_FEE_RECORD_MAP: RefCell<StableBTreeMap<LedgerFeeRecordKey, LedgerFeeRecord, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_LEDGER_FEE_RECORD))),
+    )
+  );
+}
+
+/// Query the ledger fees of the staking pool's transfers within the time range, newest first
+fn query_pool_ledger_fees(pool_id: StakingPoolId, start_time: TimestampNanos, end_time: TimestampNanos) -> Vec<LedgerFeeRecord> {
+  STAKING_POOL_LEDGER_FEE_RECORD_MAP.with(|map| {
+    map
+      .borrow()
+      .range(LedgerFeeRecordKey(pool_id, 0)..=LedgerFeeRecordKey(pool_id, u64::MAX))
+      .rev()
+      .map(|(_, record)| record)
+      .filter(|record| record.get_paid_at() >= start_time && record.get_paid_at() <= end_time)
+      .collect()
+  })
+}
+
+/// Query every ledger fee paid for the staking pool's transfers and who bore it
+#[query]
+#[has_permission_result("staking::pool::query")]
+fn get_staking_pool_ledger_fee_page(request: PageRequest<PoolLedgerFeeQueryParams>) -> Result<PageResponse<LedgerFeeRecordVo>, String> {
+  let PageRequest {
+    page,
+    page_size,
+    params: PoolLedgerFeeQueryParams {
+      pool_id,
+      bearer,
+      start_time,
+      end_time,
+    },
+  } = request;
+
+  let bearer = match bearer {
+    Some(bearer) => Some(FeeBearer::from_str(&bearer).map_err(|_| format!("Invalid fee bearer: {}", bearer))?),
+    None => None,
+  };
+
+  let start_time = start_time.unwrap_or_default();
+  let end_time = end_time.unwrap_or(u64::MAX);
+
+  let (total, page_records) = STAKING_POOL_LEDGER_FEE_RECORD_MAP.with(|map| {
+    let map = map.borrow();
+    let matched = map
+      .range(LedgerFeeRecordKey(pool_id, 0)..=LedgerFeeRecordKey(pool_id, u64::MAX))
+      .rev()
+      .map(|(_, record)| record)
+      .filter(|record| record.get_paid_at() >= start_time && record.get_paid_at() <= end_time)
+      .filter(|record| bearer.as_ref().is_none_or(|bearer| record.get_bearer() == *bearer));
+    collect_page(page, page_size, matched, LedgerFeeRecordVo::from)
+  });
+
+  Ok(PageResponse::new(page, page_size, total, page_records))
+}
+
+/// Total ledger fees of the staking pool's transfers within the time range, by the party that bore them
+#[query]
+#[has_permission("staking::pool::query")]
+fn get_staking_pool_ledger_fee_totals(pool_id: StakingPoolId, start_time: TimestampNanos, end_time: TimestampNanos) -> PoolLedgerFeeTotalsVo {
+  let mut totals = PoolLedgerFeeTotalsVo {
+    pool_id,
+    start_time,
+    end_time,
+    ..Default::default()
+  };
+
+  for record in query_pool_ledger_fees(pool_id, start_time, end_time) {
+    totals.transfer_count += 1;
+    totals.total_fee += record.get_fee();
+    match record.get_bearer() {
+      FeeBearer::User => totals.user_fee += record.get_fee(),
+      FeeBearer::Pool => totals.pool_fee += record.get_fee(),
+      FeeBearer::Treasury => totals.treasury_fee += record.get_fee(),
+    }
+  }
+
+  totals
 }
 
 #[query]
@@ -53,38 +166,15 @@ fn get_staking_pool_transaction_record_page(request: PageRequest<PoolTransaction
   if record_type.is_some() {
     let record_type_key = RecordTypeKey::from(record_type.unwrap());
 
-    let entry_ids = STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|index_map| {
-      let index_map = index_map.borrow();
-      let key = RecordTypeIndexKey(pool_id, record_type_key);
-      if let Some(index) = index_map.get(&key) {
-        let ids = index.get_entity_ids();
-        ids.iter().copied().collect::<Vec<EntityId>>()
-      } else {
-        vec![]
-      }
-    });
+    let entry_ids = get_record_type_ids(&RecordTypeIndexKey(pool_id, record_type_key));
 
     if entry_ids.is_empty() {
       return PageResponse::new_empty(page, page_size);
     }
 
-    STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
-      let map = map.borrow();
-      if let Some(records) = map.get(&pool_id) {
-        records.get_page_by_ids(page, page_size, entry_ids)
-      } else {
-        PageResponse::new_empty(page, page_size)
-      }
-    })
+    get_pool_transaction_page_by_ids(pool_id, page, page_size, entry_ids)
   } else {
-    STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
-      let map = map.borrow();
-      if let Some(records) = map.get(&pool_id) {
-        records.get_page(page, page_size)
-      } else {
-        PageResponse::new_empty(page, page_size)
-      }
-    })
+    get_pool_transaction_page(pool_id, page, page_size)
   }
 }
 
@@ -143,10 +233,7 @@ fn refactor_transaction_records(pool_id: StakingPoolId) -> Option<String> {
 
   combined_helpers.sort_by_key(|helper| helper.sort_time);
 
-  STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
-    let mut map = map.borrow_mut();
-    map.remove(&pool_id);
-  });
+  remove_pool_transaction_records(pool_id);
   for helper in combined_helpers {
     let account = &helper.account;
     match helper.category {
@@ -4,13 +4,15 @@ use candid::{CandidType, Decode, Encode, Principal};
 use ic_ledger_types::BlockIndex;
 use ic_stable_structures::{storable::Bound, Storable};
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
 use types::{
   pagination::PageResponse,
   product::ProductId,
-  staking::{PoolTransactionRecordId, StakingAccountId, StakingPoolId},
+  staking::{ExternalDepositId, PoolTransactionRecordId, StakingAccountId, StakingPoolId, StakingRewardId},
   EntityId, TimestampNanos, E8S,
 };
 
+/// All transaction records of a staking pool in a single value, replaced by the per record entries in schema version 4
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct PoolTransactionRecords {
   /// Transaction records of the staking pool, sorted by transaction time
@@ -22,14 +24,6 @@ pub struct PoolTransactionRecords {
 }
 
 impl PoolTransactionRecords {
-  pub fn new_empty(pool_id: StakingPoolId) -> Self {
-    PoolTransactionRecords {
-      pool_id: Some(pool_id),
-      max_record_id: Some(0),
-      transaction_records: Some(BTreeMap::new()),
-    }
-  }
-
   pub fn get_transaction_records(&self) -> &BTreeMap<PoolTransactionRecordId, PoolTransactionRecord> {
     self.transaction_records.as_ref().unwrap()
   }
@@ -50,32 +44,6 @@ impl PoolTransactionRecords {
       .cloned()
   }
 
-  pub fn add_record(&mut self, amount: i64, record_type: RecordType, block_index: BlockIndex, create_time: TimestampNanos) -> PoolTransactionRecord {
-    let newest_record = self.get_newest_transaction_record();
-
-    let new_record = if let Some(record) = newest_record {
-      record.next_record(amount, record_type, block_index, create_time)
-    } else {
-      assert!(amount > 0, "The first transaction record must be a deposit");
-
-      PoolTransactionRecord {
-        id: Some(1),
-        amount: Some(amount),
-        balance: Some(amount as E8S),
-        record_type: Some(record_type),
-        block_index: Some(block_index),
-        created_at: Some(create_time),
-      }
-    };
-
-    let mut transaction_records = self.transaction_records.clone().unwrap();
-    self.max_record_id = Some(new_record.get_id());
-    transaction_records.insert(new_record.get_id(), new_record.clone());
-    self.transaction_records = Some(transaction_records);
-
-    new_record
-  }
-
   pub fn get_page(&self, page: u32, page_size: u32) -> PageResponse<PoolTransactionRecord> {
     let records = self.get_transaction_records();
     let total_count = self.get_max_record_id() as u32;
@@ -103,6 +71,69 @@ impl PoolTransactionRecords {
   }
 }
 
+/// Head of the transaction records of a staking pool, the records are stored one entry per record
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct PoolTransactionHead {
+  pub pool_id: Option<StakingPoolId>,
+  /// Maximum transaction record id of the staking pool
+  pub max_record_id: Option<PoolTransactionRecordId>,
+  /// Balance of the staking pool after the newest transaction record
+  pub balance: Option<E8S>,
+}
+
+impl PoolTransactionHead {
+  pub fn new_empty(pool_id: StakingPoolId) -> Self {
+    PoolTransactionHead {
+      pool_id: Some(pool_id),
+      max_record_id: Some(0),
+      balance: Some(0),
+    }
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_max_record_id(&self) -> PoolTransactionRecordId {
+    self.max_record_id.unwrap_or_default()
+  }
+
+  pub fn get_balance(&self) -> E8S {
+    self.balance.unwrap_or_default()
+  }
+
+  /// Create the next transaction record of the staking pool and move the head to it
+  pub fn add_record(&mut self, amount: i64, record_type: RecordType, block_index: BlockIndex, create_time: TimestampNanos) -> PoolTransactionRecord {
+    let balance = if self.get_max_record_id() == 0 {
+      assert!(amount > 0, "The first transaction record must be a deposit");
+      amount as E8S
+    } else if amount > 0 {
+      self.get_balance().checked_add(amount as E8S).expect("Overflow when adding amount")
+    } else {
+      self.get_balance().checked_sub(-amount as E8S).expect("Overflow when subtracting amount")
+    };
+
+    let new_record = PoolTransactionRecord {
+      id: Some(self.get_max_record_id() + 1),
+      amount: Some(amount),
+      balance: Some(balance),
+      record_type: Some(record_type),
+      block_index: Some(block_index),
+      created_at: Some(create_time),
+    };
+
+    self.max_record_id = new_record.id;
+    self.balance = new_record.balance;
+
+    new_record
+  }
+}
+
+/// Key of the transaction records: (pool ID, record ID).
+/// Encoded as fixed size big-endian bytes so that the stable map keeps the records of a pool in id order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PoolTransactionRecordKey(pub StakingPoolId, pub PoolTransactionRecordId);
+
 /// Virtual transaction records of the pledge pool can be reconciled with the on-chain
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct PoolTransactionRecord {
@@ -118,21 +149,6 @@ pub struct PoolTransactionRecord {
 }
 
 impl PoolTransactionRecord {
-  pub fn next_record(&self, amount: i64, record_type: RecordType, block_index: BlockIndex, create_time: TimestampNanos) -> PoolTransactionRecord {
-    PoolTransactionRecord {
-      id: Some(self.get_id() + 1),
-      amount: Some(amount),
-      balance: Some(if amount > 0 {
-        self.get_balance().checked_add(amount as E8S).expect("Overflow when adding amount")
-      } else {
-        self.get_balance().checked_sub(-amount as E8S).expect("Overflow when subtracting amount")
-      }),
-      record_type: Some(record_type),
-      block_index: Some(block_index),
-      created_at: Some(create_time),
-    }
-  }
-
   pub fn get_id(&self) -> PoolTransactionRecordId {
     self.id.unwrap()
   }
@@ -168,6 +184,20 @@ pub enum RecordType {
   NNSNeuronUnstake { neuron_id: EntityId },
   /// Transaction records generated when transferring to jackpot
   Jackpot { canister_id: Principal, product_id: ProductId },
+  /// Transaction records generated when the maturity of the nns neuron is disbursed to the staking pool
+  NNSMaturityDisburse { neuron_id: EntityId },
+  /// Transaction records generated when the maturity of the nns neuron is staked,
+  /// the pool balance is unchanged and the staked maturity is kept in the neuron
+  NNSMaturityStake { neuron_id: EntityId, staked_maturity_e8s: E8S },
+  /// Transaction records generated when a transfer into the staking pool that we did not make ourselves is adopted into the pool
+  ExternalDeposit(ExternalDepositId),
+  /// Transaction records generated when a staking reward is paid to the user through the account canister,
+  /// the pool balance is unchanged and the paid amount is kept in the record type
+  Reward {
+    account_id: StakingAccountId,
+    reward_id: StakingRewardId,
+    reward_amount: E8S,
+  },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq, PartialOrd, Ord)]
@@ -180,6 +210,10 @@ pub enum RecordTypeKey {
   NNSNeuronStake,
   NNSNeuronUnstake,
   Jackpot,
+  NNSMaturityDisburse,
+  NNSMaturityStake,
+  ExternalDeposit,
+  Reward,
 }
 
 impl From<u8> for RecordTypeKey {
@@ -193,6 +227,10 @@ impl From<u8> for RecordTypeKey {
       5 => RecordTypeKey::NNSNeuronStake,
       6 => RecordTypeKey::NNSNeuronUnstake,
       7 => RecordTypeKey::Jackpot,
+      8 => RecordTypeKey::NNSMaturityDisburse,
+      9 => RecordTypeKey::NNSMaturityStake,
+      10 => RecordTypeKey::ExternalDeposit,
+      11 => RecordTypeKey::Reward,
       _ => ic_cdk::trap(format!("Invalid RecordTypeKey index from u8 with value {}", index)),
     }
   }
@@ -212,6 +250,13 @@ impl From<&RecordType> for RecordTypeKey {
         canister_id: _,
         product_id: _,
       } => RecordTypeKey::Jackpot,
+      RecordType::NNSMaturityDisburse { neuron_id: _ } => RecordTypeKey::NNSMaturityDisburse,
+      RecordType::NNSMaturityStake {
+        neuron_id: _,
+        staked_maturity_e8s: _,
+      } => RecordTypeKey::NNSMaturityStake,
+      RecordType::ExternalDeposit(_) => RecordTypeKey::ExternalDeposit,
+      RecordType::Reward { .. } => RecordTypeKey::Reward,
     }
   }
 }
@@ -219,6 +264,79 @@ impl From<&RecordType> for RecordTypeKey {
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq, Eq, PartialOrd, Ord)]
 pub struct RecordTypeIndexKey(pub StakingPoolId, pub RecordTypeKey);
 
+/// Party that bore the fee of a ledger transfer
+#[derive(EnumString, Display, Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum FeeBearer {
+  /// Paid by the user, out of the wallet or out of the funds the user prepaid
+  #[strum(serialize = "0")]
+  User,
+  /// Paid out of the staking pool's funds
+  #[strum(serialize = "1")]
+  Pool,
+  /// Paid by the payment center, out of the fees it prepaid or out of the penalty it receives
+  #[strum(serialize = "2")]
+  Treasury,
+}
+
+/// Fee the ledger charged for a transfer of the staking pool's business
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LedgerFeeRecord {
+  /// Staking pool whose business the transfer belongs to
+  pub pool_id: Option<StakingPoolId>,
+  /// Block index of the transfer
+  pub block_index: Option<BlockIndex>,
+  /// Fee charged by the ledger (unit: smallest unit of the token)
+  pub fee: Option<E8S>,
+  /// Party that bore the fee
+  pub bearer: Option<FeeBearer>,
+  /// Business operation key of the transfer
+  pub op_key: Option<String>,
+  /// Time the fee was recorded
+  pub paid_at: Option<TimestampNanos>,
+}
+
+impl LedgerFeeRecord {
+  pub fn new(pool_id: StakingPoolId, block_index: BlockIndex, fee: E8S, bearer: FeeBearer, op_key: String, paid_at: TimestampNanos) -> Self {
+    Self {
+      pool_id: Some(pool_id),
+      block_index: Some(block_index),
+      fee: Some(fee),
+      bearer: Some(bearer),
+      op_key: Some(op_key),
+      paid_at: Some(paid_at),
+    }
+  }
+
+  pub fn get_pool_id(&self) -> StakingPoolId {
+    self.pool_id.unwrap_or_default()
+  }
+
+  pub fn get_block_index(&self) -> BlockIndex {
+    self.block_index.unwrap_or_default()
+  }
+
+  pub fn get_fee(&self) -> E8S {
+    self.fee.unwrap_or_default()
+  }
+
+  pub fn get_bearer(&self) -> FeeBearer {
+    self.bearer.clone().unwrap_or(FeeBearer::Pool)
+  }
+
+  pub fn get_op_key(&self) -> String {
+    self.op_key.clone().unwrap_or_default()
+  }
+
+  pub fn get_paid_at(&self) -> TimestampNanos {
+    self.paid_at.unwrap_or_default()
+  }
+}
+
+/// Key of the ledger fee records: (pool ID, block index).
+/// Encoded as fixed size big-endian bytes so that the stable map keeps the fees of a pool in block order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LedgerFeeRecordKey(pub StakingPoolId, pub BlockIndex);
+
 impl Storable for PoolTransactionRecords {
   fn to_bytes(&self) -> Cow<[u8]> {
     Cow::Owned(Encode!(self).unwrap())
@@ -231,6 +349,82 @@ impl Storable for PoolTransactionRecords {
   const BOUND: Bound = Bound::Unbounded;
 }
 
+impl Storable for PoolTransactionHead {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for PoolTransactionRecord {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for PoolTransactionRecordKey {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    let mut bytes = Vec::with_capacity(16);
+    bytes.extend_from_slice(&self.0.to_be_bytes());
+    bytes.extend_from_slice(&self.1.to_be_bytes());
+    Cow::Owned(bytes)
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    let pool_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+    let record_id = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+    Self(pool_id, record_id)
+  }
+
+  const BOUND: Bound = Bound::Bounded {
+    max_size: 16,
+    is_fixed_size: true,
+  };
+}
+
+impl Storable for LedgerFeeRecord {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+impl Storable for LedgerFeeRecordKey {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    let mut bytes = Vec::with_capacity(16);
+    bytes.extend_from_slice(&self.0.to_be_bytes());
+    bytes.extend_from_slice(&self.1.to_be_bytes());
+    Cow::Owned(bytes)
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    let pool_id = u64::from_be_bytes(bytes[0..8].try_into().unwrap());
+    let block_index = u64::from_be_bytes(bytes[8..16].try_into().unwrap());
+    Self(pool_id, block_index)
+  }
+
+  const BOUND: Bound = Bound::Bounded {
+    max_size: 16,
+    is_fixed_size: true,
+  };
+}
+
 impl Storable for RecordTypeIndexKey {
   fn to_bytes(&self) -> Cow<[u8]> {
     Cow::Owned(Encode!(self).unwrap())
@@ -1,9 +1,62 @@
 use candid::CandidType;
+use ic_ledger_types::BlockIndex;
 use serde::{Deserialize, Serialize};
-use types::staking::StakingPoolId;
+use types::{staking::StakingPoolId, TimestampNanos, E8S};
+
+use super::stable_structures::LedgerFeeRecord;
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct PoolTransactionQueryParams {
   pub pool_id: StakingPoolId,
   pub record_type: Option<u8>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct PoolLedgerFeeQueryParams {
+  pub pool_id: StakingPoolId,
+  /// Party that bore the fee，refer to FeeBearer enumerate
+  pub bearer: Option<String>,
+  pub start_time: Option<TimestampNanos>,
+  pub end_time: Option<TimestampNanos>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct LedgerFeeRecordVo {
+  pub pool_id: StakingPoolId,
+  pub block_index: BlockIndex,
+  pub fee: E8S,
+  /// Party that bore the fee，refer to FeeBearer enumerate
+  pub bearer: String,
+  pub op_key: String,
+  pub paid_at: TimestampNanos,
+}
+
+impl From<LedgerFeeRecord> for LedgerFeeRecordVo {
+  fn from(record: LedgerFeeRecord) -> Self {
+    Self {
+      pool_id: record.get_pool_id(),
+      block_index: record.get_block_index(),
+      fee: record.get_fee(),
+      bearer: record.get_bearer().to_string(),
+      op_key: record.get_op_key(),
+      paid_at: record.get_paid_at(),
+    }
+  }
+}
+
+/// Ledger fees of the staking pool's transfers within a time range
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct PoolLedgerFeeTotalsVo {
+  pub pool_id: StakingPoolId,
+  pub start_time: TimestampNanos,
+  pub end_time: TimestampNanos,
+  /// Number of transfers
+  pub transfer_count: u64,
+  pub total_fee: E8S,
+  /// Fees borne by users
+  pub user_fee: E8S,
+  /// Fees borne by the staking pool
+  pub pool_fee: E8S,
+  /// Fees borne by the payment center
+  pub treasury_fee: E8S,
+}
@@ -1,13 +1,148 @@
 use ic_ledger_types::BlockIndex;
-use types::{btree_set_entity_index::add_indexed_id, staking::StakingPoolId, TimestampNanos};
+use types::{
+  btree_set_entity_index, composite_entity_index,
+  pagination::{collect_page, PageResponse},
+  staking::{ExternalDepositId, PoolTransactionRecordId, StakingPoolId},
+  EntityId, TimestampNanos, E8S,
+};
 
-use crate::{account::stable_structures::StakingAccount, nns::stable_structures::NnsStakeExecuteRecord};
+use crate::{
+  account::stable_structures::StakingAccount,
+  migrations::{POOL_TRANSACTION_RECORD_ENTRY_SCHEMA_VERSION, POOL_TRANSACTION_TYPE_INDEX_SCHEMA_VERSION, SCHEMA_MIGRATOR},
+  nns::stable_structures::NnsStakeExecuteRecord,
+  on_chain::transfer::get_pool_ledger,
+  reward::stable_structures::StakingReward,
+};
 
 use super::{
-  stable_structures::{PoolTransactionRecord, PoolTransactionRecords, RecordType, RecordTypeIndexKey, RecordTypeKey},
-  STAKING_POOL_TRANSACTION_RECORD_MAP, STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP,
+  stable_structures::{
+    FeeBearer, LedgerFeeRecord, LedgerFeeRecordKey, PoolTransactionHead, PoolTransactionRecord, PoolTransactionRecordKey, PoolTransactionRecords,
+    RecordType, RecordTypeIndexKey, RecordTypeKey,
+  },
+  LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP, LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP, STAKING_POOL_LEDGER_FEE_RECORD_MAP,
+  STAKING_POOL_TRANSACTION_HEAD_MAP, STAKING_POOL_TRANSACTION_RECORD_MAP, STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP,
 };
 
+/// Split the legacy transaction records of the staking pool into one entry per record,
+/// returns the number of records moved
+pub fn split_legacy_pool_transaction_records(records: &PoolTransactionRecords) -> u64 {
+  let pool_id = records.get_pool_id();
+  STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    for (record_id, record) in records.get_transaction_records() {
+      map.insert(PoolTransactionRecordKey(pool_id, *record_id), record.clone());
+    }
+  });
+
+  let head = PoolTransactionHead {
+    pool_id: Some(pool_id),
+    max_record_id: Some(records.get_max_record_id()),
+    balance: Some(
+      records
+        .get_newest_transaction_record()
+        .map(|record| record.get_balance())
+        .unwrap_or_default(),
+    ),
+  };
+  STAKING_POOL_TRANSACTION_HEAD_MAP.with(|map| map.borrow_mut().insert(pool_id, head));
+
+  records.get_transaction_records().len() as u64
+}
+
+/// The legacy transaction records of the staking pool, when they have not been split yet
+fn get_unsplit_legacy_records(pool_id: StakingPoolId) -> Option<PoolTransactionRecords> {
+  if SCHEMA_MIGRATOR.is_migrated_to(POOL_TRANSACTION_RECORD_ENTRY_SCHEMA_VERSION)
+    || STAKING_POOL_TRANSACTION_HEAD_MAP.with(|map| map.borrow().contains_key(&pool_id))
+  {
+    return None;
+  }
+
+  LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| map.borrow().get(&pool_id))
+}
+
+/// Query a page of the transaction records of the staking pool, newest first
+pub fn get_pool_transaction_page(pool_id: StakingPoolId, page: u32, page_size: u32) -> PageResponse<PoolTransactionRecord> {
+  if let Some(records) = get_unsplit_legacy_records(pool_id) {
+    return records.get_page(page, page_size);
+  }
+
+  let total = STAKING_POOL_TRANSACTION_HEAD_MAP.with(|map| map.borrow().get(&pool_id).map(|head| head.get_max_record_id()).unwrap_or_default());
+  let start = page.saturating_sub(1) as u64 * page_size as u64;
+
+  // The record ids of a pool are dense from 1 to the max record id, so the page is a key range counted from the head
+  let newest_id = total.saturating_sub(start);
+  let oldest_id = newest_id.saturating_sub(page_size as u64) + 1;
+
+  let records = if newest_id == 0 {
+    vec![]
+  } else {
+    STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
+      map
+        .borrow()
+        .range(PoolTransactionRecordKey(pool_id, oldest_id)..=PoolTransactionRecordKey(pool_id, newest_id))
+        .rev()
+        .map(|(_, record)| record)
+        .collect()
+    })
+  };
+
+  PageResponse::new(page, page_size, total as u32, records)
+}
+
+/// Query a page of the transaction records of the staking pool with the ids, newest first
+pub fn get_pool_transaction_page_by_ids(
+  pool_id: StakingPoolId,
+  page: u32,
+  page_size: u32,
+  ids: Vec<PoolTransactionRecordId>,
+) -> PageResponse<PoolTransactionRecord> {
+  if let Some(records) = get_unsplit_legacy_records(pool_id) {
+    return records.get_page_by_ids(page, page_size, ids);
+  }
+
+  let (total, records) = STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
+    let map = map.borrow();
+    collect_page(page, page_size, ids.into_iter().rev(), |id| map.get(&PoolTransactionRecordKey(pool_id, id)))
+  });
+
+  PageResponse::new(page, page_size, total, records.into_iter().flatten().collect())
+}
+
+/// Remove all transaction records of the staking pool
+pub fn remove_pool_transaction_records(pool_id: StakingPoolId) {
+  STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    let keys = map
+      .keys_range(PoolTransactionRecordKey(pool_id, 0)..=PoolTransactionRecordKey(pool_id, u64::MAX))
+      .collect::<Vec<_>>();
+    keys.iter().for_each(|key| {
+      map.remove(key);
+    });
+  });
+  STAKING_POOL_TRANSACTION_HEAD_MAP.with(|map| map.borrow_mut().remove(&pool_id));
+  LEGACY_STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| map.borrow_mut().remove(&pool_id));
+}
+
+/// Record the fee the ledger charged for a transfer of the staking pool, the fee of a block is recorded once
+pub fn record_ledger_fee(pool_id: StakingPoolId, block_index: BlockIndex, fee: E8S, bearer: FeeBearer, op_key: String) {
+  STAKING_POOL_LEDGER_FEE_RECORD_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    let key = LedgerFeeRecordKey(pool_id, block_index);
+    if !map.contains_key(&key) {
+      map.insert(key, LedgerFeeRecord::new(pool_id, block_index, fee, bearer, op_key, ic_cdk::api::time()));
+    }
+  });
+}
+
+/// Fee the ledger charged for the staking pool's transfer in the block.
+/// Transfers made before fees were recorded fall back to the current fee of the pool's ledger.
+fn get_block_fee(pool_id: StakingPoolId, block_index: BlockIndex) -> Result<E8S, String> {
+  match STAKING_POOL_LEDGER_FEE_RECORD_MAP.with(|map| map.borrow().get(&LedgerFeeRecordKey(pool_id, block_index))) {
+    Some(record) => Ok(record.get_fee()),
+    None => Ok(get_pool_ledger(pool_id)?.get_fee()),
+  }
+}
+
 /// Record a transaction for a staking pool
 fn record_transaction(
   pool_id: StakingPoolId,
@@ -16,18 +151,49 @@ fn record_transaction(
   block_index: BlockIndex,
   create_time: TimestampNanos,
 ) -> Result<PoolTransactionRecord, String> {
+  // Records of a pool that the migration has not reached yet are split before the pool takes a new record
+  if let Some(records) = get_unsplit_legacy_records(pool_id) {
+    split_legacy_pool_transaction_records(&records);
+  }
+
+  let mut head = STAKING_POOL_TRANSACTION_HEAD_MAP.with(|map| map.borrow().get(&pool_id).unwrap_or_else(|| PoolTransactionHead::new_empty(pool_id)));
+  let new_record = head.add_record(amount, record_type.clone(), block_index, create_time);
+
   STAKING_POOL_TRANSACTION_RECORD_MAP.with(|map| {
-    let mut map = map.borrow_mut();
-    let mut records = map.get(&pool_id).unwrap_or_else(|| PoolTransactionRecords::new_empty(pool_id));
+    map
+      .borrow_mut()
+      .insert(PoolTransactionRecordKey(pool_id, new_record.get_id()), new_record.clone())
+  });
+  STAKING_POOL_TRANSACTION_HEAD_MAP.with(|map| map.borrow_mut().insert(pool_id, head));
 
-    let new_record = records.add_record(amount, record_type.clone(), block_index, create_time);
-    map.insert(pool_id, records.clone());
+  index_record_type(pool_id, &new_record);
 
-    STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP
-      .with(|index_map| add_indexed_id(index_map, &RecordTypeIndexKey(pool_id, RecordTypeKey::from(record_type)), new_record.get_id()));
+  Ok(new_record)
+}
 
-    Ok(new_record)
-  })
+/// Add the transaction record to the record type index, the set index is kept up to date until the composite index is built
+fn index_record_type(pool_id: StakingPoolId, record: &PoolTransactionRecord) {
+  let key = RecordTypeIndexKey(pool_id, RecordTypeKey::from(&record.get_record_type()));
+  STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|index_map| composite_entity_index::add_indexed_id(index_map, &key, record.get_id()));
+
+  if !SCHEMA_MIGRATOR.is_migrated_to(POOL_TRANSACTION_TYPE_INDEX_SCHEMA_VERSION) {
+    LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|index_map| btree_set_entity_index::add_indexed_id(index_map, &key, record.get_id()));
+  }
+}
+
+/// Get the ids of the staking pool's transaction records of the record type in ascending order
+pub fn get_record_type_ids(key: &RecordTypeIndexKey) -> Vec<EntityId> {
+  if SCHEMA_MIGRATOR.is_migrated_to(POOL_TRANSACTION_TYPE_INDEX_SCHEMA_VERSION) {
+    STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|index_map| composite_entity_index::get_indexed_ids(index_map, key))
+  } else {
+    LEGACY_STAKING_POOL_TRANSACTION_RECORD_TYPE_INDEX_MAP.with(|index_map| {
+      index_map
+        .borrow()
+        .get(key)
+        .map(|index| index.get_entity_ids().iter().copied().collect())
+        .unwrap_or_default()
+    })
+  }
 }
 
 pub fn record_stake_transaction(account: &StakingAccount) -> Result<(), String> {
@@ -39,11 +205,11 @@ pub fn record_stake_transaction(account: &StakingAccount) -> Result<(), String>
     account.get_stake_account_to_pool_onchain_tx_id(),
     account.get_stake_time(),
   )?;
-  // Record the pay center prepaid fee transaction of the staking pool
+  // Record the prepaid fee transaction of the staking pool, the stake covers the fees of the unstake transfer and of the transfer out of the staking account
   record_transaction(
     account.get_pool_id(),
     &RecordType::PrepaidFee(staking_transaction.get_id()),
-    20_000,
+    (get_block_fee(account.get_pool_id(), account.get_stake_account_to_pool_onchain_tx_id())? * 2) as i64,
     account.get_stake_account_to_pool_onchain_tx_id(),
     account.get_stake_time(),
   )?;
@@ -64,18 +230,27 @@ pub fn record_unstake_transaction(account: &StakingAccount) -> Result<(), String
   record_transaction(
     account.get_pool_id(),
     &RecordType::Fee(unstaking_transaction.get_id()),
-    -10_000,
+    -(get_block_fee(account.get_pool_id(), account.get_release_onchain_tx_id())? as i64),
     account.get_release_onchain_tx_id(),
     release_time,
   )?;
 
   if account.get_penalty_amount() > 0 {
-    // Record the penalty transaction of the staking pool
-    record_transaction(
+    // Record the penalty transaction of the staking pool, the fee of the penalty transfer is taken out of the penalty
+    let penalty_block_index = account.get_penalty_onchain_tx_id();
+    let penalty_fee = get_block_fee(account.get_pool_id(), penalty_block_index)?;
+    let penalty_transaction = record_transaction(
       account.get_pool_id(),
       &RecordType::EarlyUnstakePenalty(account.get_id()),
-      -(account.get_penalty_amount() as i64),
-      account.get_release_onchain_tx_id(),
+      -(account.get_penalty_amount().saturating_sub(penalty_fee) as i64),
+      penalty_block_index,
+      release_time,
+    )?;
+    record_transaction(
+      account.get_pool_id(),
+      &RecordType::Fee(penalty_transaction.get_id()),
+      -(penalty_fee as i64),
+      penalty_block_index,
       release_time,
     )?;
   }
@@ -100,7 +275,7 @@ pub fn record_stake_to_neuron_transaction(execute_record: &NnsStakeExecuteRecord
   record_transaction(
     execute_record.get_pool_id(),
     &RecordType::Fee(nns_neuron_transaction.get_id()),
-    -10_000,
+    -(get_block_fee(execute_record.get_pool_id(), execute_record.get_transfer_block_index())? as i64),
     execute_record.get_transfer_block_index(),
     execute_time,
   )?;
@@ -108,21 +283,99 @@ pub fn record_stake_to_neuron_transaction(execute_record: &NnsStakeExecuteRecord
   Ok(())
 }
 
+/// Record the funds disbursed from the NNS neuron, split into the returned principal and the NNS yield
 pub fn record_nns_unstake_transaction(
   pool_id: StakingPoolId,
   neuron_id: u64,
-  amount: u64,
+  principal_amount: E8S,
+  yield_amount: E8S,
   block_index: BlockIndex,
   disburse_time: TimestampNanos,
 ) -> Result<(), String> {
   let nns_unstake_transaction = record_transaction(
     pool_id,
     &RecordType::NNSNeuronUnstake { neuron_id },
-    (amount + 10_000) as i64,
+    principal_amount as i64,
+    block_index,
+    disburse_time,
+  )?;
+
+  if yield_amount > 0 {
+    record_transaction(
+      pool_id,
+      &RecordType::NNSMaturityDisburse { neuron_id },
+      yield_amount as i64,
+      block_index,
+      disburse_time,
+    )?;
+  }
+
+  record_transaction(
+    pool_id,
+    &RecordType::Fee(nns_unstake_transaction.get_id()),
+    -(get_block_fee(pool_id, block_index)? as i64),
     block_index,
     disburse_time,
   )?;
+  Ok(())
+}
+
+/// Record the maturity disbursed from the NNS neuron to the staking pool
+pub fn record_nns_maturity_disburse_transaction(
+  pool_id: StakingPoolId,
+  neuron_id: u64,
+  amount: E8S,
+  block_index: BlockIndex,
+  disburse_time: TimestampNanos,
+) -> Result<(), String> {
+  record_transaction(pool_id, &RecordType::NNSMaturityDisburse { neuron_id }, amount as i64, block_index, disburse_time)?;
+  Ok(())
+}
+
+/// Record the maturity staked in the NNS neuron, which is the pool's yield that stays in the neuron
+pub fn record_nns_maturity_stake_transaction(
+  pool_id: StakingPoolId,
+  neuron_id: u64,
+  staked_maturity_e8s: E8S,
+  stake_time: TimestampNanos,
+) -> Result<(), String> {
+  record_transaction(
+    pool_id,
+    &RecordType::NNSMaturityStake {
+      neuron_id,
+      staked_maturity_e8s,
+    },
+    0,
+    0,
+    stake_time,
+  )?;
+  Ok(())
+}
 
-  record_transaction(pool_id, &RecordType::Fee(nns_unstake_transaction.get_id()), -10_000, block_index, disburse_time)?;
+/// Record an external deposit adopted into the staking pool
+pub fn record_external_deposit_transaction(
+  pool_id: StakingPoolId,
+  deposit_id: ExternalDepositId,
+  amount: E8S,
+  block_index: BlockIndex,
+  adopt_time: TimestampNanos,
+) -> Result<(), String> {
+  record_transaction(pool_id, &RecordType::ExternalDeposit(deposit_id), amount as i64, block_index, adopt_time)?;
+  Ok(())
+}
+
+/// Record a staking reward paid to the user, which is paid through the account canister and not out of the pool's ledger funds
+pub fn record_reward_transaction(reward: &StakingReward, paid_time: TimestampNanos) -> Result<(), String> {
+  record_transaction(
+    reward.get_pool_id(),
+    &RecordType::Reward {
+      account_id: reward.get_account_id(),
+      reward_id: reward.get_id(),
+      reward_amount: reward.get_reward_amount(),
+    },
+    0,
+    0,
+    paid_time,
+  )?;
   Ok(())
 }
@@ -1,9 +1,14 @@
 use system_configs_macro::has_permission;
-use types::entities::get_indexed_ids;
+use types::pagination::collect_page;
 
 use super::{
-  transport_structures::{StakingRewardPageRequest, StakingRewardPageResponse, StakingRewardQueryParams, StakingRewardVo},
-  STAKING_ACCOUNT_REWARD_INDEX_MAP, STAKING_POOL_REWARD_INDEX_MAP, STAKING_REWARD_MAP, STAKING_USER_REWARD_INDEX_MAP,
+  stable_structures::StakingReward,
+  transport_structures::{
+    StakingRewardCursorPageRequest, StakingRewardCursorPageResponse, StakingRewardPageRequest, StakingRewardPageResponse, StakingRewardQueryParams,
+    StakingRewardVo,
+  },
+  utils::{get_account_reward_ids, get_pool_reward_ids, get_user_reward_ids},
+  STAKING_REWARD_MAP,
 };
 
 #[ic_cdk::query]
@@ -22,61 +27,84 @@ fn query_staking_rewords(request: StakingRewardPageRequest) -> StakingRewardPage
     },
   } = request;
 
-  let indexed_records = STAKING_REWARD_MAP.with(|map| {
-    let map = map.borrow();
+  let is_match = |record: &StakingReward| {
+    (pool_id == 0 || record.get_pool_id() == pool_id)
+      && (account_id == 0 || record.get_account_id() == account_id)
+      && (user_id.is_empty() || record.get_owner().contains(&user_id))
+      && (status.is_empty() || record.get_status().to_string() == status)
+      && (start_time == 0 || record.get_create_at() >= start_time)
+      && (end_time == 0 || record.get_create_at() <= end_time)
+  };
+  let to_record = |record: StakingReward| StakingRewardVo::from_staking_reward(&record);
+
+  // Filter the data according to the passed in index, newest first, only the requested page is kept
+  let indexed_ids = if user_id.len() == 63 {
+    Some(get_user_reward_ids(&user_id))
+  } else if account_id > 0 {
+    Some(get_account_reward_ids(&account_id))
+  } else if pool_id > 0 {
+    Some(get_pool_reward_ids(&pool_id))
+  } else {
+    None
+  };
 
-    // Filter the data according to the passed in index
-    if user_id.len() == 63 {
-      STAKING_USER_REWARD_INDEX_MAP
-        .with(|m| get_indexed_ids(m, &user_id))
-        .iter()
-        .filter_map(|id| map.get(id))
-        .collect::<Vec<_>>()
-    } else if account_id > 0 {
-      // If the account is passedID，Get the corresponding one from the account index queryID
-      STAKING_ACCOUNT_REWARD_INDEX_MAP
-        .with(|m| get_indexed_ids(m, &account_id))
-        .iter()
-        .filter_map(|id| map.get(id))
-        .collect::<Vec<_>>()
-    } else if pool_id > 0 {
-      // If the stake pool is passedID，Get the corresponding one from the staking pool index queryID
-      STAKING_POOL_REWARD_INDEX_MAP
-        .with(|m| get_indexed_ids(m, &pool_id))
-        .iter()
-        .filter_map(|id| map.get(id))
-        .collect::<Vec<_>>()
-    } else {
+  let (total, records) = STAKING_REWARD_MAP.with(|map| {
+    let map = map.borrow();
+    match indexed_ids {
+      Some(ids) => collect_page(page, page_size, ids.into_iter().rev().filter_map(|id| map.get(&id)).filter(is_match), to_record),
       // If no indexes are passed in，Query from all reward data
-      map.iter().map(|(_, record)| record).collect::<Vec<_>>()
+      None => collect_page(page, page_size, map.values().rev().filter(is_match), to_record),
     }
   });
 
-  let filtered_records = indexed_records
-    .into_iter()
-    .filter(|record| {
-      (pool_id == 0 || record.get_pool_id() == pool_id)
-        && (account_id == 0 || record.get_account_id() == account_id)
-        && (user_id.is_empty() || record.get_owner().contains(&user_id))
-        && (status.is_empty() || record.get_status().to_string() == status)
-        && (start_time == 0 || record.get_create_at() >= start_time)
-        && (end_time == 0 || record.get_create_at() <= end_time)
-    })
-    .collect::<Vec<_>>();
-
-  let total = filtered_records.len() as u32;
-  let start = (page - 1) * page_size;
-
   StakingRewardPageResponse {
     total,
     page,
     page_size,
-    records: filtered_records
-      .iter()
-      .rev()
-      .skip(start as usize)
-      .take(page_size as usize)
-      .map(|record| StakingRewardVo::from_staking_reward(record))
-      .collect(),
+    records,
   }
 }
+
+/// Stream the staking rewards page by page, newest first unless ascending is requested
+#[ic_cdk::query]
+#[has_permission("staking::reward::query")]
+fn query_staking_rewards_by_cursor(request: StakingRewardCursorPageRequest) -> Result<StakingRewardCursorPageResponse, String> {
+  let position = request.position(&["id", "created_at"])?;
+  let StakingRewardQueryParams {
+    pool_id,
+    account_id,
+    user_id,
+    status,
+    start_time,
+    end_time,
+  } = request.params;
+
+  let to_record = |record: &StakingReward| {
+    ((pool_id == 0 || record.get_pool_id() == pool_id)
+      && (account_id == 0 || record.get_account_id() == account_id)
+      && (user_id.is_empty() || record.get_owner().contains(&user_id))
+      && (status.is_empty() || record.get_status().to_string() == status)
+      && (start_time == 0 || record.get_create_at() >= start_time)
+      && (end_time == 0 || record.get_create_at() <= end_time))
+      .then(|| StakingRewardVo::from_staking_reward(record))
+  };
+
+  // Narrow the scan down with the most selective index passed in
+  let indexed_ids = if user_id.len() == 63 {
+    Some(get_user_reward_ids(&user_id))
+  } else if account_id > 0 {
+    Some(get_account_reward_ids(&account_id))
+  } else if pool_id > 0 {
+    Some(get_pool_reward_ids(&pool_id))
+  } else {
+    None
+  };
+
+  STAKING_REWARD_MAP.with(|map| {
+    let map = map.borrow();
+    match indexed_ids {
+      Some(ids) => Ok(position.scan_ids(ids.into_iter(), |id| map.get(&id), to_record)),
+      None => Ok(position.scan_map(&map, to_record)),
+    }
+  })
+}
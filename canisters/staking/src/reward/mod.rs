@@ -4,6 +4,7 @@ use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
 use stable_key::StakingAccountUserRewardDateIndexKey;
 use stable_structures::StakingReward;
 use types::{
+  composite_entity_index::CompositeEntityIndex,
   entities::EntityIndex,
   stable_structures::Memory,
   staking::{StakingAccountId, StakingPoolId, StakingRewardId},
@@ -12,8 +13,8 @@ use types::{
 
 use crate::{
   memory_ids::{
-    STAKING_ACCOUNT_REWARD_INDEX, STAKING_POOL_REWARD_INDEX, STAKING_REWARD, STAKING_REWARD_SEQ, STAKING_USER_ACCOUNT_REWARD_DATE_INDEX,
-    STAKING_USER_REWARD_INDEX,
+    STAKING_ACCOUNT_REWARD_COMPOSITE_INDEX, STAKING_ACCOUNT_REWARD_INDEX, STAKING_POOL_REWARD_COMPOSITE_INDEX, STAKING_POOL_REWARD_INDEX,
+    STAKING_REWARD, STAKING_REWARD_SEQ, STAKING_USER_ACCOUNT_REWARD_DATE_INDEX, STAKING_USER_REWARD_COMPOSITE_INDEX, STAKING_USER_REWARD_INDEX,
   },
   MEMORY_MANAGER,
 };
@@ -36,21 +37,42 @@ thread_local! {
   );
 
   /// Reward index received in staked account
-  pub static STAKING_ACCOUNT_REWARD_INDEX_MAP: RefCell<StableBTreeMap<StakingAccountId, EntityIndex<StakingAccountId>, Memory>> = RefCell::new(
+  pub static STAKING_ACCOUNT_REWARD_INDEX_MAP: RefCell<CompositeEntityIndex<StakingAccountId>> = RefCell::new(
     StableBTreeMap::init(
-      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_ACCOUNT_REWARD_INDEX))),
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_ACCOUNT_REWARD_COMPOSITE_INDEX))),
     )
   );
 
   /// User Reward Index
-  pub static STAKING_USER_REWARD_INDEX_MAP: RefCell<StableBTreeMap<UserId, EntityIndex<UserId>, Memory>> = RefCell::new(
+  pub static STAKING_USER_REWARD_INDEX_MAP: RefCell<CompositeEntityIndex<UserId>> = RefCell::new(
     StableBTreeMap::init(
-      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_USER_REWARD_INDEX))),
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_USER_REWARD_COMPOSITE_INDEX))),
     )
   );
 
   /// Staking pool reward index
-  pub static STAKING_POOL_REWARD_INDEX_MAP: RefCell<StableBTreeMap<StakingPoolId, EntityIndex<StakingPoolId>, Memory>> = RefCell::new(
+  pub static STAKING_POOL_REWARD_INDEX_MAP: RefCell<CompositeEntityIndex<StakingPoolId>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_REWARD_COMPOSITE_INDEX))),
+    )
+  );
+
+  /// Vector reward index of staked accounts, read until the composite indexes are built by schema version 2
+  pub static LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP: RefCell<StableBTreeMap<StakingAccountId, EntityIndex<StakingAccountId>, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_ACCOUNT_REWARD_INDEX))),
+    )
+  );
+
+  /// Vector reward index of users, read until the composite indexes are built by schema version 2
+  pub static LEGACY_STAKING_USER_REWARD_INDEX_MAP: RefCell<StableBTreeMap<UserId, EntityIndex<UserId>, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_USER_REWARD_INDEX))),
+    )
+  );
+
+  /// Vector reward index of staking pools, read until the composite indexes are built by schema version 2
+  pub static LEGACY_STAKING_POOL_REWARD_INDEX_MAP: RefCell<StableBTreeMap<StakingPoolId, EntityIndex<StakingPoolId>, Memory>> = RefCell::new(
     StableBTreeMap::init(
       MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_POOL_REWARD_INDEX))),
     )
@@ -7,7 +7,6 @@ use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 use types::{
   date::YearMonthDay,
-  entities::add_indexed_id,
   product::e8s_to_value,
   stable_structures::{new_entity_id, MetaData},
   staking::{StakingAccountId, StakingRewardId},
@@ -16,7 +15,7 @@ use types::{
 
 use crate::{account::stable_structures::StakingAccount, pool::stable_structures::RewardCrypto};
 
-use super::{STAKING_ACCOUNT_REWARD_INDEX_MAP, STAKING_POOL_REWARD_INDEX_MAP, STAKING_REWARD_ID, STAKING_REWARD_MAP, STAKING_USER_REWARD_INDEX_MAP};
+use super::{utils::add_reward_indexes, STAKING_REWARD_ID, STAKING_REWARD_MAP};
 
 /// Staking reward data structure，use for storage the Stake Reward information
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
@@ -93,9 +92,7 @@ impl StakingReward {
       map.borrow_mut().insert(reward.get_id(), reward.clone());
 
       // 3.2 add index
-      STAKING_POOL_REWARD_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &reward.get_pool_id(), reward.get_id()));
-      STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &reward.get_account_id(), reward.get_id()));
-      STAKING_USER_REWARD_INDEX_MAP.with(|index_map| add_indexed_id(index_map, &reward.get_owner(), reward.get_id()));
+      add_reward_indexes(&reward);
 
       // 3.3 update the reward record for the account
       let updated_account = StakingAccount::update_reward(&reward, reward.get_create_at());
@@ -1,7 +1,7 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use types::{
-  pagination::{PageRequest, PageResponse},
+  pagination::{CursorPageRequest, CursorPageResponse, PageRequest, PageResponse},
   staking::{StakingAccountId, StakingPoolId, StakingRewardId},
   TimestampNanos, E8S,
 };
@@ -34,6 +34,8 @@ pub struct StakingRewardQueryParams {
 
 pub type StakingRewardPageRequest = PageRequest<StakingRewardQueryParams>;
 pub type StakingRewardPageResponse = PageResponse<StakingRewardVo>;
+pub type StakingRewardCursorPageRequest = CursorPageRequest<StakingRewardQueryParams>;
+pub type StakingRewardCursorPageResponse = CursorPageResponse<StakingRewardVo>;
 
 impl StakingRewardVo {
   pub fn from_staking_reward(reward: &StakingReward) -> Self {
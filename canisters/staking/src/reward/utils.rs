@@ -1,19 +1,26 @@
 use candid::Principal;
 use common_canisters::account::{Crypto, Result25};
-use types::{date::YearMonthDay, product::generate_staking_reward_payment_transaction_id, sys::ExteralCanisterLabels};
+use types::{
+  composite_entity_index, date::YearMonthDay, entities, product::generate_staking_reward_payment_transaction_id, staking::StakingAccountId,
+  staking::StakingPoolId, sys::ExteralCanisterLabels, EntityId, UserId,
+};
 
 use crate::{
   account::stable_structures::StakingAccount,
   event_log::stake_reward_events::{save_reward_distribute_event, save_reward_received_event},
   guard_keys::get_distribute_reward_guard_key,
+  migrations::{COMPOSITE_REWARD_INDEX_SCHEMA_VERSION, SCHEMA_MIGRATOR},
   parallel_guard::EntryGuard,
+  pool_transaction_record::utils::record_reward_transaction,
   system_configs::get_exteral_canister_id,
 };
 
 use super::{
-  STAKING_REWARD_MAP, STAKING_USER_ACCOUNT_REWARD_DATE_INDEX_MAP,
   stable_key::StakingAccountUserRewardDateIndexKey,
   stable_structures::{StakingReward, StakingRewardStatus},
+  LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP, LEGACY_STAKING_POOL_REWARD_INDEX_MAP, LEGACY_STAKING_USER_REWARD_INDEX_MAP,
+  STAKING_ACCOUNT_REWARD_INDEX_MAP, STAKING_POOL_REWARD_INDEX_MAP, STAKING_REWARD_MAP, STAKING_USER_ACCOUNT_REWARD_DATE_INDEX_MAP,
+  STAKING_USER_REWARD_INDEX_MAP,
 };
 
 /// Update the reward distribution record of the current account on a certain day
@@ -122,6 +129,49 @@ pub async fn distribute_reward(account: &StakingAccount, day: YearMonthDay) -> R
   let updated_reward = reward.received(account_tx_id)?;
 
   save_reward_received_event(&updated_reward);
+  if let Err(e) = record_reward_transaction(&updated_reward, ic_cdk::api::time()) {
+    ic_cdk::println!("Failed to record the reward {} in the pool transaction records: {}", updated_reward.get_id(), e);
+  }
 
   Ok(updated_reward)
 }
+
+/// Add the reward to the reward indexes, the vector indexes are kept in step until the composite indexes are built
+pub fn add_reward_indexes(reward: &StakingReward) {
+  STAKING_POOL_REWARD_INDEX_MAP.with(|index_map| composite_entity_index::add_indexed_id(index_map, &reward.get_pool_id(), reward.get_id()));
+  STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|index_map| composite_entity_index::add_indexed_id(index_map, &reward.get_account_id(), reward.get_id()));
+  STAKING_USER_REWARD_INDEX_MAP.with(|index_map| composite_entity_index::add_indexed_id(index_map, &reward.get_owner(), reward.get_id()));
+
+  if !SCHEMA_MIGRATOR.is_migrated_to(COMPOSITE_REWARD_INDEX_SCHEMA_VERSION) {
+    LEGACY_STAKING_POOL_REWARD_INDEX_MAP.with(|index_map| entities::add_indexed_id(index_map, &reward.get_pool_id(), reward.get_id()));
+    LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|index_map| entities::add_indexed_id(index_map, &reward.get_account_id(), reward.get_id()));
+    LEGACY_STAKING_USER_REWARD_INDEX_MAP.with(|index_map| entities::add_indexed_id(index_map, &reward.get_owner(), reward.get_id()));
+  }
+}
+
+/// Get the reward ids of the user in ascending order
+pub fn get_user_reward_ids(user_id: &UserId) -> Vec<EntityId> {
+  if SCHEMA_MIGRATOR.is_migrated_to(COMPOSITE_REWARD_INDEX_SCHEMA_VERSION) {
+    STAKING_USER_REWARD_INDEX_MAP.with(|m| composite_entity_index::get_indexed_ids(m, user_id))
+  } else {
+    LEGACY_STAKING_USER_REWARD_INDEX_MAP.with(|m| entities::get_indexed_ids(m, user_id))
+  }
+}
+
+/// Get the reward ids of the staking account in ascending order
+pub fn get_account_reward_ids(account_id: &StakingAccountId) -> Vec<EntityId> {
+  if SCHEMA_MIGRATOR.is_migrated_to(COMPOSITE_REWARD_INDEX_SCHEMA_VERSION) {
+    STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|m| composite_entity_index::get_indexed_ids(m, account_id))
+  } else {
+    LEGACY_STAKING_ACCOUNT_REWARD_INDEX_MAP.with(|m| entities::get_indexed_ids(m, account_id))
+  }
+}
+
+/// Get the reward ids of the staking pool in ascending order
+pub fn get_pool_reward_ids(pool_id: &StakingPoolId) -> Vec<EntityId> {
+  if SCHEMA_MIGRATOR.is_migrated_to(COMPOSITE_REWARD_INDEX_SCHEMA_VERSION) {
+    STAKING_POOL_REWARD_INDEX_MAP.with(|m| composite_entity_index::get_indexed_ids(m, pool_id))
+  } else {
+    LEGACY_STAKING_POOL_REWARD_INDEX_MAP.with(|m| entities::get_indexed_ids(m, pool_id))
+  }
+}
@@ -0,0 +1,9 @@
+use crate::deposit::scanner::scan_external_deposits;
+
+pub async fn scan_external_deposits_task() {
+  match scan_external_deposits().await {
+    Ok(deposits) if !deposits.is_empty() => ic_cdk::println!("Found {} external deposits", deposits.len()),
+    Ok(_) => {}
+    Err(e) => ic_cdk::println!("Failed to scan external deposits: {}", e),
+  }
+}
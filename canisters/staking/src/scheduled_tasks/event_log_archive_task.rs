@@ -0,0 +1,9 @@
+use crate::event_log::archive::archive_event_logs;
+
+pub async fn archive_event_logs_task() {
+  match archive_event_logs().await {
+    Ok(archived) if archived > 0 => ic_cdk::println!("Archived {} event logs", archived),
+    Ok(_) => {}
+    Err(e) => ic_cdk::println!("Failed to archive event logs: {}", e),
+  }
+}
@@ -1,3 +1,5 @@
+pub mod deposit_scan_task;
+pub mod event_log_archive_task;
 pub mod nns_neuron_tasks;
 pub mod reward_distribution_task;
 pub mod stake_error_recovery_task;
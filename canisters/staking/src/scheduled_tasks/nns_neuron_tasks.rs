@@ -1,9 +1,18 @@
+use crate::guard_keys::{get_nns_sync_guard_key, NNS_SYNC_GUARD_LEASE};
 use crate::nns::utils::nns_query::sync_nns_neuron;
+use crate::nns::utils::nns_update::{apply_missing_pool_followees, retry_pending_nns_disburse_settlements};
+use crate::parallel_guard::EntryGuard;
 use crate::pool::crud_utils::get_all_staking_pools;
 
 pub async fn sync_nns_neuron_info_task() -> Result<(), String> {
+  let _entry_guard =
+    EntryGuard::with_lease(get_nns_sync_guard_key(), NNS_SYNC_GUARD_LEASE).map_err(|_| "The NNS neuron sync is already running".to_string())?;
+
   ic_cdk::println!("Starting NNS neuron check task...");
 
+  // Disburses whose ledger lookup or settlement failed are settled before the neurons are synced
+  retry_pending_nns_disburse_settlements().await;
+
   // Get all staking pool IDs
   let staking_pools = get_all_staking_pools();
 
@@ -18,6 +27,9 @@ pub async fn sync_nns_neuron_info_task() -> Result<(), String> {
       Ok(_) => ic_cdk::println!("Successfully synced NNS neuron for pool {}", pool_id),
       Err(e) => ic_cdk::println!("Failed to sync NNS neuron for pool {}: {}", pool_id, e),
     }
+
+    // Neurons that missed the pool's followees take them from the synced state
+    apply_missing_pool_followees(pool_id).await;
   }
 
   ic_cdk::println!("NNS neuron check task completed");
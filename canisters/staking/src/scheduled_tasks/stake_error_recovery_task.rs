@@ -1,5 +1,6 @@
 use crate::{
   account::{recovery_errors::recover_staking_account_error, STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP},
+  guard_keys::{get_staking_error_recovery_guard_key, STAKING_ERROR_RECOVERY_GUARD_LEASE},
   parallel_guard::EntryGuard,
 };
 
@@ -7,7 +8,10 @@ use crate::{
 /// This task checks for unprocessed error records every time it starts，And try to recover them
 
 pub async fn recover_staking_account_errors() {
-  let _entry_guard = EntryGuard::new("recover_staking_account_errors".to_string());
+  let Ok(_entry_guard) = EntryGuard::with_lease(get_staking_error_recovery_guard_key(), STAKING_ERROR_RECOVERY_GUARD_LEASE) else {
+    ic_cdk::println!("Staking account error recovery is already running, skip this round");
+    return;
+  };
 
   // Check for unprocessed error records
   let recoverable_error_account_ids = STAKING_RECOVERABLE_ERROR_ACCOUNT_INDEX_MAP.with(|map| {
@@ -3,8 +3,11 @@ use std::{cell::RefCell, str::FromStr};
 use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
 use stable_structures::{StakingSubscription, SubscribeScene};
 use system_configs_macro::has_permission;
-use transport_structures::{StakingSubscribeAddDto, StakingSubscriptionVo, SubscriptionQueryParams, SubscriptionRequest, SubscriptionResponse};
-use types::{stable_structures::Memory, staking::SubscriptionId, EntityId};
+use transport_structures::{
+  StakingSubscribeAddDto, StakingSubscriptionVo, SubscriptionCursorRequest, SubscriptionCursorResponse, SubscriptionQueryParams, SubscriptionRequest,
+  SubscriptionResponse,
+};
+use types::{pagination::collect_page, stable_structures::Memory, staking::SubscriptionId, EntityId};
 
 use crate::{
   event_log::transport_structures::SortType,
@@ -76,60 +79,19 @@ fn query_subscriptions(request: SubscriptionRequest) -> SubscriptionResponse {
   STAKING_SUBSCRIPTION_MAP.with(|map| {
     let map = map.borrow();
 
-    let subscriptions: Vec<StakingSubscription> = map
-      .values()
-      .into_iter()
-      .filter(|subscribe| {
-        // Filter subscription scenarios
-        if subscribe_scene.is_some() {
-          subscribe.get_scene().to_string() == scene
-        } else {
-          true
-        }
-      })
-      .filter(|subscribe| {
-        // Filter subscription email
-        if email.len() > 0 {
-          subscribe.get_email().contains(&email)
-        } else {
-          true
-        }
-      })
-      .filter(|subscribe| {
-        // Filter subscribers id
-        if user_id.len() > 0 {
-          subscribe.get_user_id().contains(&user_id)
-        } else {
-          true
-        }
-      })
-      .filter(|subscribe| {
-        // Filter subscription time
-        if start_time > 0 && end_time > 0 {
-          subscribe.get_created_at() >= start_time && subscribe.get_created_at() <= end_time
-        } else {
-          true
-        }
-      })
-      .collect();
-
-    let total = subscriptions.len() as u32;
-    let start: u32 = (page - 1) * page_size;
-
-    let subscriptions = match id_sort {
-      SortType::Asc => subscriptions
-        .iter()
-        .skip(start as usize)
-        .take(page_size as usize)
-        .map(|subscription| StakingSubscriptionVo::from_stable(subscription))
-        .collect(),
-      SortType::Desc => subscriptions
-        .iter()
-        .rev()
-        .skip(start as usize)
-        .take(page_size as usize)
-        .map(|subscription| StakingSubscriptionVo::from_stable(subscription))
-        .collect(),
+    let is_match = |subscribe: &StakingSubscription| {
+      // Filter subscription scenarios, email, subscribers id and subscription time
+      (subscribe_scene.is_none() || subscribe.get_scene().to_string() == scene)
+        && (email.is_empty() || subscribe.get_email().contains(&email))
+        && (user_id.is_empty() || subscribe.get_user_id().contains(&user_id))
+        && (start_time == 0 || end_time == 0 || (subscribe.get_created_at() >= start_time && subscribe.get_created_at() <= end_time))
+    };
+    let to_record = |subscription: StakingSubscription| StakingSubscriptionVo::from_stable(&subscription);
+
+    // Only the requested page is kept
+    let (total, subscriptions) = match id_sort {
+      SortType::Asc => collect_page(page, page_size, map.values().filter(is_match), to_record),
+      SortType::Desc => collect_page(page, page_size, map.values().rev().filter(is_match), to_record),
     };
 
     SubscriptionResponse {
@@ -140,3 +102,29 @@ fn query_subscriptions(request: SubscriptionRequest) -> SubscriptionResponse {
     }
   })
 }
+
+/// Back-management stream the subscription list page by page, the direction of the request replaces the id sort of the params
+#[ic_cdk::query]
+#[has_permission("staking::subscription::query")]
+fn query_subscriptions_by_cursor(request: SubscriptionCursorRequest) -> Result<SubscriptionCursorResponse, String> {
+  let position = request.position(&["id", "created_at"])?;
+  let SubscriptionQueryParams {
+    scene,
+    email,
+    user_id,
+    start_time,
+    end_time,
+    ..
+  } = request.params;
+  let subscribe_scene = SubscribeScene::from_str(&scene).ok();
+
+  STAKING_SUBSCRIPTION_MAP.with(|map| {
+    Ok(position.scan_map(&map.borrow(), |subscribe| {
+      (subscribe_scene.as_ref().is_none_or(|scene| subscribe.get_scene() == *scene)
+        && (email.is_empty() || subscribe.get_email().contains(&email))
+        && (user_id.is_empty() || subscribe.get_user_id().contains(&user_id))
+        && (start_time == 0 || end_time == 0 || (subscribe.get_created_at() >= start_time && subscribe.get_created_at() <= end_time)))
+        .then(|| StakingSubscriptionVo::from_stable(subscribe))
+    }))
+  })
+}
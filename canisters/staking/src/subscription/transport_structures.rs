@@ -1,7 +1,7 @@
 use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use types::{
-  pagination::{PageRequest, PageResponse},
+  pagination::{CursorPageRequest, CursorPageResponse, PageRequest, PageResponse},
   TimestampNanos, UserId,
 };
 
@@ -65,3 +65,5 @@ pub struct SubscriptionQueryParams {
 
 pub type SubscriptionRequest = PageRequest<SubscriptionQueryParams>;
 pub type SubscriptionResponse = PageResponse<StakingSubscriptionVo>;
+pub type SubscriptionCursorRequest = CursorPageRequest<SubscriptionQueryParams>;
+pub type SubscriptionCursorResponse = CursorPageResponse<StakingSubscriptionVo>;
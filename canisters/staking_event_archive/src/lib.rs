@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use ic_stable_structures::{memory_manager::MemoryId, memory_manager::MemoryManager, Cell, DefaultMemoryImpl, StableBTreeMap};
+use types::{
+  stable_structures::Memory,
+  staking::{
+    event_archive::{ArchivedEventLog, EventArchiveInfoVo, EventArchiveInitArgs, GetEventLogsArgs, MAX_ARCHIVED_EVENT_LOGS_PER_QUERY},
+    StakingEventLogId,
+  },
+};
+
+pub mod memory_ids;
+
+use memory_ids::{ARCHIVED_EVENT_LOG, STAKING_CANISTER_ID};
+
+thread_local! {
+  static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+
+  /// Event logs moved here by the staking canister
+  static ARCHIVED_EVENT_LOG_MAP: RefCell<StableBTreeMap<StakingEventLogId, ArchivedEventLog, Memory>> = RefCell::new(
+    StableBTreeMap::init(
+      MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(ARCHIVED_EVENT_LOG))),
+    )
+  );
+
+  /// The staking canister that spawned this archive
+  static STAKING_CANISTER_ID_CELL: RefCell<Cell<Principal, Memory>> = RefCell::new(Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(STAKING_CANISTER_ID))), Principal::anonymous()).unwrap());
+}
+
+fn get_staking_canister_id() -> Principal {
+  STAKING_CANISTER_ID_CELL.with(|cell| *cell.borrow().get())
+}
+
+#[ic_cdk::init]
+fn init(args: EventArchiveInitArgs) {
+  STAKING_CANISTER_ID_CELL.with(|cell| {
+    cell
+      .borrow_mut()
+      .set(args.staking_canister_id)
+      .expect("Failed to save the staking canister id")
+  });
+}
+
+/// Append a batch of event logs in ascending id order, the ids that are already archived are skipped
+/// so that a batch sent again after an unknown call outcome is not stored twice
+#[ic_cdk::update]
+fn append_event_logs(event_logs: Vec<ArchivedEventLog>) -> Result<u64, String> {
+  if ic_cdk::api::msg_caller() != get_staking_canister_id() {
+    return Err("Only the staking canister can append event logs".to_string());
+  }
+  if event_logs.windows(2).any(|pair| pair[0].id >= pair[1].id) {
+    return Err("The event logs must be in ascending id order".to_string());
+  }
+
+  ARCHIVED_EVENT_LOG_MAP.with(|map| {
+    let mut map = map.borrow_mut();
+    let last_id = map.last_key_value().map(|(id, _)| id);
+    let mut appended = 0;
+    for event_log in event_logs {
+      if last_id.is_some_and(|last_id| event_log.id <= last_id) {
+        continue;
+      }
+      map.insert(event_log.id, event_log);
+      appended += 1;
+    }
+    Ok(appended)
+  })
+}
+
+/// Query the archived event logs with ids in `start..start + length`, the callback of the archived ranges
+#[ic_cdk::query]
+fn get_event_logs(args: GetEventLogsArgs) -> Vec<ArchivedEventLog> {
+  let length = args.length.min(MAX_ARCHIVED_EVENT_LOGS_PER_QUERY);
+  let end = args.start.saturating_add(length);
+
+  ARCHIVED_EVENT_LOG_MAP.with(|map| map.borrow().range(args.start..end).map(|(_, event_log)| event_log).collect())
+}
+
+#[ic_cdk::query]
+fn get_event_archive_info() -> EventArchiveInfoVo {
+  ARCHIVED_EVENT_LOG_MAP.with(|map| {
+    let map = map.borrow();
+    EventArchiveInfoVo {
+      staking_canister_id: get_staking_canister_id(),
+      first_id: map.first_key_value().map(|(id, _)| id),
+      last_id: map.last_key_value().map(|(id, _)| id),
+      total: map.len(),
+    }
+  })
+}
+
+ic_cdk::export_candid!();
@@ -0,0 +1,3 @@
+/// Memory of the archived event logs definition
+pub const ARCHIVED_EVENT_LOG: u8 = 0;
+pub const STAKING_CANISTER_ID: u8 = 1;
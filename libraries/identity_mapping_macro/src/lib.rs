@@ -5,13 +5,32 @@ pub fn identity_mapping(_: TokenStream) -> TokenStream {
   quote::quote! {
     pub mod identity_mapping {
       use ic_cdk::{api::{is_controller, msg_caller}};
-      use std::{cell::RefCell, collections::HashMap};
+      use std::cell::RefCell;
       use candid::Principal;
+      use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
+      use types::{stable_structures::Memory, sys::{identity_mapping::{IdentityMappingAction, IdentityMappingDelta, IdentityMappingEvent, IdentityMappingEventVo}, IDENTITY_MAPPING_EVENT_MEMORY_ID, IDENTITY_MAPPING_MEMORY_ID, IDENTITY_MAPPING_VERSION_MEMORY_ID}};
 
       thread_local! {
-        // identity mapping cache
-        static IDENTITY_MAPPING: RefCell<HashMap<Principal, Principal>> = RefCell::new(
-          HashMap::new()
+        // identity mapping, kept in stable memory across upgrades
+        static IDENTITY_MAPPING: RefCell<StableBTreeMap<Principal, Principal, Memory>> = RefCell::new(
+          StableBTreeMap::init(
+            crate::MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(IDENTITY_MAPPING_MEMORY_ID))),
+          )
+        );
+
+        // identity mapping version, incremented by every change
+        static IDENTITY_MAPPING_VERSION: RefCell<Cell<u64, Memory>> = RefCell::new(
+          Cell::init(
+            crate::MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(IDENTITY_MAPPING_VERSION_MEMORY_ID))),
+            0,
+          ).expect("Failed to init identity mapping version")
+        );
+
+        // identity mapping change events
+        static IDENTITY_MAPPING_EVENTS: RefCell<StableBTreeMap<u64, IdentityMappingEvent, Memory>> = RefCell::new(
+          StableBTreeMap::init(
+            crate::MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(IDENTITY_MAPPING_EVENT_MEMORY_ID))),
+          )
         );
       }
 
@@ -32,26 +51,93 @@ pub fn identity_mapping(_: TokenStream) -> TokenStream {
         }
       }
 
+      /// Add or replace mappings without a version check
       #[ic_cdk::update]
       pub async fn update_identity_mapping(identity_mapping: Vec<(Principal, Principal)>) -> () {
         if !caller_is_controller().await {
           ic_cdk::trap("Update identity mapping failed : caller is not a controller!");
         }
 
-        IDENTITY_MAPPING.with(|cache| {
-          for (key, value) in identity_mapping {
-            cache.borrow_mut().insert(key, value);
-          }
-        });
+        let version = next_version();
+        for (from, to) in identity_mapping {
+          upsert_mapping(from, to, version);
+        }
+        commit_version(version);
 
         ic_cdk::api::debug_print(format!("Identity mapping updated successfully"));
       }
 
+      /// Replace the whole identity mapping, mappings missing from the list are removed.
+      /// Returns the committed version, unchanged when the mapping was already in sync.
+      #[ic_cdk::update]
+      pub async fn sync_identity_mapping(identity_mapping: Vec<(Principal, Principal)>) -> u64 {
+        if !caller_is_controller().await {
+          ic_cdk::trap("Sync identity mapping failed : caller is not a controller!");
+        }
+
+        let version = next_version();
+
+        let synced = identity_mapping.iter().map(|(from, _)| *from).collect::<std::collections::HashSet<_>>();
+        let stale = IDENTITY_MAPPING.with(|map| map.borrow().keys().filter(|from| !synced.contains(from)).collect::<Vec<_>>());
+        for from in stale {
+          remove_mapping(from, version);
+        }
+
+        for (from, to) in identity_mapping {
+          upsert_mapping(from, to, version);
+        }
+        commit_version(version);
+
+        let version = get_identity_mapping_version();
+        ic_cdk::api::debug_print(format!("Identity mapping synced to version {}", version));
+        version
+      }
+
+      /// Apply a delta on top of the version it is based on, the identity canister falls back to a full sync on a version mismatch.
+      /// Returns the committed version, unchanged when the delta changed nothing.
+      #[ic_cdk::update]
+      pub async fn apply_identity_mapping_delta(delta: IdentityMappingDelta) -> Result<u64, String> {
+        if !caller_is_controller().await {
+          return Err(String::from("Apply identity mapping delta failed : caller is not a controller!"));
+        }
+
+        let current_version = get_identity_mapping_version();
+        if delta.base_version != current_version {
+          return Err(format!("Identity mapping version mismatch: the delta is based on {}, the current version is {}", delta.base_version, current_version));
+        }
+
+        let version = next_version();
+        for from in delta.removals {
+          remove_mapping(from, version);
+        }
+        for (from, to) in delta.upserts {
+          upsert_mapping(from, to, version);
+        }
+        commit_version(version);
+
+        Ok(get_identity_mapping_version())
+      }
+
+      #[ic_cdk::query]
+      pub fn get_identity_mapping_version() -> u64 {
+        IDENTITY_MAPPING_VERSION.with(|cell| *cell.borrow().get())
+      }
 
       #[ic_cdk::query]
       pub fn query_identity_mapping() -> Vec<(Principal, Principal)> {
-        IDENTITY_MAPPING.with(|cache| {
-          cache.borrow().iter().map(|(key, value)| (key.clone(), value.clone())).collect()
+        IDENTITY_MAPPING.with(|map| map.borrow().iter().collect())
+      }
+
+      /// Query the identity mapping change events after the given event id, oldest first
+      #[ic_cdk::query]
+      pub fn query_identity_mapping_events(after_id: Option<u64>, limit: u32) -> Vec<IdentityMappingEventVo> {
+        if !is_controller(&msg_caller()) {
+          ic_cdk::trap("Query identity mapping events failed : caller is not a controller!");
+        }
+
+        let start = after_id.map_or(0, |id| id + 1);
+        IDENTITY_MAPPING_EVENTS.with(|events| {
+          events.borrow().range(start..).take(limit.min(1000) as usize).map(|(id, event)| IdentityMappingEventVo::from_event(id, &event)).collect()
         })
       }
 
@@ -62,12 +148,59 @@ pub fn identity_mapping(_: TokenStream) -> TokenStream {
       pub fn wl_caller() -> Principal {
         let ic_caller = msg_caller();
 
-        IDENTITY_MAPPING.with(|cache| {
-          match cache.borrow().get(&ic_caller) {
-            Some(mapped_principal) => mapped_principal.clone(),
-            None => ic_caller,
-          }
-        })
+        IDENTITY_MAPPING.with(|map| map.borrow().get(&ic_caller).unwrap_or(ic_caller))
+      }
+
+      fn next_version() -> u64 {
+        get_identity_mapping_version() + 1
+      }
+
+      /// The version is only moved forward when the change recorded events
+      fn commit_version(version: u64) {
+        let changed = IDENTITY_MAPPING_EVENTS.with(|events| events.borrow().last_key_value().is_some_and(|(_, event)| event.version == Some(version)));
+        if !changed {
+          return;
+        }
+
+        if let Err(e) = IDENTITY_MAPPING_VERSION.with(|cell| cell.borrow_mut().set(version)) {
+          ic_cdk::println!("Failed to set identity mapping version: {:?}", e);
+        }
+      }
+
+      fn upsert_mapping(from: Principal, to: Principal, version: u64) {
+        let previous_to = IDENTITY_MAPPING.with(|map| map.borrow_mut().insert(from, to));
+
+        let action = match previous_to {
+          Some(previous_to) if previous_to == to => return,
+          Some(_) => IdentityMappingAction::Replaced,
+          None => IdentityMappingAction::Added,
+        };
+
+        save_event(action, from, Some(to), previous_to, version);
+      }
+
+      fn remove_mapping(from: Principal, version: u64) {
+        if let Some(previous_to) = IDENTITY_MAPPING.with(|map| map.borrow_mut().remove(&from)) {
+          save_event(IdentityMappingAction::Removed, from, None, Some(previous_to), version);
+        }
+      }
+
+      fn save_event(action: IdentityMappingAction, from: Principal, to: Option<Principal>, previous_to: Option<Principal>, version: u64) {
+        let event = IdentityMappingEvent {
+          version: Some(version),
+          action: Some(action),
+          from: Some(from),
+          to,
+          previous_to,
+          changed_by: Some(msg_caller()),
+          changed_at: Some(ic_cdk::api::time()),
+        };
+
+        IDENTITY_MAPPING_EVENTS.with(|events| {
+          let mut events = events.borrow_mut();
+          let id = events.last_key_value().map_or(0, |(id, _)| id + 1);
+          events.insert(id, event);
+        });
       }
     }
   }
@@ -1,8 +1,8 @@
 pub mod nns_governance_api;
 
-use ic_ledger_types::MAINNET_GOVERNANCE_CANISTER_ID;
+use candid::Principal;
 use nns_governance_api::Service;
 
-pub fn get_governance() -> Service {
-  Service(MAINNET_GOVERNANCE_CANISTER_ID)
+pub fn get_governance(governance_canister_id: Principal) -> Service {
+  Service(governance_canister_id)
 }
@@ -12,17 +12,36 @@ pub fn parallel_guard(_: TokenStream) -> TokenStream {
   quote::quote! {
     pub mod parallel_guard {
       use std::cell::RefCell;
-      use std::collections::HashSet;
+      use std::collections::HashMap;
+      use std::time::Duration;
+      use ic_cdk::api::{is_controller, msg_caller, time};
+      use types::sys::guard::EntryGuardVo;
+
+      /// Lease of a held guard
+      struct EntryLease {
+        id: u64,
+        acquired_at: u64,
+        /// Maximum hold time, None for a guard held until it is dropped or force released
+        lease_nanos: Option<u64>,
+      }
 
       thread_local! {
-        // cache the user role permissions
-        static ENTRY_STATE: RefCell<HashSet<String>> = RefCell::new(
-          HashSet::new()
+        // held guards by item
+        static ENTRY_STATE: RefCell<HashMap<String, EntryLease>> = RefCell::new(
+          HashMap::new()
         );
+
+        // id of the last granted lease
+        static LAST_LEASE_ID: RefCell<u64> = const { RefCell::new(0) };
       }
 
+      /// Guard of an entry, held until it is dropped.
+      /// `#[must_use]` on the constructors only warns when the result is discarded, `let _entry_guard = EntryGuard::new(..)`
+      /// compiles without a warning and holds nothing on an error, so bind it with `?` or `let Ok(_entry_guard) = .. else`.
+      #[must_use = "a guard dropped in its own statement releases the entry immediately"]
       pub struct EntryGuard {
         item: String,
+        lease_id: u64,
       }
 
       #[derive(Debug, PartialEq, Eq)]
@@ -31,12 +50,39 @@ pub fn parallel_guard(_: TokenStream) -> TokenStream {
       }
 
       impl EntryGuard {
+        /// Acquire the guard until it is dropped, a stuck guard is released with force_release_entry_guard.
+        /// Long-running jobs use with_lease so that a run trapped after an await does not block them for good
+        #[must_use = "check the result, an ignored error lets a concurrent entry run"]
         pub fn new(item: String) -> Result<Self, EntryGuardError> {
+          Self::acquire(item, None)
+        }
+
+        /// Acquire the guard for at most the given hold time, an expired guard is taken over by the next entry
+        #[must_use = "check the result, an ignored error lets a concurrent entry run"]
+        pub fn with_lease(item: String, lease: Duration) -> Result<Self, EntryGuardError> {
+          Self::acquire(item, Some(lease.as_nanos() as u64))
+        }
+
+        fn acquire(item: String, lease_nanos: Option<u64>) -> Result<Self, EntryGuardError> {
+          let now = time();
+
           ENTRY_STATE.with(|state| {
-            if !state.borrow_mut().insert(item.clone()) {
-              return Err(EntryGuardError::AlreadyProcessing);
+            let mut state = state.borrow_mut();
+
+            if let Some(held) = state.get(&item) {
+              if held.lease_nanos.is_none_or(|lease_nanos| now.saturating_sub(held.acquired_at) < lease_nanos) {
+                return Err(EntryGuardError::AlreadyProcessing);
+              }
+              ic_cdk::println!("Entry guard {} expired after {} ns, taking it over", item, now.saturating_sub(held.acquired_at));
             }
-            Ok(Self { item })
+
+            let lease_id = LAST_LEASE_ID.with(|id| {
+              *id.borrow_mut() += 1;
+              *id.borrow()
+            });
+
+            state.insert(item.clone(), EntryLease { id: lease_id, acquired_at: now, lease_nanos });
+            Ok(Self { item, lease_id })
           })
         }
       }
@@ -44,10 +90,58 @@ pub fn parallel_guard(_: TokenStream) -> TokenStream {
       impl Drop for EntryGuard {
         fn drop(&mut self) {
           ENTRY_STATE.with(|state| {
-            state.borrow_mut().remove(&self.item);
+            let mut state = state.borrow_mut();
+            // The guard may have expired and been taken over, only release our own lease
+            if state.get(&self.item).is_some_and(|held| held.id == self.lease_id) {
+              state.remove(&self.item);
+            }
           });
         }
       }
+
+      /// Query the held entry guards and how long they have been held
+      #[ic_cdk::query]
+      pub fn query_entry_guards() -> Vec<EntryGuardVo> {
+        if !crate::system_configs::has_permission("sys::guard::query") {
+          ic_cdk::trap("Caller does not have permission to call sys::guard::query");
+        }
+
+        let now = time();
+        ENTRY_STATE.with(|state| {
+          let mut guards = state
+            .borrow()
+            .iter()
+            .map(|(item, held)| {
+              let held_nanos = now.saturating_sub(held.acquired_at);
+              EntryGuardVo {
+                key: item.clone(),
+                acquired_at: held.acquired_at,
+                held_nanos,
+                lease_nanos: held.lease_nanos,
+                expired: held.lease_nanos.is_some_and(|lease_nanos| held_nanos >= lease_nanos),
+              }
+            })
+            .collect::<Vec<_>>();
+          guards.sort_by_key(|guard| guard.acquired_at);
+          guards
+        })
+      }
+
+      /// Release a held entry guard, for entries stuck by a trapped or abandoned call
+      #[ic_cdk::update]
+      pub fn force_release_entry_guard(key: String) -> Result<(), String> {
+        if !is_controller(&msg_caller()) {
+          return Err(String::from("Force release entry guard failed: caller is not a controller!"));
+        }
+
+        match ENTRY_STATE.with(|state| state.borrow_mut().remove(&key)) {
+          Some(_) => {
+            ic_cdk::println!("Entry guard {} force released by {}", key, msg_caller());
+            Ok(())
+          }
+          None => Err(format!("Entry guard {} is not held", key)),
+        }
+      }
     }
   }
   .into()
@@ -14,7 +14,8 @@ pub fn system_configs(_: TokenStream) -> TokenStream {
       use ic_cdk::{api::{is_controller, msg_caller}};
       use std::{cell::RefCell, collections::{HashMap, HashSet}};
       use candid::Principal;
-      use types::{sys::{config::{PermissionCode, RoleCode, SystemConfig, UserRolePermissionVo}, dict::{transfer_structures::DictVo, DictCode}, ExteralCanisterLabels, SystemSwitches}, UserId};
+      use ic_stable_structures::{memory_manager::MemoryId, Cell, StableBTreeMap};
+      use types::{pagination::{collect_page, PageRequest, PageResponse}, stable_structures::Memory, sys::{audit::{hash_audit_args, AdminAuditEntry, AdminAuditEntryVo, AdminAuditOutcome, AdminAuditQueryParams, MAX_ADMIN_AUDIT_ENTRIES}, config::{permission_code_matches, PermissionCode, PermissionExplanationVo, RoleCode, RolePermissionVo, SystemConfig, SystemConfigSnapshot, SystemConfigStatusVo, UserRolePermissionVo}, dict::{transfer_structures::DictVo, DictCode}, ExteralCanisterLabels, SystemSwitches, ADMIN_AUDIT_LOG_MEMORY_ID, SYSTEM_CONFIG_MEMORY_ID}, UserId};
 
       thread_local! {
         // cache the user role permissions
@@ -22,10 +23,107 @@ pub fn system_configs(_: TokenStream) -> TokenStream {
           HashMap::new()
         );
 
+        // cache the permissions of each role
+        static ROLE_PERMISSIONS: RefCell<HashMap<RoleCode, HashSet<PermissionCode>>> = RefCell::new(
+          HashMap::new()
+        );
+
         // cache the dictionary
         static DICT: RefCell<HashMap<DictCode, DictVo>> = RefCell::new(
           HashMap::new()
         );
+
+        // the last received system config, kept in stable memory across upgrades
+        static SYSTEM_CONFIG_SNAPSHOT: RefCell<Cell<SystemConfigSnapshot, Memory>> = RefCell::new(
+          Cell::init(
+            crate::MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(SYSTEM_CONFIG_MEMORY_ID))),
+            SystemConfigSnapshot::default(),
+          ).expect("Failed to init system config snapshot")
+        );
+
+        // calls of the audited admin endpoints
+        static ADMIN_AUDIT_LOG: RefCell<StableBTreeMap<u64, AdminAuditEntry, Memory>> = RefCell::new(
+          StableBTreeMap::init(
+            crate::MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(ADMIN_AUDIT_LOG_MEMORY_ID))),
+          )
+        );
+      }
+
+      /// An audited admin call, recorded as started when the call begins so that calls trapping after an await are still logged
+      pub struct AdminAudit {
+        id: u64,
+        entry: AdminAuditEntry,
+      }
+
+      impl AdminAudit {
+        pub fn begin(permission_code: &str, method: &str, encoded_args: &[u8]) -> Self {
+          let entry = AdminAuditEntry {
+            caller: Some(msg_caller()),
+            permission_code: Some(permission_code.to_string()),
+            method: Some(method.to_string()),
+            args_hash: Some(hash_audit_args(encoded_args)),
+            outcome: Some(AdminAuditOutcome::Started),
+            called_at: Some(ic_cdk::api::time()),
+          };
+
+          let id = ADMIN_AUDIT_LOG.with(|log| {
+            let mut log = log.borrow_mut();
+            let id = log.last_key_value().map_or(0, |(id, _)| id + 1);
+            log.insert(id, entry.clone());
+
+            // drop the oldest entries beyond the cap
+            while log.len() > MAX_ADMIN_AUDIT_ENTRIES {
+              match log.first_key_value() {
+                Some((oldest_id, _)) => log.remove(&oldest_id),
+                None => break,
+              };
+            }
+
+            id
+          });
+
+          Self { id, entry }
+        }
+
+        pub fn finish(mut self, outcome: AdminAuditOutcome) {
+          self.entry.outcome = Some(outcome);
+
+          ADMIN_AUDIT_LOG.with(|log| {
+            let mut log = log.borrow_mut();
+            if log.contains_key(&self.id) {
+              log.insert(self.id, self.entry);
+            }
+          });
+        }
+      }
+
+      /// Query the admin audit log, newest first. The log is capped at MAX_ADMIN_AUDIT_ENTRIES entries.
+      #[ic_cdk::query]
+      pub fn query_admin_audit_log(request: PageRequest<AdminAuditQueryParams>) -> PageResponse<AdminAuditEntryVo> {
+        if !has_permission("sys::audit::query") {
+          ic_cdk::trap("Caller does not have permission to call sys::audit::query");
+        }
+
+        let PageRequest { page, page_size, params } = request;
+        let start_time = params.start_time.unwrap_or_default();
+        let end_time = params.end_time.unwrap_or(u64::MAX);
+
+        ADMIN_AUDIT_LOG.with(|log| {
+          let log = log.borrow();
+          let matched = log.iter().rev().filter(|(_, entry)| {
+            let called_at = entry.called_at.unwrap_or_default();
+            called_at >= start_time
+              && called_at <= end_time
+              && params.caller.is_none_or(|caller| entry.caller == Some(caller))
+              && params.method.as_ref().is_none_or(|method| entry.method.as_ref() == Some(method))
+              && params.permission_code.as_ref().is_none_or(|code| entry.permission_code.as_ref() == Some(code))
+          });
+
+          // only the entries of the requested page are decoded into view objects
+          let (total, records) = collect_page(page, page_size, matched, |(id, entry)| AdminAuditEntryVo::from_entry(id, &entry));
+
+          PageResponse::new(page, page_size, total, records)
+        })
       }
 
       #[ic_cdk::update]
@@ -50,6 +148,36 @@ pub fn system_configs(_: TokenStream) -> TokenStream {
           ic_cdk::trap("Update system configs failed : caller is not a controller!");
         }
 
+        let snapshot = SystemConfigSnapshot {
+          version: Some(SYSTEM_CONFIG_SNAPSHOT.with(|cell| cell.borrow().get().get_version()) + 1),
+          received_at: Some(ic_cdk::api::time()),
+          config: Some(config.clone()),
+        };
+
+        if let Err(e) = SYSTEM_CONFIG_SNAPSHOT.with(|cell| cell.borrow_mut().set(snapshot)) {
+          ic_cdk::println!("Failed to persist system configs: {:?}", e);
+        }
+
+        load_system_configs(config);
+
+        ic_cdk::api::debug_print(format!("System configs updated successfully"));
+      }
+
+      /// Reload the caches from the system config persisted in stable memory, call it in post_upgrade
+      pub fn restore_system_configs() {
+        let snapshot = SYSTEM_CONFIG_SNAPSHOT.with(|cell| cell.borrow().get().clone());
+
+        let version = snapshot.get_version();
+        match snapshot.config {
+          Some(config) => {
+            load_system_configs(config);
+            ic_cdk::println!("System configs version {} restored", version);
+          }
+          None => ic_cdk::println!("No system configs to restore"),
+        }
+      }
+
+      fn load_system_configs(config: SystemConfig) {
         USER_ROLE_PERMISSIONS.with(|cache| {
           cache.borrow_mut().clear();
           for user_role_permission in config.user_role_permissions {
@@ -57,14 +185,31 @@ pub fn system_configs(_: TokenStream) -> TokenStream {
           }
         });
 
+        ROLE_PERMISSIONS.with(|cache| {
+          cache.borrow_mut().clear();
+          for role_permission in config.role_permissions.unwrap_or_default() {
+            cache.borrow_mut().insert(role_permission.role_code, role_permission.permission_codes.into_iter().collect());
+          }
+        });
+
         DICT.with(|cache| {
           cache.borrow_mut().clear();
           for dict in config.dicts {
             cache.borrow_mut().insert(dict.code.clone(), dict);
           }
         });
+      }
 
-        ic_cdk::api::debug_print(format!("System configs updated successfully"));
+      #[ic_cdk::query]
+      pub fn get_system_config_status() -> SystemConfigStatusVo {
+        let snapshot = SYSTEM_CONFIG_SNAPSHOT.with(|cell| cell.borrow().get().clone());
+
+        SystemConfigStatusVo {
+          version: snapshot.get_version(),
+          received_at: snapshot.get_received_at(),
+          user_count: USER_ROLE_PERMISSIONS.with(|cache| cache.borrow().len() as u32),
+          dict_count: DICT.with(|cache| cache.borrow().len() as u32),
+        }
       }
 
       #[ic_cdk::query]
@@ -81,32 +226,108 @@ pub fn system_configs(_: TokenStream) -> TokenStream {
         })
       }
 
+      #[ic_cdk::query]
+      pub fn get_role_permissions() -> Vec<RolePermissionVo> {
+        ROLE_PERMISSIONS.with(|cache| {
+          cache.borrow().iter().map(|(role_code, permission_codes)| {
+            RolePermissionVo {
+              role_code: role_code.clone(),
+              permission_codes: permission_codes.iter().cloned().collect(),
+            }
+          }).collect()
+        })
+      }
+
+      /// Explain why a principal does or does not hold a permission, callers can explain themselves, controllers anyone
+      #[ic_cdk::query]
+      pub fn explain_permission(principal: Principal, permission_code: String) -> PermissionExplanationVo {
+        let caller = msg_caller();
+        if caller != principal && !is_controller(&caller) {
+          ic_cdk::trap("Explain permission failed: only controllers can explain the permissions of other principals!");
+        }
+
+        explain_user_permission(&principal.to_text(), &permission_code)
+      }
+
       pub fn get_dict_with_dict_code(dict_code: &DictCode) -> Option<DictVo> {
         DICT.with(|cache| {
           cache.borrow().get(dict_code).cloned()
         })
       }
 
-      /// Check if the caller has the given permission
+      /// Check if the caller has the given permission, directly or through one of its roles
       pub fn has_permission(permission_code: &str) -> bool {
-        let caller = msg_caller().to_text();
-        USER_ROLE_PERMISSIONS.with(|cache| {
-          match cache.borrow().get(&caller) {
-            Some((_, _, permission_codes)) => permission_codes.contains(permission_code),
-            None => false
+        explain_user_permission(&msg_caller().to_text(), permission_code).granted
+      }
+
+      fn explain_user_permission(user: &UserId, permission_code: &str) -> PermissionExplanationVo {
+        let mut explanation = PermissionExplanationVo {
+          principal_id: user.clone(),
+          permission_code: permission_code.to_string(),
+          granted: false,
+          granted_by_role: None,
+          matched_permission_code: None,
+          reason: String::new(),
+        };
+
+        let Some((_, role_codes, permission_codes)) = USER_ROLE_PERMISSIONS.with(|cache| cache.borrow().get(user).cloned()) else {
+          explanation.reason = String::from("The principal has no role permissions");
+          return explanation;
+        };
+
+        if let Some(matched) = find_matched_permission_code(&permission_codes, permission_code) {
+          explanation.granted = true;
+          explanation.reason = format!("Granted to the principal directly by {}", matched);
+          explanation.matched_permission_code = Some(matched);
+          return explanation;
+        }
+
+        let mut role_codes = role_codes.into_iter().collect::<Vec<_>>();
+        role_codes.sort();
+
+        let mut undefined_roles = vec![];
+        for role_code in role_codes.iter() {
+          let Some(role_permission_codes) = ROLE_PERMISSIONS.with(|cache| cache.borrow().get(role_code).cloned()) else {
+            undefined_roles.push(role_code.clone());
+            continue;
+          };
+
+          if let Some(matched) = find_matched_permission_code(&role_permission_codes, permission_code) {
+            explanation.granted = true;
+            explanation.reason = format!("Granted by role {} with {}", role_code, matched);
+            explanation.granted_by_role = Some(role_code.clone());
+            explanation.matched_permission_code = Some(matched);
+            return explanation;
           }
-        })
+        }
+
+        explanation.reason = format!("Neither the permissions of the principal nor its roles [{}] grant it", role_codes.join(", "));
+        if !undefined_roles.is_empty() {
+          explanation.reason.push_str(&format!(", roles [{}] are not defined", undefined_roles.join(", ")));
+        }
+        explanation
+      }
+
+      /// An exact permission code is preferred over a wildcard
+      fn find_matched_permission_code(permission_codes: &HashSet<PermissionCode>, permission_code: &str) -> Option<PermissionCode> {
+        if permission_codes.contains(permission_code) {
+          return Some(permission_code.to_string());
+        }
+
+        let mut matched = permission_codes.iter().filter(|granted| permission_code_matches(granted, permission_code)).collect::<Vec<_>>();
+        // The most specific wildcard first
+        matched.sort_by_key(|granted| std::cmp::Reverse(granted.len()));
+        matched.first().map(|granted| granted.to_string())
       }
 
+      /// The ICP ledger and the NNS governance fall back to their mainnet canister IDs when the system config does not set them
       pub fn get_exteral_canister_id(canister: ExteralCanisterLabels) -> Principal {
-        let dict = match get_dict_with_dict_code(&DictCode::from("system_config")) {
-          Some(dict) => dict,
-          None => ic_cdk::trap("Failed to get system config dict!")
-        };
-        let canister_item = dict.items.iter().find(|item| item.label == canister.to_string());
-        match canister_item {
-          Some(item) => Principal::from_text(&item.value).unwrap_or_else(|_| ic_cdk::trap(&format!("Failed to get canister id: canister label = {} and value = {} is not a valid principal!", canister.to_string(), item.value)),),
-          None => ic_cdk::trap(&format!("Failed to get canister id: canister label = {} not found!", canister.to_string()))
+        let canister_item = get_dict_with_dict_code(&DictCode::from("system_config"))
+          .and_then(|dict| dict.items.into_iter().find(|item| item.label == canister.to_string()));
+        match (canister_item, canister.mainnet_canister_id()) {
+          (Some(item), _) => Principal::from_text(&item.value).unwrap_or_else(|_| ic_cdk::trap(&format!("Failed to get canister id: canister label = {} and value = {} is not a valid principal!", canister.to_string(), item.value)),),
+          (None, Some(mainnet_canister_id)) => Principal::from_text(mainnet_canister_id).unwrap(),
+          (None, None) => ic_cdk::trap(&format!("Failed to get canister id: canister label = {} not found!", canister.to_string()))
         }
       }
 
@@ -133,33 +354,41 @@ pub fn system_configs(_: TokenStream) -> TokenStream {
   .into()
 }
 
-#[proc_macro_attribute]
-pub fn has_permission(attr: TokenStream, item: TokenStream) -> TokenStream {
-  let attr_input = syn::parse_macro_input!(attr as syn::LitStr);
-  let input = syn::parse_macro_input!(item as syn::ItemFn);
-  let name = &input.sig.ident;
-  let block = &input.block;
-  let vis = &input.vis;
-  let attrs = &input.attrs;
-  let asyncness = &input.sig.asyncness;
-  let inputs = &input.sig.inputs;
-  let output = &input.sig.output;
+/// Arguments of the permission macros: the permission code, optionally followed by `audit` to record every permitted call in the admin audit log
+struct PermissionArgs {
+  permission_code: syn::LitStr,
+  audit: bool,
+}
 
-  quote::quote! {
-    #(#attrs)*
-    #vis #asyncness fn #name(#inputs) #output {
-      if !crate::system_configs::has_permission(#attr_input) {
-        ic_cdk::trap(&format!("Caller does not have permission to call {}", stringify!(#attr_input)));
+impl syn::parse::Parse for PermissionArgs {
+  fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+    let permission_code = input.parse()?;
+    let mut audit = false;
+
+    if input.parse::<Option<syn::Token![,]>>()?.is_some() {
+      let flag: syn::Ident = input.parse()?;
+      if flag != "audit" {
+        return Err(syn::Error::new(flag.span(), "expected `audit`"));
       }
-      #block
+      audit = true;
     }
+
+    Ok(Self { permission_code, audit })
   }
-  .into()
 }
 
-#[proc_macro_attribute]
-pub fn has_permission_option(attr: TokenStream, item: TokenStream) -> TokenStream {
-  let attr_input = syn::parse_macro_input!(attr as syn::LitStr);
+/// How a guarded function reports a missing permission
+enum PermissionDenial {
+  Trap,
+  Option,
+  Result,
+}
+
+fn expand_permission_guard(attr: TokenStream, item: TokenStream, denial: PermissionDenial) -> TokenStream {
+  let PermissionArgs {
+    permission_code: attr_input,
+    audit,
+  } = syn::parse_macro_input!(attr as PermissionArgs);
   let input = syn::parse_macro_input!(item as syn::ItemFn);
   let name = &input.sig.ident;
   let block = &input.block;
@@ -169,38 +398,103 @@ pub fn has_permission_option(attr: TokenStream, item: TokenStream) -> TokenStrea
   let inputs = &input.sig.inputs;
   let output = &input.sig.output;
 
-  quote::quote! {
-    #(#attrs)*
-    #vis #asyncness fn #name(#inputs) #output {
-      if !crate::system_configs::has_permission(#attr_input) {
-        return Option::Some(format!("Caller does not have permission to call {}", stringify!(#attr_input)));
+  let deny = match denial {
+    PermissionDenial::Trap => quote::quote! {
+      ic_cdk::trap(&format!("Caller does not have permission to call {}", stringify!(#attr_input)));
+    },
+    PermissionDenial::Option => quote::quote! {
+      return Option::Some(format!("Caller does not have permission to call {}", stringify!(#attr_input)));
+    },
+    PermissionDenial::Result => quote::quote! {
+      return Err(types::sys::config::PermissionDenied::permission_denied(#attr_input));
+    },
+  };
+
+  if !audit {
+    return quote::quote! {
+      #(#attrs)*
+      #vis #asyncness fn #name(#inputs) #output {
+        if !crate::system_configs::has_permission(#attr_input) {
+          #deny
+        }
+        #block
       }
-      #block
     }
+    .into();
   }
-  .into()
-}
 
-#[proc_macro_attribute]
-pub fn has_permission_result(attr: TokenStream, item: TokenStream) -> TokenStream {
-  let attr_input = syn::parse_macro_input!(attr as syn::LitStr);
-  let input = syn::parse_macro_input!(item as syn::ItemFn);
-  let name = &input.sig.ident;
-  let block = &input.block;
-  let vis = &input.vis;
-  let attrs = &input.attrs;
-  let asyncness = &input.sig.asyncness;
-  let inputs = &input.sig.inputs;
-  let output = &input.sig.output;
+  let mut args = vec![];
+  for arg in inputs.iter() {
+    match arg {
+      syn::FnArg::Typed(pat_type) => match pat_type.pat.as_ref() {
+        syn::Pat::Ident(pat_ident) => args.push(pat_ident.ident.clone()),
+        pat => {
+          return syn::Error::new_spanned(pat, "audited functions only support plain argument names")
+            .to_compile_error()
+            .into()
+        }
+      },
+      syn::FnArg::Receiver(receiver) => {
+        return syn::Error::new_spanned(receiver, "audited functions cannot take self")
+          .to_compile_error()
+          .into()
+      }
+    }
+  }
+
+  let await_inner = asyncness.map(|_| quote::quote! { .await });
+
+  let outcome = match denial {
+    PermissionDenial::Trap => quote::quote! {
+      types::sys::audit::AdminAuditOutcome::Succeeded
+    },
+    PermissionDenial::Option => quote::quote! {
+      match &result {
+        Some(err) => types::sys::audit::AdminAuditOutcome::Failed(err.to_string()),
+        None => types::sys::audit::AdminAuditOutcome::Succeeded,
+      }
+    },
+    PermissionDenial::Result => quote::quote! {
+      match &result {
+        Ok(_) => types::sys::audit::AdminAuditOutcome::Succeeded,
+        Err(err) => types::sys::audit::AdminAuditOutcome::Failed(err.to_string()),
+      }
+    },
+  };
 
   quote::quote! {
     #(#attrs)*
     #vis #asyncness fn #name(#inputs) #output {
+      #asyncness fn audited(#inputs) #output #block
+
+      // Denied calls are not recorded, so that callers without the permission cannot push admin calls out of the capped log
       if !crate::system_configs::has_permission(#attr_input) {
-        return Err(format!("Caller does not have permission to call {}", stringify!(#attr_input)));
+        #deny
       }
-      #block
+
+      let mut encoded_args = candid::ser::IDLBuilder::new();
+      #(encoded_args.arg(&#args).ok();)*
+      let audit = crate::system_configs::AdminAudit::begin(#attr_input, stringify!(#name), &encoded_args.serialize_to_vec().unwrap_or_default());
+
+      let result = audited(#(#args),*)#await_inner;
+      audit.finish(#outcome);
+      result
     }
   }
   .into()
 }
+
+#[proc_macro_attribute]
+pub fn has_permission(attr: TokenStream, item: TokenStream) -> TokenStream {
+  expand_permission_guard(attr, item, PermissionDenial::Trap)
+}
+
+#[proc_macro_attribute]
+pub fn has_permission_option(attr: TokenStream, item: TokenStream) -> TokenStream {
+  expand_permission_guard(attr, item, PermissionDenial::Option)
+}
+
+#[proc_macro_attribute]
+pub fn has_permission_result(attr: TokenStream, item: TokenStream) -> TokenStream {
+  expand_permission_guard(attr, item, PermissionDenial::Result)
+}
@@ -0,0 +1,121 @@
+use std::{borrow::Cow, cell::RefCell, ops::Bound as RangeBound};
+
+use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::{storable::Bound, StableBTreeMap, Storable};
+use serde::{Deserialize, Serialize};
+
+use crate::{stable_structures::Memory, EntityId};
+
+/// Key of a composite entity index, one map entry per indexed entity,
+/// so adding or removing an entity touches a single entry whatever the number of entities under the key
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, CandidType)]
+pub struct CompositeIndexKey<T>(pub T, pub EntityId);
+
+impl<T: CandidType + Serialize + for<'a> Deserialize<'a>> Storable for CompositeIndexKey<T> {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+impl<T> CompositeIndexKey<T> {
+  pub fn get_key(&self) -> &T {
+    &self.0
+  }
+
+  pub fn get_entity_id(&self) -> EntityId {
+    self.1
+  }
+}
+
+pub type CompositeEntityIndex<T> = StableBTreeMap<CompositeIndexKey<T>, (), Memory>;
+
+/// Range of the index entries of a key, optionally starting after an entity id
+fn key_range<T: Clone>(key: &T, after: Option<EntityId>) -> (RangeBound<CompositeIndexKey<T>>, RangeBound<CompositeIndexKey<T>>) {
+  let start = match after {
+    Some(entity_id) => RangeBound::Excluded(CompositeIndexKey(key.clone(), entity_id)),
+    None => RangeBound::Included(CompositeIndexKey(key.clone(), EntityId::MIN)),
+  };
+  (start, RangeBound::Included(CompositeIndexKey(key.clone(), EntityId::MAX)))
+}
+
+/// Get the ids in the index in ascending order, tool function
+pub fn get_indexed_ids<T>(stable_map: &RefCell<CompositeEntityIndex<T>>, key: &T) -> Vec<EntityId>
+where
+  T: CandidType + Ord + Clone + Serialize + for<'a> Deserialize<'a>,
+{
+  stable_map
+    .borrow()
+    .keys_range(key_range(key, None))
+    .map(|index_key| index_key.get_entity_id())
+    .collect()
+}
+
+/// Get a page of the ids in the index, continuing after the `after` id of the previous page, tool function
+pub fn get_indexed_ids_page<T>(
+  stable_map: &RefCell<CompositeEntityIndex<T>>,
+  key: &T,
+  after: Option<EntityId>,
+  limit: usize,
+  descending: bool,
+) -> Vec<EntityId>
+where
+  T: CandidType + Ord + Clone + Serialize + for<'a> Deserialize<'a>,
+{
+  let map = stable_map.borrow();
+  if descending {
+    let end = match after {
+      Some(entity_id) => RangeBound::Excluded(CompositeIndexKey(key.clone(), entity_id)),
+      None => RangeBound::Included(CompositeIndexKey(key.clone(), EntityId::MAX)),
+    };
+    map
+      .keys_range((RangeBound::Included(CompositeIndexKey(key.clone(), EntityId::MIN)), end))
+      .rev()
+      .take(limit)
+      .map(|index_key| index_key.get_entity_id())
+      .collect()
+  } else {
+    map
+      .keys_range(key_range(key, after))
+      .take(limit)
+      .map(|index_key| index_key.get_entity_id())
+      .collect()
+  }
+}
+
+/// Count the ids in the index, tool function
+pub fn count_indexed_ids<T>(stable_map: &RefCell<CompositeEntityIndex<T>>, key: &T) -> u64
+where
+  T: CandidType + Ord + Clone + Serialize + for<'a> Deserialize<'a>,
+{
+  stable_map.borrow().keys_range(key_range(key, None)).count() as u64
+}
+
+/// Whether the id is in the index, tool function
+pub fn has_indexed_id<T>(stable_map: &RefCell<CompositeEntityIndex<T>>, key: &T, entity_id: EntityId) -> bool
+where
+  T: CandidType + Ord + Clone + Serialize + for<'a> Deserialize<'a>,
+{
+  stable_map.borrow().contains_key(&CompositeIndexKey(key.clone(), entity_id))
+}
+
+/// Add a new entity id to the id in the index, tool function
+pub fn add_indexed_id<T>(stable_map: &RefCell<CompositeEntityIndex<T>>, key: &T, entity_id: EntityId)
+where
+  T: CandidType + Ord + Clone + Serialize + for<'a> Deserialize<'a>,
+{
+  stable_map.borrow_mut().insert(CompositeIndexKey(key.clone(), entity_id), ());
+}
+
+/// Delete the id in the index, tool function
+pub fn remove_indexed_id<T>(stable_map: &RefCell<CompositeEntityIndex<T>>, key: &T, entity_id: EntityId)
+where
+  T: CandidType + Ord + Clone + Serialize + for<'a> Deserialize<'a>,
+{
+  stable_map.borrow_mut().remove(&CompositeIndexKey(key.clone(), entity_id));
+}
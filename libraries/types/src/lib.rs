@@ -5,6 +5,7 @@ use strum_macros::{Display, EnumString};
 
 pub mod assets_management;
 pub mod btree_set_entity_index;
+pub mod composite_entity_index;
 pub mod date;
 pub mod entities;
 pub mod on_chain;
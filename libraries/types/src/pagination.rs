@@ -1,5 +1,16 @@
-use candid::CandidType;
+use std::ops::{Bound as RangeBound, RangeBounds};
+
+use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::{StableBTreeMap, Storable};
 use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+use crate::{stable_structures::Memory, EntityId};
+
+/// Maximum number of records of a cursor page
+pub const MAX_CURSOR_PAGE_LIMIT: u32 = 500;
+/// Maximum number of entries a cursor page scans, a page that reaches it returns fewer records and a cursor to continue from
+pub const MAX_CURSOR_PAGE_SCAN: usize = 20_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct PageRequest<T: CandidType> {
@@ -43,11 +54,221 @@ impl<T: CandidType> PageResponse<T> {
 
   /// Record start position
   pub fn start(&self) -> u32 {
-    (self.page - 1) * self.page_size
+    self.page.saturating_sub(1) * self.page_size
   }
 
-  /// Record end position
+  /// Record end position, an empty page ends before it starts
   pub fn end(&self) -> u32 {
-    self.start() + self.page_size - 1
+    self.start().saturating_add(self.page_size).saturating_sub(1)
+  }
+}
+
+/// Take a page out of the matched records in page order, only the records of the page are converted and kept, the others are counted.
+/// Returns the total of the matched records and the records of the page.
+pub fn collect_page<V, R>(page: u32, page_size: u32, matched: impl Iterator<Item = V>, mut to_record: impl FnMut(V) -> R) -> (u32, Vec<R>) {
+  let start = page.saturating_sub(1).saturating_mul(page_size);
+  let end = start.saturating_add(page_size);
+
+  let mut total = 0u32;
+  let mut records = Vec::new();
+  for value in matched {
+    if total >= start && total < end {
+      records.push(to_record(value));
+    }
+    total += 1;
+  }
+
+  (total, records)
+}
+
+/// Sort direction of a cursor page
+#[derive(EnumString, Display, Debug, Clone, Copy, Serialize, Deserialize, CandidType, PartialEq, Default)]
+pub enum SortDirection {
+  Asc,
+  #[default]
+  Desc,
+}
+
+/// Request of a cursor page, the first page is requested without a cursor,
+/// the next pages pass the `next_cursor` of the previous response with the same sort field and direction.
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CursorPageRequest<T: CandidType> {
+  /// Maximum number of records, at most MAX_CURSOR_PAGE_LIMIT
+  pub limit: u32,
+  /// Field to sort by, the first sort field of the endpoint when not set
+  pub sort_field: Option<String>,
+  /// Descending when not set
+  pub direction: Option<SortDirection>,
+  /// Opaque continuation token from the previous page
+  pub cursor: Option<String>,
+  pub params: T,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct CursorPageResponse<T: CandidType> {
+  pub sort_field: String,
+  pub direction: SortDirection,
+  pub records: Vec<T>,
+  /// Continuation token of the next page, None when there are no more records
+  pub next_cursor: Option<String>,
+}
+
+/// Content of the continuation token
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+struct PageCursor {
+  sort_field: String,
+  direction: SortDirection,
+  /// Id of the last entry scanned by the previous page
+  last_id: EntityId,
+}
+
+impl PageCursor {
+  fn encode(&self) -> String {
+    Encode!(self).unwrap().iter().map(|byte| format!("{:02x}", byte)).collect()
+  }
+
+  fn decode(token: &str) -> Result<Self, String> {
+    let invalid = || format!("Invalid cursor: {}", token);
+    if token.len() % 2 != 0 {
+      return Err(invalid());
+    }
+    let bytes = (0..token.len())
+      .step_by(2)
+      .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+      .collect::<Result<Vec<u8>, _>>()
+      .map_err(|_| invalid())?;
+    Decode!(&bytes, Self).map_err(|_| invalid())
+  }
+}
+
+/// Resolved position of a cursor page.
+/// The records of the cursor endpoints are sorted by their ids, which follow the creation order of the records,
+/// so the creation time sort field an endpoint accepts besides the id is served by the id order.
+pub struct CursorPosition {
+  pub sort_field: String,
+  pub direction: SortDirection,
+  /// Continue after this id
+  pub after: Option<EntityId>,
+  pub limit: usize,
+}
+
+impl<T: CandidType> CursorPageRequest<T> {
+  /// Check the request against the sort fields the endpoint accepts and decode its cursor, an unsupported sort field is rejected
+  pub fn position(&self, sort_fields: &[&str]) -> Result<CursorPosition, String> {
+    let sort_field = match &self.sort_field {
+      Some(sort_field) if sort_fields.contains(&sort_field.as_str()) => sort_field.clone(),
+      Some(sort_field) => return Err(format!("Unsupported sort field: {}, supported: {}", sort_field, sort_fields.join(", "))),
+      None => sort_fields.first().map(|field| field.to_string()).unwrap_or_default(),
+    };
+    let direction = self.direction.unwrap_or_default();
+
+    let after = match &self.cursor {
+      Some(token) => {
+        let cursor = PageCursor::decode(token)?;
+        if cursor.sort_field != sort_field || cursor.direction != direction {
+          return Err("The cursor belongs to another sort field or direction".to_string());
+        }
+        Some(cursor.last_id)
+      }
+      None => None,
+    };
+
+    Ok(CursorPosition {
+      sort_field,
+      direction,
+      after,
+      limit: self.limit.clamp(1, MAX_CURSOR_PAGE_LIMIT) as usize,
+    })
+  }
+}
+
+impl CursorPosition {
+  /// Id range of the entries after the cursor
+  fn id_range(&self) -> (RangeBound<EntityId>, RangeBound<EntityId>) {
+    match (self.after, self.direction) {
+      (None, _) => (RangeBound::Unbounded, RangeBound::Unbounded),
+      (Some(after), SortDirection::Asc) => (RangeBound::Excluded(after), RangeBound::Unbounded),
+      (Some(after), SortDirection::Desc) => (RangeBound::Unbounded, RangeBound::Excluded(after)),
+    }
+  }
+
+  /// Stream a page out of a map keyed by entity id
+  pub fn scan_map<V: Storable, R: CandidType>(
+    &self,
+    map: &StableBTreeMap<EntityId, V, Memory>,
+    filter_map: impl FnMut(&V) -> Option<R>,
+  ) -> CursorPageResponse<R> {
+    let range = map.range(self.id_range());
+    match self.direction {
+      SortDirection::Asc => self.collect(range, filter_map),
+      SortDirection::Desc => self.collect(range.rev(), filter_map),
+    }
+  }
+
+  /// Stream a page out of ascending entity ids, such as the ids of an index
+  pub fn scan_ids<V, R: CandidType>(
+    &self,
+    ids: impl DoubleEndedIterator<Item = EntityId>,
+    get: impl Fn(EntityId) -> Option<V>,
+    filter_map: impl FnMut(&V) -> Option<R>,
+  ) -> CursorPageResponse<R> {
+    let (start, end) = self.id_range();
+    let ids = ids.filter(move |id| (start, end).contains(id));
+    match self.direction {
+      SortDirection::Asc => self.collect(ids.filter_map(|id| get(id).map(|value| (id, value))), filter_map),
+      SortDirection::Desc => self.collect(ids.rev().filter_map(|id| get(id).map(|value| (id, value))), filter_map),
+    }
+  }
+
+  /// Stream a page out of the entity ids after the cursor that are already in page order, such as a page of an index
+  pub fn scan_page_ids<V, R: CandidType>(
+    &self,
+    ids: impl Iterator<Item = EntityId>,
+    get: impl Fn(EntityId) -> Option<V>,
+    filter_map: impl FnMut(&V) -> Option<R>,
+  ) -> CursorPageResponse<R> {
+    self.collect(ids.filter_map(|id| get(id).map(|value| (id, value))), filter_map)
+  }
+
+  fn collect<V, R: CandidType>(
+    &self,
+    entries: impl Iterator<Item = (EntityId, V)>,
+    mut filter_map: impl FnMut(&V) -> Option<R>,
+  ) -> CursorPageResponse<R> {
+    let mut records = Vec::new();
+    let mut last_id = None;
+    let mut next_cursor = None;
+
+    for (scanned, (id, value)) in entries.enumerate() {
+      if let Some(record) = filter_map(&value) {
+        if records.len() == self.limit {
+          next_cursor = last_id.map(|last_id| self.cursor(last_id));
+          break;
+        }
+        records.push(record);
+      }
+      last_id = Some(id);
+
+      if scanned + 1 >= MAX_CURSOR_PAGE_SCAN {
+        next_cursor = Some(self.cursor(id));
+        break;
+      }
+    }
+
+    CursorPageResponse {
+      sort_field: self.sort_field.clone(),
+      direction: self.direction,
+      records,
+      next_cursor,
+    }
+  }
+
+  fn cursor(&self, last_id: EntityId) -> String {
+    PageCursor {
+      sort_field: self.sort_field.clone(),
+      direction: self.direction,
+      last_id,
+    }
+    .encode()
   }
 }
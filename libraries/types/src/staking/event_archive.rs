@@ -0,0 +1,71 @@
+use std::borrow::Cow;
+
+use candid::{define_function, CandidType, Decode, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{Deserialize, Serialize};
+
+use crate::{CanisterId, TimestampNanos};
+
+use super::StakingEventLogId;
+
+/// Event logs returned by one call to an archive canister
+pub const MAX_ARCHIVED_EVENT_LOGS_PER_QUERY: u64 = 2000;
+
+/// An event log moved from the staking canister to an event archive canister
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct ArchivedEventLog {
+  pub id: StakingEventLogId,
+  pub event_time: TimestampNanos,
+  /// The person who triggered the event
+  pub principal: Principal,
+  /// The event log in the candid encoding of the staking canister `EventLog` type
+  #[serde(with = "serde_bytes")]
+  pub event_log: Vec<u8>,
+}
+
+impl Storable for ArchivedEventLog {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Range of the archived event logs to read, from the id `start`
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct GetEventLogsArgs {
+  pub start: StakingEventLogId,
+  /// Capped at MAX_ARCHIVED_EVENT_LOGS_PER_QUERY
+  pub length: u64,
+}
+
+pub type ArchivedEventLogs = Vec<ArchivedEventLog>;
+
+define_function!(pub QueryArchivedEventLogsFn : (GetEventLogsArgs) -> (ArchivedEventLogs) query);
+
+/// Event logs that were moved to an archive canister, read them by calling `callback` with `start` and `length`
+#[derive(Debug, Clone, Deserialize, CandidType)]
+pub struct ArchivedEventLogRange {
+  pub start: StakingEventLogId,
+  pub length: u64,
+  pub callback: QueryArchivedEventLogsFn,
+}
+
+/// Init argument of an event archive canister
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EventArchiveInitArgs {
+  /// The only canister allowed to append event logs
+  pub staking_canister_id: CanisterId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EventArchiveInfoVo {
+  pub staking_canister_id: CanisterId,
+  pub first_id: Option<StakingEventLogId>,
+  pub last_id: Option<StakingEventLogId>,
+  pub total: u64,
+}
@@ -1,5 +1,7 @@
 use crate::EntityId;
 
+pub mod event_archive;
+
 /// Each moduleIDAlias，Avoid confusion，Enhanced readability
 pub type StakingAccountId = EntityId;
 pub type StakingPoolId = EntityId;
@@ -7,3 +9,4 @@ pub type StakingEventLogId = EntityId;
 pub type StakingRewardId = EntityId;
 pub type SubscriptionId = EntityId;
 pub type PoolTransactionRecordId = EntityId;
+pub type ExternalDepositId = EntityId;
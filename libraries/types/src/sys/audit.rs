@@ -0,0 +1,86 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::TimestampNanos;
+
+/// The admin audit log keeps only the newest entries, older ones are dropped when a call is recorded
+pub const MAX_ADMIN_AUDIT_ENTRIES: u64 = 10_000;
+
+/// Outcome of an audited admin call
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum AdminAuditOutcome {
+  /// The call began and has not returned yet, it stays so when the call trapped after an await
+  Started,
+  /// The call completed
+  Succeeded,
+  /// The call returned an error
+  Failed(String),
+}
+
+/// Record of an admin call guarded by a permission
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AdminAuditEntry {
+  pub caller: Option<Principal>,
+  pub permission_code: Option<String>,
+  /// Name of the called method
+  pub method: Option<String>,
+  /// SHA-256 of the candid encoded arguments, hex encoded
+  pub args_hash: Option<String>,
+  pub outcome: Option<AdminAuditOutcome>,
+  pub called_at: Option<TimestampNanos>,
+}
+
+impl Storable for AdminAuditEntry {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AdminAuditQueryParams {
+  pub caller: Option<Principal>,
+  pub method: Option<String>,
+  pub permission_code: Option<String>,
+  pub start_time: Option<TimestampNanos>,
+  pub end_time: Option<TimestampNanos>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct AdminAuditEntryVo {
+  pub id: u64,
+  pub caller: Principal,
+  pub permission_code: String,
+  pub method: String,
+  pub args_hash: String,
+  pub outcome: AdminAuditOutcome,
+  pub called_at: TimestampNanos,
+}
+
+impl AdminAuditEntryVo {
+  pub fn from_entry(id: u64, entry: &AdminAuditEntry) -> Self {
+    Self {
+      id,
+      caller: entry.caller.unwrap_or(Principal::anonymous()),
+      permission_code: entry.permission_code.clone().unwrap_or_default(),
+      method: entry.method.clone().unwrap_or_default(),
+      args_hash: entry.args_hash.clone().unwrap_or_default(),
+      outcome: entry.outcome.clone().unwrap_or(AdminAuditOutcome::Succeeded),
+      called_at: entry.called_at.unwrap_or_default(),
+    }
+  }
+}
+
+/// Hash the candid encoded arguments of an audited call
+pub fn hash_audit_args(encoded_args: &[u8]) -> String {
+  Sha256::digest(encoded_args).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -1,7 +1,10 @@
-use candid::CandidType;
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::{storable::Bound, Storable};
 use serde::{Deserialize, Serialize};
 
-use crate::UserId;
+use crate::{TimestampNanos, UserId};
 
 use super::dict::transfer_structures::DictVo;
 
@@ -27,8 +30,99 @@ impl UserRolePermissionVo {
   }
 }
 
+/// Permissions granted by a role, a permission code ending with `*` grants every permission under it, such as `staking::pool::*`
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct RolePermissionVo {
+  pub role_code: RoleCode,
+  pub permission_codes: Vec<PermissionCode>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
 pub struct SystemConfig {
   pub user_role_permissions: Vec<UserRolePermissionVo>,
   pub dicts: Vec<DictVo>,
+  /// Role definitions, users are granted the permissions of their roles
+  pub role_permissions: Option<Vec<RolePermissionVo>>,
+}
+
+/// Error returned by the permission guarded endpoints when the caller lacks the permission
+pub trait PermissionDenied {
+  fn permission_denied(permission_code: &str) -> Self;
+}
+
+impl PermissionDenied for String {
+  fn permission_denied(permission_code: &str) -> Self {
+    format!("Caller does not have permission to call \"{}\"", permission_code)
+  }
+}
+
+/// Whether a permission code is granted by a granted permission code.
+/// `*` grants every permission, and `a::b::*` grants `a::b` and every permission under it.
+pub fn permission_code_matches(granted: &str, requested: &str) -> bool {
+  if granted == requested || granted == "*" {
+    return true;
+  }
+
+  match granted.strip_suffix("::*") {
+    Some(prefix) => requested == prefix || requested.strip_prefix(prefix).is_some_and(|rest| rest.starts_with("::")),
+    None => false,
+  }
+}
+
+/// The last received system config, persisted so the caches can be restored after an upgrade
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct SystemConfigSnapshot {
+  /// Incremented every time a system config is received
+  pub version: Option<u64>,
+  /// Time the system config was received
+  pub received_at: Option<TimestampNanos>,
+  pub config: Option<SystemConfig>,
+}
+
+impl SystemConfigSnapshot {
+  pub fn get_version(&self) -> u64 {
+    self.version.unwrap_or_default()
+  }
+
+  pub fn get_received_at(&self) -> TimestampNanos {
+    self.received_at.unwrap_or_default()
+  }
+}
+
+impl Storable for SystemConfigSnapshot {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SystemConfigStatusVo {
+  /// Version of the system config in use, 0 if no system config has been received
+  pub version: u64,
+  /// Time the system config in use was received
+  pub received_at: TimestampNanos,
+  /// Number of users with role permissions
+  pub user_count: u32,
+  /// Number of dictionaries
+  pub dict_count: u32,
+}
+
+/// Explains whether a principal holds a permission
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct PermissionExplanationVo {
+  pub principal_id: UserId,
+  pub permission_code: PermissionCode,
+  pub granted: bool,
+  /// Role that grants the permission，None when it is granted to the user directly or not granted
+  pub granted_by_role: Option<RoleCode>,
+  /// Permission code that matches the requested permission code, it can be a wildcard
+  pub matched_permission_code: Option<PermissionCode>,
+  /// Human readable explanation
+  pub reason: String,
 }
@@ -0,0 +1,17 @@
+use candid::CandidType;
+use serde::{Deserialize, Serialize};
+
+use crate::TimestampNanos;
+
+/// A held entry guard
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct EntryGuardVo {
+  pub key: String,
+  pub acquired_at: TimestampNanos,
+  /// How long the guard has been held
+  pub held_nanos: u64,
+  /// Maximum hold time of the guard, None when the guard has no lease
+  pub lease_nanos: Option<u64>,
+  /// The guard is held beyond its lease, the next entry takes it over
+  pub expired: bool,
+}
@@ -0,0 +1,85 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Encode, Principal};
+use ic_stable_structures::{storable::Bound, Storable};
+use serde::{Deserialize, Serialize};
+
+use crate::TimestampNanos;
+
+/// Change made to an identity mapping
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, PartialEq)]
+pub enum IdentityMappingAction {
+  /// A new mapping was added
+  Added,
+  /// The mapped principal of an existing mapping was replaced
+  Replaced,
+  /// The mapping was removed
+  Removed,
+}
+
+/// A change of the identity mapping, kept as an audit trail
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct IdentityMappingEvent {
+  /// Identity mapping version produced by the change
+  pub version: Option<u64>,
+  pub action: Option<IdentityMappingAction>,
+  /// The principal calling the canister
+  pub from: Option<Principal>,
+  /// The principal it is mapped to after the change，None if removed
+  pub to: Option<Principal>,
+  /// The principal it was mapped to before the change，None if added
+  pub previous_to: Option<Principal>,
+  /// The principal that pushed the change
+  pub changed_by: Option<Principal>,
+  pub changed_at: Option<TimestampNanos>,
+}
+
+impl Storable for IdentityMappingEvent {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+/// Incremental identity mapping change, applied only on top of the version it was computed from
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct IdentityMappingDelta {
+  /// The identity mapping version the delta is based on
+  pub base_version: u64,
+  /// Mappings to add or replace
+  pub upserts: Vec<(Principal, Principal)>,
+  /// Principals whose mapping is removed
+  pub removals: Vec<Principal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct IdentityMappingEventVo {
+  pub id: u64,
+  pub version: u64,
+  pub action: IdentityMappingAction,
+  pub from: Principal,
+  pub to: Option<Principal>,
+  pub previous_to: Option<Principal>,
+  pub changed_by: Principal,
+  pub changed_at: TimestampNanos,
+}
+
+impl IdentityMappingEventVo {
+  pub fn from_event(id: u64, event: &IdentityMappingEvent) -> Self {
+    Self {
+      id,
+      version: event.version.unwrap_or_default(),
+      action: event.action.clone().unwrap_or(IdentityMappingAction::Added),
+      from: event.from.unwrap_or(Principal::anonymous()),
+      to: event.to,
+      previous_to: event.previous_to,
+      changed_by: event.changed_by.unwrap_or(Principal::anonymous()),
+      changed_at: event.changed_at.unwrap_or_default(),
+    }
+  }
+}
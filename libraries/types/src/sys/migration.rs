@@ -0,0 +1,250 @@
+use std::{borrow::Cow, cell::RefCell, ops::Bound as RangeBound, thread::LocalKey, time::Duration};
+
+use candid::{CandidType, Decode, Encode};
+use ic_stable_structures::{storable::Bound, Cell, StableBTreeMap, Storable};
+use serde::{Deserialize, Serialize};
+
+use crate::{stable_structures::Memory, TimestampNanos};
+
+/// Instructions a migration may spend in post_upgrade before it hands the rest over to timers
+const POST_UPGRADE_INSTRUCTION_BUDGET: u64 = 100_000_000_000;
+/// Instructions a migration may spend in a single timer message
+const TIMER_INSTRUCTION_BUDGET: u64 = 10_000_000_000;
+
+pub type SchemaMigrationStateCell = Cell<SchemaMigrationState, Memory>;
+
+/// Migrate the records after the cursor, the step must stop when the budget check returns true
+pub type MigrationStepFn = fn(cursor: Option<Vec<u8>>, budget_exhausted: &dyn Fn() -> bool) -> Result<MigrationProgress, String>;
+
+/// Schema version of the canister stable memory and the progress of the running migration
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType, Default)]
+pub struct SchemaMigrationState {
+  /// Version of the last migration step that completed
+  pub schema_version: Option<u32>,
+  /// Key of the last record migrated by the running step, in its storable encoding
+  pub cursor: Option<Vec<u8>>,
+  /// Records migrated by the running step
+  pub processed: Option<u64>,
+  pub started_at: Option<TimestampNanos>,
+  pub updated_at: Option<TimestampNanos>,
+  pub last_error: Option<String>,
+}
+
+impl Storable for SchemaMigrationState {
+  fn to_bytes(&self) -> Cow<[u8]> {
+    Cow::Owned(Encode!(self).unwrap())
+  }
+
+  fn from_bytes(bytes: Cow<[u8]>) -> Self {
+    Decode!(bytes.as_ref(), Self).unwrap()
+  }
+
+  const BOUND: Bound = Bound::Unbounded;
+}
+
+impl SchemaMigrationState {
+  pub fn get_schema_version(&self) -> u32 {
+    self.schema_version.unwrap_or_default()
+  }
+
+  pub fn get_processed(&self) -> u64 {
+    self.processed.unwrap_or_default()
+  }
+}
+
+/// Progress of one chunk of a migration step
+pub struct MigrationProgress {
+  /// Key to continue after, None when the step is complete
+  pub next_cursor: Option<Vec<u8>>,
+  /// Records migrated in this chunk
+  pub processed: u64,
+}
+
+/// An ordered migration step, it is run chunk by chunk until it returns no cursor
+pub struct MigrationStep {
+  /// Schema version after the step completes, the versions of the steps must be increasing
+  pub version: u32,
+  pub description: &'static str,
+  pub run: MigrationStepFn,
+}
+
+/// Migration status of the canister
+#[derive(Debug, Clone, Serialize, Deserialize, CandidType)]
+pub struct SchemaMigrationStatusVo {
+  pub schema_version: u32,
+  pub latest_version: u32,
+  /// Description of the step in progress, None when the schema is up to date
+  pub running_step: Option<String>,
+  pub processed: u64,
+  pub pending_steps: Vec<String>,
+  pub started_at: TimestampNanos,
+  pub updated_at: TimestampNanos,
+  pub last_error: Option<String>,
+}
+
+/// The migrations of a canister, declared as a static next to its schema version cell
+pub struct SchemaMigrator {
+  pub state: &'static LocalKey<RefCell<SchemaMigrationStateCell>>,
+  pub steps: &'static [MigrationStep],
+}
+
+impl SchemaMigrator {
+  pub fn latest_version(&self) -> u32 {
+    self.steps.last().map(|step| step.version).unwrap_or_default()
+  }
+
+  pub fn get_state(&self) -> SchemaMigrationState {
+    self.state.with(|cell| cell.borrow().get().clone())
+  }
+
+  fn set_state(&self, state: SchemaMigrationState) {
+    self.state.with(|cell| {
+      if let Err(e) = cell.borrow_mut().set(state) {
+        ic_cdk::println!("Failed to save the schema migration state: {:?}", e);
+      }
+    });
+  }
+
+  /// A fresh install starts on the latest schema, called from init
+  pub fn mark_latest(&self) {
+    self.set_state(SchemaMigrationState {
+      schema_version: Some(self.latest_version()),
+      ..Default::default()
+    });
+  }
+
+  /// The schema is up to date
+  pub fn is_completed(&self) -> bool {
+    self.get_state().get_schema_version() >= self.latest_version()
+  }
+
+  /// The step of the version and all steps before it have completed
+  pub fn is_migrated_to(&self, version: u32) -> bool {
+    self.get_state().get_schema_version() >= version
+  }
+
+  /// Run the pending steps from post_upgrade, the steps that do not finish in the upgrade continue on timers
+  pub fn run_on_upgrade(&'static self) {
+    self.run_pending(POST_UPGRADE_INSTRUCTION_BUDGET);
+  }
+
+  /// Continue the pending steps after a failed chunk was fixed or its cause went away
+  pub fn resume(&'static self) {
+    let mut state = self.get_state();
+    state.last_error = None;
+    self.set_state(state);
+    self.run_pending(TIMER_INSTRUCTION_BUDGET);
+  }
+
+  fn run_pending(&'static self, instruction_budget: u64) {
+    let budget_exhausted = || ic_cdk::api::instruction_counter() >= instruction_budget;
+
+    loop {
+      let mut state = self.get_state();
+      if state.last_error.is_some() {
+        return;
+      }
+
+      let Some(step) = self.steps.iter().find(|step| step.version > state.get_schema_version()) else {
+        return;
+      };
+
+      if state.started_at.is_none() {
+        ic_cdk::println!("Schema migration to version {} started: {}", step.version, step.description);
+        state.started_at = Some(ic_cdk::api::time());
+      }
+
+      match (step.run)(state.cursor.take(), &budget_exhausted) {
+        Ok(MigrationProgress { next_cursor, processed }) => {
+          state.processed = Some(state.get_processed() + processed);
+          state.updated_at = Some(ic_cdk::api::time());
+          match next_cursor {
+            Some(cursor) => state.cursor = Some(cursor),
+            None => {
+              ic_cdk::println!("Schema migration to version {} completed, {} records migrated", step.version, state.get_processed());
+              state = SchemaMigrationState {
+                schema_version: Some(step.version),
+                ..Default::default()
+              };
+            }
+          }
+        }
+        Err(e) => {
+          ic_cdk::println!("Schema migration to version {} failed: {}", step.version, e);
+          state.last_error = Some(e);
+          state.updated_at = Some(ic_cdk::api::time());
+        }
+      }
+      self.set_state(state);
+
+      if budget_exhausted() {
+        // Leave the rest to a fresh message with its own instruction limit
+        ic_cdk_timers::set_timer(Duration::ZERO, move || self.run_pending(TIMER_INSTRUCTION_BUDGET));
+        return;
+      }
+    }
+  }
+
+  pub fn status(&self) -> SchemaMigrationStatusVo {
+    let state = self.get_state();
+    let schema_version = state.get_schema_version();
+    let mut pending_steps = self
+      .steps
+      .iter()
+      .filter(|step| step.version > schema_version)
+      .map(|step| format!("{}: {}", step.version, step.description));
+
+    SchemaMigrationStatusVo {
+      schema_version,
+      latest_version: self.latest_version(),
+      running_step: pending_steps.next(),
+      processed: state.get_processed(),
+      pending_steps: pending_steps.collect(),
+      started_at: state.started_at.unwrap_or_default(),
+      updated_at: state.updated_at.unwrap_or_default(),
+      last_error: state.last_error,
+    }
+  }
+}
+
+/// Rewrite the records of a map after the cursor until the budget runs out,
+/// `migrate` returns the new value of a record, or None to keep it as it is.
+/// Each record is read and written before the budget is checked, so the writes are counted against the budget too.
+pub fn migrate_map_chunk<K, V>(
+  map: &mut StableBTreeMap<K, V, Memory>,
+  cursor: Option<Vec<u8>>,
+  budget_exhausted: &dyn Fn() -> bool,
+  mut migrate: impl FnMut(&K, V) -> Result<Option<V>, String>,
+) -> Result<MigrationProgress, String>
+where
+  K: Storable + Ord + Clone,
+  V: Storable,
+{
+  let mut start = match cursor {
+    Some(bytes) => RangeBound::Excluded(K::from_bytes(Cow::Owned(bytes))),
+    None => RangeBound::Unbounded,
+  };
+
+  let mut processed = 0;
+  while let Some((key, value)) = map.range((start.clone(), RangeBound::Unbounded)).next() {
+    if let Some(new_value) = migrate(&key, value)? {
+      map.insert(key.clone(), new_value);
+    }
+    processed += 1;
+    start = RangeBound::Excluded(key);
+
+    if budget_exhausted() {
+      break;
+    }
+  }
+
+  // A chunk that reached the end of the map completes the step
+  let next_cursor = match start {
+    RangeBound::Excluded(key) if map.range((RangeBound::Excluded(key.clone()), RangeBound::Unbounded)).next().is_some() => {
+      Some(key.to_bytes().into_owned())
+    }
+    _ => None,
+  };
+
+  Ok(MigrationProgress { next_cursor, processed })
+}
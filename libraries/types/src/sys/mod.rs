@@ -2,8 +2,20 @@ use candid::CandidType;
 use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumString};
 
+pub mod audit;
 pub mod config;
 pub mod dict;
+pub mod guard;
+pub mod identity_mapping;
+pub mod migration;
+
+/// Memory IDs 240 ~ 254 are reserved for the state kept by the shared library macros, canisters must not use them
+pub const SYSTEM_CONFIG_MEMORY_ID: u8 = 254;
+pub const IDENTITY_MAPPING_MEMORY_ID: u8 = 253;
+pub const IDENTITY_MAPPING_VERSION_MEMORY_ID: u8 = 252;
+pub const IDENTITY_MAPPING_EVENT_MEMORY_ID: u8 = 251;
+pub const ADMIN_AUDIT_LOG_MEMORY_ID: u8 = 250;
+pub const SCHEMA_MIGRATION_MEMORY_ID: u8 = 249;
 
 #[derive(EnumString, Display, Debug, Clone, Serialize, Deserialize, CandidType)]
 pub enum ExteralCanisterLabels {
@@ -23,6 +35,21 @@ pub enum ExteralCanisterLabels {
   Staking,
   #[strum(serialize = "Account")]
   Account,
+  #[strum(serialize = "ICP ledger")]
+  IcpLedger,
+  #[strum(serialize = "NNS governance")]
+  NnsGovernance,
+}
+
+impl ExteralCanisterLabels {
+  /// Canister ID on the IC mainnet of the system canisters, used when the system config does not set one
+  pub fn mainnet_canister_id(&self) -> Option<&'static str> {
+    match self {
+      ExteralCanisterLabels::IcpLedger => Some("ryjl3-tyaaa-aaaaa-aaaba-cai"),
+      ExteralCanisterLabels::NnsGovernance => Some("rrkah-fqaaa-aaaaa-aaaaq-cai"),
+      _ => None,
+    }
+  }
 }
 
 /// System switch, map system_switches dictionary in system configuration